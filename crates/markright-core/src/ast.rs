@@ -1,8 +1,8 @@
-use std::collections::HashMap;
-
 use comrak::nodes::{Ast, NodeValue};
 use comrak::{Arena, Options, parse_document};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+use crate::slug::{SlugStyle, Slugger};
 
 /// A serializable Markdown AST node.
 ///
@@ -17,6 +17,9 @@ pub struct MdNode {
     pub level: Option<u8>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
+    /// Section number of a heading, such as `3.2.1`, when numbering is on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub literal: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -41,6 +44,9 @@ pub struct MdNode {
     pub header: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub alignments: Option<Vec<String>>,
+    /// Classes from a heading's `{.class}` attribute block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub classes: Option<Vec<String>>,
 }
 
 impl MdNode {
@@ -50,6 +56,7 @@ impl MdNode {
             children: Vec::new(),
             level: None,
             id: None,
+            number: None,
             literal: None,
             url: None,
             title: None,
@@ -62,6 +69,7 @@ impl MdNode {
             checked: None,
             header: None,
             alignments: None,
+            classes: None,
         }
     }
 }
@@ -82,53 +90,465 @@ fn collect_text<'a>(node: &'a comrak::arena_tree::Node<'a, std::cell::RefCell<As
     text
 }
 
-/// Generate a URL-safe slug from heading text.
-fn slugify(text: &str) -> String {
-    text.to_lowercase()
-        .chars()
-        .map(|c| {
-            if c.is_alphanumeric() {
-                c
-            } else if c == ' ' || c == '-' {
-                '-'
-            } else {
-                '\0'
-            }
-        })
-        .filter(|&c| c != '\0')
-        .collect::<String>()
-        .split('-')
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<_>>()
-        .join("-")
+/// Id and classes from a heading's attribute block.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct HeadingAttrs {
+    id: Option<String>,
+    classes: Vec<String>,
 }
 
-/// Parse Markdown and return a serializable AST.
-pub fn serialize_ast(input: &str) -> MdNode {
-    serialize_ast_themed(input, "ocean")
+/// Split a trailing attribute block such as `{#install .lead}` off heading
+/// text, as Pandoc and `MkDocs` write it. Returns the text before the
+/// block, trimmed, and its attributes, or `None` if there is no block.
+///
+/// The block must follow a space and hold only `#id`, `.class` and
+/// `key=value` items. Key-value pairs are accepted but not kept.
+fn split_heading_attrs(text: &str) -> Option<(&str, HeadingAttrs)> {
+    let inner = text.trim_end().strip_suffix('}')?;
+    let open = inner.rfind('{')?;
+    let before = &inner[..open];
+    if !before.ends_with(char::is_whitespace) {
+        return None;
+    }
+    let mut attrs = HeadingAttrs::default();
+    let mut items = inner[open + 1..].split_whitespace().peekable();
+    items.peek()?;
+    for item in items {
+        if let Some(id) = item.strip_prefix('#').filter(|id| !id.is_empty()) {
+            attrs.id = Some(id.to_string());
+        } else if let Some(class) = item.strip_prefix('.').filter(|c| !c.is_empty()) {
+            attrs.classes.push(class.to_string());
+        } else if item.split_once('=').is_none_or(|(key, _)| key.is_empty()) {
+            return None;
+        }
+    }
+    Some((before.trim_end(), attrs))
 }
 
-/// Parse Markdown and return a serializable AST with a specific code theme.
-pub fn serialize_ast_themed(input: &str, code_theme: &str) -> MdNode {
-    let arena = Arena::new();
+/// A heading's text, id and classes, taking an attribute block at the end of
+/// its last text node into account. Also returns the length of the text to
+/// strip from that node to remove the block.
+fn heading_parts<'a>(
+    node: &'a comrak::arena_tree::Node<'a, std::cell::RefCell<Ast>>,
+    slugger: &mut Slugger,
+) -> (String, String, Vec<String>, usize) {
+    let text = collect_text(node);
+    let last_text_len = node
+        .last_child()
+        .map_or(0, |last| match &last.data.borrow().value {
+            NodeValue::Text(t) => t.len(),
+            _ => 0,
+        });
+    let split =
+        split_heading_attrs(&text).filter(|(before, _)| text.len() - before.len() <= last_text_len);
+    let Some((before, attrs)) = split else {
+        let id = slugger.slug(&text);
+        return (text, id, Vec::new(), 0);
+    };
+    let strip = text.len() - before.len();
+    let id = match attrs.id {
+        Some(id) => {
+            slugger.reserve(&id);
+            id
+        }
+        None => slugger.slug(before),
+    };
+    (before.to_string(), id, attrs.classes, strip)
+}
+
+/// Parser options shared by every parse, so positions and ids agree with the
+/// rendered document.
+fn parse_options<'c>() -> Options<'c> {
     let mut options = Options::default();
     options.extension.table = true;
     options.extension.strikethrough = true;
     options.extension.tasklist = true;
     options.extension.autolink = true;
     options.render.unsafe_ = true;
+    options
+}
+
+/// A heading located in the Markdown source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceHeading {
+    pub level: u8,
+    pub text: String,
+    /// The id the heading gets in the serialized AST.
+    pub id: String,
+    /// 1-based line of the input on which the heading starts.
+    pub line: usize,
+}
+
+/// The kind of block a line of Markdown belongs to.
+///
+/// As a search filter, `Code` without a language matches code blocks in any
+/// language.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BlockKind {
+    /// Paragraphs, list items, block quotes and anything else that is not
+    /// one of the other kinds.
+    Prose,
+    Heading,
+    /// A fenced or indented code block, with the first word of its info string.
+    Code {
+        #[serde(default)]
+        language: Option<String>,
+    },
+    Table,
+    /// The frontmatter block, which the parser never sees.
+    Frontmatter,
+}
+
+impl BlockKind {
+    /// Whether a block of kind `other` passes this kind used as a filter.
+    pub fn includes(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Code { language: want }, Self::Code { language: lang }) => match want {
+                None => true,
+                Some(want) => lang.as_ref().is_some_and(|l| l.eq_ignore_ascii_case(want)),
+            },
+            _ => self == other,
+        }
+    }
+}
+
+/// A leaf block located in the Markdown source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceBlock {
+    pub kind: BlockKind,
+    /// 1-based line of the input on which the block starts.
+    pub start_line: usize,
+    /// 1-based line of the input on which the block ends.
+    pub end_line: usize,
+    /// The first line is an opening code fence rather than content.
+    pub opening_fence: bool,
+    /// The last line is a closing code fence rather than content.
+    pub closing_fence: bool,
+    /// Plain text of each line of the block, with emphasis, link
+    /// destinations and HTML tags dropped. Table rows have their cells
+    /// separated by ` · `, and the delimiter row is empty. Empty for code
+    /// blocks.
+    pub text: Vec<String>,
+}
+
+/// The headings and leaf blocks of a Markdown document.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    pub headings: Vec<SourceHeading>,
+    /// Blocks in source order. They never overlap; blank lines and the
+    /// markers of lists and block quotes between them belong to no block.
+    pub blocks: Vec<SourceBlock>,
+}
+
+impl SourceMap {
+    /// The block spanning `line`, if any.
+    pub fn block_at(&self, line: usize) -> Option<&SourceBlock> {
+        self.block_index(line).map(|i| &self.blocks[i])
+    }
+
+    /// Index in [`Self::blocks`] of the block spanning `line`, if any.
+    pub fn block_index(&self, line: usize) -> Option<usize> {
+        let i = self.blocks.partition_point(|b| b.end_line < line);
+        self.blocks
+            .get(i)
+            .filter(|b| b.start_line <= line)
+            .map(|_| i)
+    }
+}
+
+/// Whether a node is one of the leaf blocks listed in [`SourceMap::blocks`].
+fn is_leaf_block(value: &NodeValue) -> bool {
+    matches!(
+        value,
+        NodeValue::Heading(_)
+            | NodeValue::CodeBlock(_)
+            | NodeValue::Table(_)
+            | NodeValue::Paragraph
+            | NodeValue::HtmlBlock(_)
+            | NodeValue::ThematicBreak
+    )
+}
+
+/// Map the headings and leaf blocks of a Markdown document to their source
+/// lines, along with the plain text of each line.
+///
+/// Heading ids match those assigned by [`serialize_ast_themed`] with the same
+/// slug style, but no AST is built and no code is highlighted, so this is
+/// cheap enough to run on many files.
+pub fn source_map(input: &str, slugs: SlugStyle) -> SourceMap {
+    let arena = Arena::new();
+    let options = parse_options();
+    let root = parse_document(&arena, input, &options);
+    let mut slugger = Slugger::new(slugs);
+    let mut lines: Option<Vec<&str>> = None;
+    let mut map = SourceMap::default();
+
+    for node in root.descendants() {
+        let data = node.data.borrow();
+        if !is_leaf_block(&data.value) {
+            continue;
+        }
+        let (start_line, end_line) = (data.sourcepos.start.line, data.sourcepos.end.line);
+        let mut block = SourceBlock {
+            kind: BlockKind::Prose,
+            start_line,
+            end_line,
+            opening_fence: false,
+            closing_fence: false,
+            text: vec![String::new(); end_line + 1 - start_line],
+        };
+        match &data.value {
+            NodeValue::Heading(h) => {
+                let level = h.level;
+                drop(data);
+                let (text, id, _, strip) = heading_parts(node, &mut slugger);
+                map.headings.push(SourceHeading {
+                    level,
+                    text,
+                    id,
+                    line: start_line,
+                });
+                block.kind = BlockKind::Heading;
+                push_inline_text(node, start_line, &mut block.text);
+                // The attribute block ends the last text node.
+                if let Some(last) = node.last_child() {
+                    let line = last.data.borrow().sourcepos.start.line;
+                    if let Some(text) = block.text.get_mut(line.saturating_sub(start_line)) {
+                        text.truncate(text.len().saturating_sub(strip));
+                    }
+                }
+            }
+            NodeValue::CodeBlock(cb) => {
+                block.text.clear();
+                let language = cb.info.split_whitespace().next().map(str::to_string);
+                block.kind = BlockKind::Code { language };
+                if cb.fenced {
+                    let lines = lines.get_or_insert_with(|| input.lines().collect());
+                    let fence = (cb.fence_char as char).to_string().repeat(cb.fence_length);
+                    block.opening_fence = true;
+                    block.closing_fence = end_line > start_line
+                        && lines.get(end_line - 1).is_some_and(|l| {
+                            let l = l.trim();
+                            l.starts_with(&fence)
+                                && l.trim_start_matches(cb.fence_char as char).is_empty()
+                        });
+                }
+            }
+            NodeValue::Table(_) => {
+                block.kind = BlockKind::Table;
+                drop(data);
+                for row in node.children() {
+                    let line = row.data.borrow().sourcepos.start.line;
+                    let cells: Vec<String> = row.children().map(collect_text).collect();
+                    if let Some(text) = block.text.get_mut(line - start_line) {
+                        *text = cells.join(" · ");
+                    }
+                }
+            }
+            NodeValue::HtmlBlock(html) => {
+                for (text, line) in block.text.iter_mut().zip(strip_tags(&html.literal).lines()) {
+                    *text = line.to_string();
+                }
+            }
+            NodeValue::Paragraph => {
+                drop(data);
+                push_inline_text(node, start_line, &mut block.text);
+            }
+            _ => {}
+        }
+        map.blocks.push(block);
+    }
+    map
+}
+
+/// Append the text of the inline descendants of `node` to the entries of
+/// `lines` for the source lines they start on, counting from `start_line`.
+///
+/// Lines are placed by source position rather than by counting line breaks,
+/// so inline HTML and code spans that span lines keep later text aligned.
+fn push_inline_text<'a>(
+    node: &'a comrak::arena_tree::Node<'a, std::cell::RefCell<Ast>>,
+    start_line: usize,
+    lines: &mut [String],
+) {
+    let mut current = 0;
+    for child in node.descendants().skip(1) {
+        let data = child.data.borrow();
+        let text = match &data.value {
+            NodeValue::Text(t) => t,
+            NodeValue::Code(c) => &c.literal,
+            _ => continue,
+        };
+        let line = data.sourcepos.start.line.saturating_sub(start_line);
+        current = line.clamp(current, lines.len().saturating_sub(1));
+        if let Some(entry) = lines.get_mut(current) {
+            entry.push_str(text);
+        }
+    }
+}
+
+/// The text of the paragraphs of a Markdown document, as runs of adjacent
+/// text nodes. Code spans, HTML and link destinations are left out, as are
+/// headings, tables and code blocks.
+pub fn prose_runs(input: &str) -> Vec<String> {
+    let arena = Arena::new();
+    let options = parse_options();
+    let root = parse_document(&arena, input, &options);
+    let is_text = |node: &comrak::arena_tree::Node<'_, std::cell::RefCell<Ast>>| {
+        matches!(node.data.borrow().value, NodeValue::Text(_))
+    };
+    let mut runs: Vec<String> = Vec::new();
+
+    for node in root.descendants() {
+        let data = node.data.borrow();
+        let NodeValue::Text(text) = &data.value else {
+            continue;
+        };
+        let in_paragraph = node
+            .ancestors()
+            .any(|a| matches!(a.data.borrow().value, NodeValue::Paragraph));
+        if !in_paragraph {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if node.previous_sibling().is_some_and(is_text) => run.push_str(text),
+            _ => runs.push(text.clone()),
+        }
+    }
+    runs
+}
 
+/// A link located in the Markdown source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLink {
+    /// The destination as written.
+    pub url: String,
+    /// Plain text of the link.
+    pub text: String,
+    /// 1-based line of the input on which the link starts.
+    pub line: usize,
+    /// Plain text of that line, as in [`SourceBlock::text`].
+    pub context: String,
+}
+
+/// List the links in the paragraphs, headings and tables of a Markdown
+/// document. Images are not included.
+pub fn source_links(input: &str) -> Vec<SourceLink> {
+    let arena = Arena::new();
+    let options = parse_options();
     let root = parse_document(&arena, input, &options);
-    let mut id_counts: HashMap<String, usize> = HashMap::new();
+    let mut links = Vec::new();
+
+    for node in root.descendants() {
+        let data = node.data.borrow();
+        let start_line = data.sourcepos.start.line;
+        let is_table = matches!(data.value, NodeValue::Table(_));
+        let is_text_block = matches!(data.value, NodeValue::Paragraph | NodeValue::Heading(_));
+        drop(data);
+        if is_text_block {
+            let mut text_lines = vec![String::new()];
+            let mut found = Vec::new();
+            inline_links(node, &mut text_lines, &mut found);
+            links.extend(found.into_iter().map(|(offset, url, text)| SourceLink {
+                url,
+                text,
+                line: start_line + offset,
+                context: text_lines[offset].clone(),
+            }));
+        } else if is_table {
+            // Rows take one line each, after the delimiter row.
+            for (i, row) in node.children().enumerate() {
+                let mut text_lines = vec![String::new()];
+                let mut found = Vec::new();
+                for (j, cell) in row.children().enumerate() {
+                    if j > 0 {
+                        text_lines[0].push_str(" · ");
+                    }
+                    inline_links(cell, &mut text_lines, &mut found);
+                }
+                let line = start_line + i + usize::from(i > 0);
+                links.extend(found.into_iter().map(|(_, url, text)| SourceLink {
+                    url,
+                    text,
+                    line,
+                    context: text_lines.concat(),
+                }));
+            }
+        }
+    }
+    links
+}
+
+/// Append the plain text of the inline children of `node` to `lines`,
+/// starting a new line at each line break, and record each link as its line
+/// offset, destination and text.
+fn inline_links<'a>(
+    node: &'a comrak::arena_tree::Node<'a, std::cell::RefCell<Ast>>,
+    lines: &mut Vec<String>,
+    found: &mut Vec<(usize, String, String)>,
+) {
+    for child in node.children() {
+        let data = child.data.borrow();
+        match &data.value {
+            NodeValue::Text(t) => lines.last_mut().unwrap().push_str(t),
+            NodeValue::Code(c) => lines.last_mut().unwrap().push_str(&c.literal),
+            NodeValue::SoftBreak | NodeValue::LineBreak => lines.push(String::new()),
+            NodeValue::HtmlInline(_) => {}
+            NodeValue::Link(link) => {
+                let url = link.url.clone();
+                drop(data);
+                let offset = lines.len() - 1;
+                let text = collect_text(child);
+                inline_links(child, lines, found);
+                found.push((offset, url, text));
+            }
+            _ => {
+                drop(data);
+                inline_links(child, lines, found);
+            }
+        }
+    }
+}
+
+/// Remove HTML tags, keeping the line breaks inside them.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            '\n' => text.push(c),
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
 
-    convert_node(root, &mut id_counts, code_theme)
+/// Parse Markdown and return a serializable AST.
+pub fn serialize_ast(input: &str) -> MdNode {
+    serialize_ast_themed(input, "ocean", SlugStyle::default())
+}
+
+/// Parse Markdown and return a serializable AST with a specific code theme,
+/// giving headings ids in the `slugs` style.
+pub fn serialize_ast_themed(input: &str, code_theme: &str, slugs: SlugStyle) -> MdNode {
+    let arena = Arena::new();
+    let options = parse_options();
+
+    let root = parse_document(&arena, input, &options);
+    let mut slugger = Slugger::new(slugs);
+
+    convert_node(root, &mut slugger, Some(code_theme))
 }
 
 #[allow(clippy::too_many_lines)]
 fn convert_node<'a>(
     node: &'a comrak::arena_tree::Node<'a, std::cell::RefCell<Ast>>,
-    id_counts: &mut HashMap<String, usize>,
-    code_theme: &str,
+    slugger: &mut Slugger,
+    code_theme: Option<&str>,
 ) -> MdNode {
     let data = node.data.borrow();
     let mut md_node = match &data.value {
@@ -136,24 +556,21 @@ fn convert_node<'a>(
         NodeValue::Heading(h) => {
             let level = h.level;
             drop(data);
-            let text = collect_text(node);
-            let base_slug = slugify(&text);
-            let count = id_counts.entry(base_slug.clone()).or_insert(0);
-            let id = if *count == 0 {
-                base_slug
-            } else {
-                format!("{base_slug}-{count}")
-            };
-            let entry = id_counts.get_mut(&slugify(&text)).unwrap();
-            *entry += 1;
+            let (_, id, classes, strip) = heading_parts(node, slugger);
 
             let mut n = MdNode::new("Heading");
             n.level = Some(level);
             n.id = Some(id);
             n.children = node
                 .children()
-                .map(|c| convert_node(c, id_counts, code_theme))
+                .map(|c| convert_node(c, slugger, code_theme))
                 .collect();
+            if let Some(literal) = n.children.last_mut().and_then(|c| c.literal.as_mut()) {
+                literal.truncate(literal.len() - strip);
+            }
+            if !classes.is_empty() {
+                n.classes = Some(classes);
+            }
             return n;
         }
         NodeValue::Paragraph => MdNode::new("Paragraph"),
@@ -177,7 +594,10 @@ fn convert_node<'a>(
             let lang = cb.info.split_whitespace().next().unwrap_or("").to_string();
             if !lang.is_empty() {
                 n.info = Some(lang.clone());
-                if let Ok((dark, light)) = markright_syntax::highlight_pair(&cb.literal, &lang, code_theme) {
+                let highlighted = code_theme.and_then(|theme| {
+                    markright_syntax::highlight_pair(&cb.literal, &lang, theme).ok()
+                });
+                if let Some((dark, light)) = highlighted {
                     n.highlighted_html = Some(dark);
                     n.highlighted_html_light = Some(light);
                 }
@@ -261,7 +681,7 @@ fn convert_node<'a>(
 
     md_node.children = node
         .children()
-        .map(|c| convert_node(c, id_counts, code_theme))
+        .map(|c| convert_node(c, slugger, code_theme))
         .collect();
 
     md_node
@@ -294,6 +714,49 @@ mod tests {
         assert_eq!(ids, vec!["foo", "foo-1", "foo-2"]);
     }
 
+    #[test]
+    fn test_heading_attributes() {
+        let input = "## Installation {#install .lead .wide}\n\n## Install\n\n\
+            # Plain {not attrs}\n\n# Code `{#x}`\n\n# *Styled* {.note data-x=1}\n\n\
+            # Glued{#glued}\n";
+        let ast = serialize_ast(input);
+        let headings: Vec<(&str, &str, Option<&Vec<String>>)> = ast
+            .children
+            .iter()
+            .map(|n| {
+                let last = n.children.last().unwrap();
+                (
+                    n.id.as_deref().unwrap(),
+                    last.literal.as_deref().unwrap(),
+                    n.classes.as_ref(),
+                )
+            })
+            .collect();
+        let classes = |names: &[&str]| names.iter().map(|c| (*c).to_string()).collect();
+        let lead: Vec<String> = classes(&["lead", "wide"]);
+        let note: Vec<String> = classes(&["note"]);
+        assert_eq!(
+            headings,
+            vec![
+                ("install", "Installation", Some(&lead)),
+                ("install-1", "Install", None),
+                ("plain-not-attrs", "Plain {not attrs}", None),
+                ("code-x", "{#x}", None),
+                ("styled", "", Some(&note)),
+                ("gluedglued", "Glued{#glued}", None),
+            ]
+        );
+
+        let map = source_map(input, SlugStyle::default());
+        let texts: Vec<(&str, &str)> = map
+            .headings
+            .iter()
+            .map(|h| (h.id.as_str(), h.text.as_str()))
+            .collect();
+        assert_eq!(texts[0], ("install", "Installation"));
+        assert_eq!(texts[4], ("styled", "Styled"));
+    }
+
     #[test]
     fn test_code_block() {
         let ast = serialize_ast("```rust\nfn main() {}\n```");
@@ -332,9 +795,123 @@ mod tests {
     }
 
     #[test]
-    fn test_slugify() {
-        assert_eq!(slugify("Hello World"), "hello-world");
-        assert_eq!(slugify("What's New?"), "whats-new");
-        assert_eq!(slugify("  Multiple   Spaces  "), "multiple-spaces");
+    fn test_source_headings_match_ast_ids() {
+        let input = "# Intro\n\ntext\n\nSetup\n-----\n\n## Intro\n\n```\n# not a heading\n```\n";
+        let headings = source_map(input, SlugStyle::default()).headings;
+        let summary: Vec<(u8, &str, &str, usize)> = headings
+            .iter()
+            .map(|h| (h.level, h.text.as_str(), h.id.as_str(), h.line))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, "Intro", "intro", 1),
+                (2, "Setup", "setup", 5),
+                (2, "Intro", "intro-1", 8),
+            ]
+        );
+
+        let toc = crate::toc::extract_toc_from_ast(&serialize_ast(input));
+        let ids: Vec<&str> = toc.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["intro", "setup", "intro-1"]);
+    }
+
+    #[test]
+    fn test_source_map_blocks() {
+        let input = "# Title\n\nSome *prose*.\n\n```rust\nfn main() {}\n```\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n- item\n\nafter\n\n    indented\n";
+        let map = source_map(input, SlugStyle::default());
+        let summary: Vec<(&BlockKind, usize, usize)> = map
+            .blocks
+            .iter()
+            .map(|b| (&b.kind, b.start_line, b.end_line))
+            .collect();
+        let rust = BlockKind::Code {
+            language: Some("rust".to_string()),
+        };
+        let indented = BlockKind::Code { language: None };
+        assert_eq!(
+            summary,
+            vec![
+                (&BlockKind::Heading, 1, 1),
+                (&BlockKind::Prose, 3, 3),
+                (&rust, 5, 7),
+                (&BlockKind::Table, 9, 11),
+                (&BlockKind::Prose, 13, 13),
+                (&BlockKind::Prose, 15, 15),
+                (&indented, 17, 17),
+            ]
+        );
+        assert!(map.blocks[2].opening_fence && map.blocks[2].closing_fence);
+        assert!(!map.blocks[6].opening_fence);
+        assert_eq!(map.block_at(6).map(|b| &b.kind), Some(&rust));
+        assert_eq!(map.block_at(8), None);
+    }
+
+    #[test]
+    fn test_plain_lines() {
+        let input = "Some **bold** and [a link](x.md)\nwith <kbd>Ctrl</kbd> `code`\n\n\
+            | a | *b* |\n|---|---|\n| 1 | 2 |\n\n<div class=\"note\">\nHeads up\n</div>\n";
+        let map = source_map(input, SlugStyle::default());
+        let lines: Vec<&[String]> = map.blocks.iter().map(|b| b.text.as_slice()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                vec!["Some bold and a link", "with Ctrl code"],
+                vec!["a · b", "", "1 · 2"],
+                vec!["", "Heads up", ""],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plain_lines_after_multiline_html() {
+        let input = "# Setup {#setup}\n\nPress <kbd\nclass=\"key\">Ctrl</kbd> and\n`a\nb` then\nthe **end**\n";
+        let map = source_map(input, SlugStyle::default());
+        let lines: Vec<&[String]> = map.blocks.iter().map(|b| b.text.as_slice()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                vec!["Setup"],
+                vec!["Press ", "Ctrl and", "a b", " then", "the end"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_source_links() {
+        let input = "# See [Intro](intro.md)\n\nRead the\n[setup *guide*](docs/setup.md#linux) \
+            first.\n\n![logo](logo.png)\n\n| a | b |\n|---|---|\n| x | [B](b.md) |\n";
+        let found = source_links(input);
+        let links: Vec<(&str, &str, usize, &str)> = found
+            .iter()
+            .map(|l| (l.url.as_str(), l.text.as_str(), l.line, l.context.as_str()))
+            .collect();
+        assert_eq!(
+            links,
+            vec![
+                ("intro.md", "Intro", 1, "See Intro"),
+                (
+                    "docs/setup.md#linux",
+                    "setup guide",
+                    4,
+                    "setup guide first."
+                ),
+                ("b.md", "B", 10, "x · B"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_block_kind_filter() {
+        let any_code = BlockKind::Code { language: None };
+        let rust = BlockKind::Code {
+            language: Some("rust".to_string()),
+        };
+        assert!(any_code.includes(&rust));
+        assert!(rust.includes(&BlockKind::Code {
+            language: Some("Rust".to_string())
+        }));
+        assert!(!rust.includes(&any_code));
+        assert!(!BlockKind::Prose.includes(&BlockKind::Heading));
     }
 }
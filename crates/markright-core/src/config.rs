@@ -1,8 +1,15 @@
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
+use crate::slug::SlugStyle;
+use crate::toc::NumberingStyle;
+
+/// Name of the file holding a folder's [`FolderConfig`].
+pub const FOLDER_CONFIG_FILE: &str = ".markright.json";
+
 /// Application configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -20,6 +27,8 @@ pub struct AppConfig {
     pub zoom: f32,
     pub content_width: String,
     pub code_theme: String,
+    /// Heading numbering for documents that don't set it in frontmatter.
+    pub heading_numbering: NumberingStyle,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,6 +55,7 @@ impl Default for AppConfig {
             zoom: 100.0,
             content_width: "default".to_owned(),
             code_theme: "ocean".to_owned(),
+            heading_numbering: NumberingStyle::None,
         }
     }
 }
@@ -73,6 +83,115 @@ impl AppConfig {
     }
 }
 
+/// Settings for the documents of a folder and its subfolders, read from a
+/// `.markright.json` file in the folder.
+///
+/// Unset fields are inherited from the nearest folder above that sets them,
+/// up to the open root folder.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FolderConfig {
+    /// How heading ids are generated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slug_style: Option<SlugStyle>,
+    /// JSON Schema file the frontmatter of documents must match. Relative
+    /// paths are resolved against the folder of the config file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frontmatter_schema: Option<PathBuf>,
+    /// Why config files of this folder or the folders above it could not be
+    /// read. Their settings are left out, so they are reported rather than
+    /// ignored.
+    #[serde(skip)]
+    pub errors: Vec<String>,
+}
+
+impl FolderConfig {
+    /// Load the config file of `dir`. A missing file gives an empty config;
+    /// one that cannot be read or parsed gives an empty config with the
+    /// reason in [`Self::errors`].
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(FOLDER_CONFIG_FILE);
+        let failed = |message: String| Self {
+            errors: vec![format!("{}: {message}", path.display())],
+            ..Self::default()
+        };
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => return failed(e.to_string()),
+        };
+        let mut config: Self = match serde_json::from_str(&data) {
+            Ok(config) => config,
+            Err(e) => return failed(e.to_string()),
+        };
+        config.frontmatter_schema = config.frontmatter_schema.map(|p| dir.join(p));
+        config
+    }
+
+    /// The config that applies to the file at `path`, from the config files
+    /// of its folder and the folders above it up to `root`. Without a root,
+    /// only the file's own folder is read.
+    pub fn for_file(root: Option<&Path>, path: &Path) -> Self {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        FolderConfigs::new(root.unwrap_or(dir)).for_file(path)
+    }
+
+    /// The slug style, or the default if no folder sets one.
+    pub fn slug_style(&self) -> SlugStyle {
+        self.slug_style.unwrap_or_default()
+    }
+
+    /// Take the fields this config leaves unset from `parent`.
+    fn inherit(&mut self, parent: &Self) {
+        self.slug_style = self.slug_style.or(parent.slug_style);
+        if self.frontmatter_schema.is_none() {
+            self.frontmatter_schema
+                .clone_from(&parent.frontmatter_schema);
+        }
+        self.errors.extend(parent.errors.iter().cloned());
+    }
+}
+
+/// Resolves the [`FolderConfig`] of many files under one root, reading each
+/// folder's config file once.
+#[derive(Debug)]
+pub struct FolderConfigs {
+    root: PathBuf,
+    /// Resolved config of each folder read so far.
+    folders: HashMap<PathBuf, FolderConfig>,
+}
+
+impl FolderConfigs {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            folders: HashMap::new(),
+        }
+    }
+
+    /// The config that applies to the file at `path`.
+    pub fn for_file(&mut self, path: &Path) -> FolderConfig {
+        self.for_dir(path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// The config that applies to the files in `dir`.
+    pub fn for_dir(&mut self, dir: &Path) -> FolderConfig {
+        if let Some(config) = self.folders.get(dir) {
+            return config.clone();
+        }
+        let mut config = FolderConfig::load(dir);
+        let parent = dir
+            .parent()
+            .filter(|_| dir != self.root && dir.starts_with(&self.root));
+        if let Some(parent) = parent {
+            let parent = self.for_dir(parent);
+            config.inherit(&parent);
+        }
+        self.folders.insert(dir.to_path_buf(), config.clone());
+        config
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,4 +264,49 @@ mod tests {
         assert_eq!(cfg.font_family_ui, "system-ui");
         assert_eq!(cfg.left_panel_width, 256);
     }
+
+    #[test]
+    fn folder_config_nearest_folder_wins() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("book/src")).unwrap();
+        fs::create_dir_all(root.join("notes")).unwrap();
+        fs::write(root.join(FOLDER_CONFIG_FILE), r#"{"slug_style":"github"}"#).unwrap();
+        fs::write(
+            root.join("book").join(FOLDER_CONFIG_FILE),
+            r#"{"slug_style":"mdbook"}"#,
+        )
+        .unwrap();
+        fs::write(root.join("notes").join(FOLDER_CONFIG_FILE), "{{{").unwrap();
+
+        let mut configs = FolderConfigs::new(root);
+        let style =
+            |configs: &mut FolderConfigs, rel: &str| configs.for_file(&root.join(rel)).slug_style();
+        assert_eq!(style(&mut configs, "README.md"), SlugStyle::Github);
+        assert_eq!(style(&mut configs, "book/src/intro.md"), SlugStyle::Mdbook);
+        assert_eq!(style(&mut configs, "notes/todo.md"), SlugStyle::Github);
+        assert!(configs.for_file(&root.join("README.md")).errors.is_empty());
+        assert_eq!(
+            configs.for_file(&root.join("notes/todo.md")).errors.len(),
+            1
+        );
+        assert_eq!(
+            FolderConfig::for_file(None, &root.join("book/src/intro.md")).slug_style(),
+            SlugStyle::Legacy
+        );
+    }
+
+    #[test]
+    fn folder_config_reports_mistyped_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(FOLDER_CONFIG_FILE),
+            r#"{"frontmater_schema": "schema.json"}"#,
+        )
+        .unwrap();
+        let config = FolderConfig::load(dir.path());
+        assert_eq!(config.frontmatter_schema, None);
+        assert_eq!(config.errors.len(), 1);
+        assert!(config.errors[0].contains("unknown field `frontmater_schema`"));
+    }
 }
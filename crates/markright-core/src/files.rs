@@ -0,0 +1,133 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::UNIX_EPOCH;
+
+/// Modification time of `path` in nanoseconds since the Unix epoch.
+pub(crate) fn modified_nanos(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some(u64::try_from(since_epoch.as_nanos()).unwrap_or(u64::MAX))
+}
+
+/// Modification time of `path` in milliseconds since the Unix epoch, which
+/// JavaScript numbers hold exactly.
+pub fn modified_millis(path: &Path) -> Option<u64> {
+    modified_nanos(path).map(|nanos| nanos / 1_000_000)
+}
+
+/// Whether `path` lies under `root` once both are canonicalized, so that
+/// `..` components and symbolic links cannot lead out of it.
+///
+/// # Errors
+///
+/// Returns an error if either path cannot be canonicalized, for example
+/// because it does not exist.
+pub fn is_within_root(root: &Path, path: &Path) -> io::Result<bool> {
+    Ok(path.canonicalize()?.starts_with(root.canonicalize()?))
+}
+
+/// Temporary files staged by this process, to give each a unique name.
+static STAGED: AtomicU64 = AtomicU64::new(0);
+
+/// Write `content` to a hidden file next to `path`, with the permissions of
+/// `path` if it exists, and return the hidden file's path. Renaming it over
+/// `path` then replaces the file in one step, so a crash never leaves it
+/// half written.
+///
+/// The hidden file's name is unique to this process and call, and it is
+/// created only if it does not exist yet, so writes to the same file that
+/// run at the same time never share a temporary file.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written. Nothing is left behind
+/// on error.
+pub fn stage_write(path: &Path, content: &str) -> io::Result<PathBuf> {
+    let name = path
+        .file_name()
+        .map_or_else(String::new, |n| n.to_string_lossy().to_string());
+    let unique = STAGED.fetch_add(1, Ordering::Relaxed);
+    let temp = path.with_file_name(format!(
+        ".{name}.{}-{unique}.markright-tmp",
+        std::process::id()
+    ));
+    let mut file = fs::File::options()
+        .write(true)
+        .create_new(true)
+        .open(&temp)?;
+    let result = file
+        .write_all(content.as_bytes())
+        .and_then(|()| match fs::metadata(path) {
+            Ok(metadata) => fs::set_permissions(&temp, metadata.permissions()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        });
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    Ok(temp)
+}
+
+/// Replace the content of the file at `path` in one step, or create it,
+/// through [`stage_write`].
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written.
+pub fn write_atomic(path: &Path, content: &str) -> io::Result<()> {
+    let temp = stage_write(path, content)?;
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_is_within_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("docs/a.md"), "a").unwrap();
+        fs::write(dir.path().join("outside.md"), "b").unwrap();
+
+        assert!(is_within_root(&root, &root.join("docs/../docs/a.md")).unwrap());
+        assert!(!is_within_root(&root, &root.join("docs/../../outside.md")).unwrap());
+        assert!(is_within_root(&root, &root.join("missing.md")).is_err());
+    }
+
+    #[test]
+    fn test_staged_files_are_unique() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.md");
+        fs::write(&path, "old").unwrap();
+        let first = stage_write(&path, "first").unwrap();
+        let second = stage_write(&path, "second").unwrap();
+        assert_ne!(first, second);
+        assert_eq!(fs::read_to_string(&first).unwrap(), "first");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "second");
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.md");
+        fs::write(&path, "old").unwrap();
+        write_atomic(&path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        write_atomic(&dir.path().join("new.md"), "x").unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("new.md")).unwrap(), "x");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        assert!(write_atomic(&dir.path().join("missing/a.md"), "x").is_err());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
@@ -1,47 +1,1004 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::files::{is_within_root, modified_millis, write_atomic};
+
+/// Language of a frontmatter block, told apart by its fence lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrontmatterFormat {
+    /// YAML between `---` lines.
+    #[default]
+    Yaml,
+    /// TOML between `+++` lines, as used by Hugo.
+    Toml,
+    /// A JSON object between `;;;` lines.
+    Json,
+}
+
+impl FrontmatterFormat {
+    /// The line that opens and closes a block in this format.
+    fn fence(self) -> &'static str {
+        match self {
+            Self::Yaml => "---",
+            Self::Toml => "+++",
+            Self::Json => ";;;",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Yaml => "YAML",
+            Self::Toml => "TOML",
+            Self::Json => "JSON",
+        }
+    }
+}
 
 /// Parsed frontmatter from a Markdown document.
 #[derive(Debug, Default, Serialize)]
 pub struct Frontmatter {
+    /// The `title` field, if it is a single value.
     pub title: Option<String>,
-    pub fields: HashMap<String, String>,
+    /// The top-level fields, with lists and nested maps kept as such.
+    pub fields: Map<String, Value>,
+    /// The language the block was written in.
+    pub format: FrontmatterFormat,
 }
 
-/// Strip YAML frontmatter from Markdown content.
-/// Returns the frontmatter (if present) and the remaining content.
-pub fn strip_frontmatter(content: &str) -> (Option<Frontmatter>, &str) {
+impl Frontmatter {
+    /// Look up a field's value.
+    pub fn value(&self, key: &str) -> Option<&Value> {
+        self.fields.get(key)
+    }
+
+    /// Look up a field as text. Numbers and booleans are given as text;
+    /// lists, maps and `null` are `None`.
+    pub fn get(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.value(key).and_then(scalar_text)
+    }
+
+    /// Look up a field as a list, accepting both `[a, b]` and `a, b`.
+    ///
+    /// A single value is returned as a one-element list.
+    pub fn list(&self, key: &str) -> Vec<String> {
+        let items: Vec<String> = match self.value(key) {
+            Some(Value::Array(items)) => items
+                .iter()
+                .filter_map(scalar_text)
+                .map(|item| item.trim().to_string())
+                .collect(),
+            Some(Value::String(text)) => text.split(',').map(|i| i.trim().to_string()).collect(),
+            Some(value) => scalar_text(value)
+                .into_iter()
+                .map(Cow::into_owned)
+                .collect(),
+            None => Vec::new(),
+        };
+        items.into_iter().filter(|item| !item.is_empty()).collect()
+    }
+
+    /// Look up a yes/no field: a boolean, or `yes`/`no`, `on`/`off` or
+    /// `true`/`false` in any case.
+    pub fn flag(&self, key: &str) -> Option<bool> {
+        match self.value(key)? {
+            Value::Bool(b) => Some(*b),
+            Value::String(text) => match text.trim().to_ascii_lowercase().as_str() {
+                "true" | "yes" | "on" => Some(true),
+                "false" | "no" | "off" => Some(false),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The `description` field.
+    pub fn description(&self) -> Option<Cow<'_, str>> {
+        self.get("description")
+    }
+
+    /// The names in the `author` and `authors` fields. Each may be a name,
+    /// a list of names, or a map with a `name`, or a list of such maps.
+    pub fn authors(&self) -> Vec<String> {
+        fn name(value: &Value) -> Option<String> {
+            match value {
+                Value::Object(map) => map.get("name").and_then(name),
+                value => scalar_text(value).map(|text| text.trim().to_string()),
+            }
+        }
+        let mut authors: Vec<String> = Vec::new();
+        for value in ["author", "authors"]
+            .iter()
+            .filter_map(|key| self.value(key))
+        {
+            let names: Vec<String> = match value {
+                Value::Array(items) => items.iter().filter_map(name).collect(),
+                value => name(value).into_iter().collect(),
+            };
+            for author in names {
+                if !author.is_empty() && !authors.contains(&author) {
+                    authors.push(author);
+                }
+            }
+        }
+        authors
+    }
+
+    /// The `date` field, if it is a valid date.
+    pub fn date(&self) -> Option<DocumentDate> {
+        self.get("date").and_then(|text| DocumentDate::parse(&text))
+    }
+
+    /// The `tags` field as a list.
+    pub fn tags(&self) -> Vec<String> {
+        self.list("tags")
+    }
+
+    /// Whether `draft` is set.
+    pub fn draft(&self) -> bool {
+        self.flag("draft").unwrap_or(false)
+    }
+
+    /// The document language from `lang` or `language`, if it is a
+    /// language tag such as `en` or `de-CH`.
+    pub fn lang(&self) -> Option<Cow<'_, str>> {
+        ["lang", "language"]
+            .iter()
+            .filter_map(|key| self.get(key))
+            .find(|lang| is_language_tag(lang))
+    }
+
+    /// Whether the document shows a table of contents, which `toc: false`
+    /// turns off.
+    pub fn toc(&self) -> bool {
+        self.flag("toc").unwrap_or(true)
+    }
+
+    /// Parse a block in `format` that starts on line `line` of its file.
+    fn parse(format: FrontmatterFormat, text: &str, line: usize) -> Result<Self, FrontmatterError> {
+        let fields = match format {
+            FrontmatterFormat::Yaml => yaml_fields(text, line)?,
+            FrontmatterFormat::Toml => toml_fields(text, line)?,
+            FrontmatterFormat::Json => json_fields(text, line)?,
+        };
+        let title = fields
+            .get("title")
+            .and_then(scalar_text)
+            .map(Cow::into_owned);
+        Ok(Self {
+            title,
+            fields,
+            format,
+        })
+    }
+}
+
+/// The frontmatter fields with a meaning of their own, read through the
+/// accessors of [`Frontmatter`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DocumentProperties {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub authors: Vec<String>,
+    pub date: Option<DocumentDate>,
+    pub tags: Vec<String>,
+    pub draft: bool,
+    /// Language for hyphenation and spellchecking.
+    pub lang: Option<String>,
+    /// Whether the table of contents is shown.
+    pub toc: bool,
+}
+
+impl DocumentProperties {
+    /// The properties set by `frontmatter`, with defaults for a document
+    /// without it.
+    pub fn from_frontmatter(frontmatter: Option<&Frontmatter>) -> Self {
+        let Some(fm) = frontmatter else {
+            return Self {
+                title: None,
+                description: None,
+                authors: Vec::new(),
+                date: None,
+                tags: Vec::new(),
+                draft: false,
+                lang: None,
+                toc: true,
+            };
+        };
+        Self {
+            title: fm.title.clone(),
+            description: fm.description().map(Cow::into_owned),
+            authors: fm.authors(),
+            date: fm.date(),
+            tags: fm.tags(),
+            draft: fm.draft(),
+            lang: fm.lang().map(Cow::into_owned),
+            toc: fm.toc(),
+        }
+    }
+}
+
+/// A date from frontmatter, such as `2024-03-01` or `2024-03-01T09:30:00Z`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DocumentDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    /// Hour, minute and second, if a time of day was given.
+    pub time: Option<[u8; 3]>,
+    /// Offset from UTC in minutes, if the time has one.
+    pub offset_minutes: Option<i16>,
+}
+
+impl DocumentDate {
+    /// Parse a date as written in YAML, TOML and RFC 3339: `YYYY-MM-DD`,
+    /// optionally followed by `T` or a space and a time of `HH:MM`,
+    /// `HH:MM:SS` or `HH:MM:SS.fraction`, then `Z` or an offset such as
+    /// `+02:00`. The fraction of a second is dropped.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (date, time) = match text.find(['T', 't', ' ']) {
+            Some(i) => (&text[..i], Some(text[i + 1..].trim_start())),
+            None => (text, None),
+        };
+        let mut parts = date.split('-');
+        let year = parts.next().filter(|y| y.len() == 4)?.parse().ok()?;
+        let month = two_digits(parts.next()?)?;
+        let day = two_digits(parts.next()?)?;
+        if parts.next().is_some() || day == 0 || day > days_in_month(year, month)? {
+            return None;
+        }
+        let (time, offset_minutes) = match time {
+            Some(time) => {
+                let (time, offset) = parse_time(time)?;
+                (Some(time), offset)
+            }
+            None => (None, None),
+        };
+        Some(Self {
+            year,
+            month,
+            day,
+            time,
+            offset_minutes,
+        })
+    }
+}
+
+fn two_digits(text: &str) -> Option<u8> {
+    if text.len() == 2 && text.bytes().all(|b| b.is_ascii_digit()) {
+        text.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn days_in_month(year: u16, month: u8) -> Option<u8> {
+    let leap = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => Some(31),
+        4 | 6 | 9 | 11 => Some(30),
+        2 if leap => Some(29),
+        2 => Some(28),
+        _ => None,
+    }
+}
+
+/// Parse `HH:MM[:SS[.fraction]]` and an optional offset into the hour,
+/// minute and second and the offset in minutes.
+fn parse_time(text: &str) -> Option<([u8; 3], Option<i16>)> {
+    let (clock, offset) = match text.find(['Z', 'z', '+', '-']) {
+        Some(i) => (text[..i].trim_end(), Some(parse_offset(&text[i..])?)),
+        None => (text, None),
+    };
+    let clock = match clock.split_once('.') {
+        Some((clock, fraction))
+            if !fraction.is_empty() && fraction.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            clock
+        }
+        Some(_) => return None,
+        None => clock,
+    };
+    let mut parts = clock.split(':');
+    let hour = two_digits(parts.next()?)?;
+    let minute = two_digits(parts.next()?)?;
+    let second = parts.next().map_or(Some(0), two_digits)?;
+    // A second of 60 is a leap second.
+    (parts.next().is_none() && hour < 24 && minute < 60 && second <= 60)
+        .then_some(([hour, minute, second], offset))
+}
+
+/// Parse `Z`, `+HH:MM` or `-HHMM` into minutes east of UTC.
+fn parse_offset(text: &str) -> Option<i16> {
+    if text.eq_ignore_ascii_case("z") {
+        return Some(0);
+    }
+    let (sign, rest) = match text.split_at_checked(1)? {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some(parts) => parts,
+        None => rest.split_at_checked(2)?,
+    };
+    let (hours, minutes) = (two_digits(hours)?, two_digits(minutes)?);
+    (hours < 24 && minutes < 60).then(|| sign * (i16::from(hours) * 60 + i16::from(minutes)))
+}
+
+/// Whether `text` looks like a BCP 47 language tag, such as `en`, `pt-BR`
+/// or `zh-Hant-TW`.
+fn is_language_tag(text: &str) -> bool {
+    let mut subtags = text.split('-');
+    subtags.next().is_some_and(|primary| {
+        (2..=8).contains(&primary.len()) && primary.bytes().all(|b| b.is_ascii_alphabetic())
+    }) && subtags
+        .all(|s| (1..=8).contains(&s.len()) && s.bytes().all(|b| b.is_ascii_alphanumeric()))
+}
+
+/// Error returned when a frontmatter block cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FrontmatterError {
+    /// 1-based line of the document the error was found on.
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for FrontmatterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "frontmatter error on line {}: {}",
+            self.line, self.message
+        )
+    }
+}
+
+impl std::error::Error for FrontmatterError {}
+
+fn not_a_map(line: usize) -> FrontmatterError {
+    FrontmatterError {
+        line,
+        message: "frontmatter is not a map of fields".to_string(),
+    }
+}
+
+/// Most values a YAML block may hold once its aliases are expanded. Each
+/// alias copies the value it refers to, so a few lines of nested aliases can
+/// otherwise expand to more values than fit in memory.
+const MAX_YAML_VALUES: u64 = 10_000;
+
+/// Most bytes of scalar text a YAML block may hold once its aliases are
+/// expanded, as an alias of a long string copies the whole string.
+const MAX_YAML_BYTES: u64 = 16 << 20;
+
+/// The values a YAML node expands to, and the bytes of scalar text in them.
+#[derive(Debug, Clone, Copy, Default)]
+struct Expansion {
+    values: u64,
+    bytes: u64,
+}
+
+impl Expansion {
+    /// A scalar, or the node of a sequence or map without its members.
+    fn node(bytes: usize) -> Self {
+        Self {
+            values: 1,
+            bytes: u64::try_from(bytes).unwrap_or(u64::MAX),
+        }
+    }
+
+    fn plus(self, other: Self) -> Self {
+        Self {
+            values: self.values.saturating_add(other.values),
+            bytes: self.bytes.saturating_add(other.bytes),
+        }
+    }
+
+    fn exceeds_limits(self) -> bool {
+        self.values > MAX_YAML_VALUES || self.bytes > MAX_YAML_BYTES
+    }
+}
+
+/// Measures what a YAML stream expands to, without building it.
+#[derive(Default)]
+struct ValueCounter {
+    /// Everything expanded so far, aliases included.
+    total: Expansion,
+    /// Anchor ids and expansions of the sequences and maps being read.
+    open: Vec<(usize, Expansion)>,
+    /// Expansion of each anchored value.
+    anchored: HashMap<usize, Expansion>,
+    /// Where the total first went over [`MAX_YAML_VALUES`] or [`MAX_YAML_BYTES`].
+    exceeded: Option<Marker>,
+}
+
+impl ValueCounter {
+    /// Count a value that expands to `size`, of which `new` has not been
+    /// counted yet.
+    fn add(&mut self, size: Expansion, new: Expansion, mark: Marker) {
+        self.total = self.total.plus(new);
+        if let Some((_, open)) = self.open.last_mut() {
+            *open = open.plus(size);
+        }
+        if self.total.exceeds_limits() && self.exceeded.is_none() {
+            self.exceeded = Some(mark);
+        }
+    }
+}
+
+impl MarkedEventReceiver for ValueCounter {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, _, anchor, _) => {
+                let size = Expansion::node(value.len());
+                if anchor > 0 {
+                    self.anchored.insert(anchor, size);
+                }
+                self.add(size, size, mark);
+            }
+            Event::Alias(anchor) => {
+                let size = self
+                    .anchored
+                    .get(&anchor)
+                    .copied()
+                    .unwrap_or(Expansion::node(0));
+                self.add(size, size, mark);
+            }
+            Event::SequenceStart(anchor, _) | Event::MappingStart(anchor, _) => {
+                self.open.push((anchor, Expansion::default()));
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                // The members were counted as they were read.
+                if let Some((anchor, members)) = self.open.pop() {
+                    let size = members.plus(Expansion::node(0));
+                    if anchor > 0 {
+                        self.anchored.insert(anchor, size);
+                    }
+                    self.add(size, Expansion::node(0), mark);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn yaml_fields(text: &str, line: usize) -> Result<Map<String, Value>, FrontmatterError> {
+    let scan_error = |e: yaml_rust2::ScanError| FrontmatterError {
+        line: line + e.marker().line().saturating_sub(1),
+        message: e.info().to_string(),
+    };
+    // Count the values first, as the loader expands aliases without limit.
+    let mut counter = ValueCounter::default();
+    Parser::new_from_str(text)
+        .load(&mut counter, true)
+        .map_err(scan_error)?;
+    if let Some(mark) = counter.exceeded {
+        return Err(FrontmatterError {
+            line: line + mark.line().saturating_sub(1),
+            message: format!(
+                "aliases expand to more than {MAX_YAML_VALUES} values or {} MiB of text",
+                MAX_YAML_BYTES >> 20
+            ),
+        });
+    }
+    let docs = YamlLoader::load_from_str(text).map_err(scan_error)?;
+    match docs.into_iter().next() {
+        None | Some(Yaml::Null) => Ok(Map::new()),
+        Some(Yaml::Hash(hash)) => Ok(hash
+            .into_iter()
+            .filter_map(|(key, value)| Some((key_text(&key)?, yaml_value(value))))
+            .collect()),
+        Some(_) => Err(not_a_map(line)),
+    }
+}
+
+fn toml_fields(text: &str, line: usize) -> Result<Map<String, Value>, FrontmatterError> {
+    let table: toml::Table = text
+        .parse()
+        .map_err(|e: toml::de::Error| FrontmatterError {
+            line: line
+                + e.span()
+                    .map_or(0, |span| text[..span.start].matches('\n').count()),
+            message: e.message().to_string(),
+        })?;
+    Ok(table
+        .into_iter()
+        .map(|(key, value)| (key, toml_value(value)))
+        .collect())
+}
+
+fn json_fields(text: &str, line: usize) -> Result<Map<String, Value>, FrontmatterError> {
+    if text.trim().is_empty() {
+        return Ok(Map::new());
+    }
+    match serde_json::from_str(text) {
+        Ok(Value::Object(fields)) => Ok(fields),
+        Ok(_) => Err(not_a_map(line)),
+        Err(e) => Err(FrontmatterError {
+            line: line + e.line().saturating_sub(1),
+            message: e.to_string(),
+        }),
+    }
+}
+
+/// The text of a single value, or `None` for lists, maps and `null`.
+fn scalar_text(value: &Value) -> Option<Cow<'_, str>> {
+    match value {
+        Value::String(text) => Some(Cow::Borrowed(text)),
+        Value::Number(n) => Some(Cow::Owned(n.to_string())),
+        Value::Bool(b) => Some(Cow::Owned(b.to_string())),
+        Value::Null | Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+/// A map key as text. Keys that are lists or maps are not supported.
+fn key_text(key: &Yaml) -> Option<String> {
+    match key {
+        Yaml::String(text) | Yaml::Real(text) => Some(text.clone()),
+        Yaml::Integer(n) => Some(n.to_string()),
+        Yaml::Boolean(b) => Some(b.to_string()),
+        Yaml::Null => Some("null".to_string()),
+        _ => None,
+    }
+}
+
+fn yaml_value(yaml: Yaml) -> Value {
+    match yaml {
+        Yaml::Real(ref text) => match yaml.as_f64().and_then(Number::from_f64) {
+            Some(n) => Value::Number(n),
+            // `.inf` and `.nan` have no JSON number.
+            None => Value::String(text.clone()),
+        },
+        Yaml::Integer(n) => Value::from(n),
+        Yaml::String(text) => Value::String(text),
+        Yaml::Boolean(b) => Value::Bool(b),
+        Yaml::Array(items) => Value::Array(items.into_iter().map(yaml_value).collect()),
+        Yaml::Hash(hash) => Value::Object(
+            hash.into_iter()
+                .filter_map(|(key, value)| Some((key_text(&key)?, yaml_value(value))))
+                .collect(),
+        ),
+        Yaml::Alias(_) | Yaml::Null | Yaml::BadValue => Value::Null,
+    }
+}
+
+fn toml_value(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(text) => Value::String(text),
+        toml::Value::Integer(n) => Value::from(n),
+        toml::Value::Float(f) => {
+            Number::from_f64(f).map_or_else(|| Value::String(f.to_string()), Value::Number)
+        }
+        toml::Value::Boolean(b) => Value::Bool(b),
+        // Dates are kept as written, as YAML dates are.
+        toml::Value::Datetime(date) => Value::String(date.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_value).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// A frontmatter block found at the start of a document.
+struct Block<'a> {
+    format: FrontmatterFormat,
+    /// The text between the fence lines.
+    text: &'a str,
+    /// Byte offset of `text` in the content.
+    start: usize,
+    /// Byte offset of the closing fence line.
+    close: usize,
+    /// 1-based line that `text` starts on.
+    line: usize,
+    /// The content after the closing fence.
+    rest: &'a str,
+}
+
+/// Find the frontmatter block between fence lines at the start of `content`.
+fn split_block(content: &str) -> Option<Block<'_>> {
     let trimmed = content.trim_start();
-    if !trimmed.starts_with("---") {
-        return (None, content);
-    }
-
-    // Find the closing ---
-    if let Some(end) = trimmed[3..].find("\n---") {
-        let yaml_block = &trimmed[3..end + 3].trim();
-        let rest = &trimmed[end + 3 + 4..]; // skip past "\n---"
-
-        let mut fm = Frontmatter::default();
-        for line in yaml_block.lines() {
-            if let Some((key, value)) = line.split_once(':') {
-                let key = key.trim().to_string();
-                let value = value.trim().to_string();
-                if key == "title" {
-                    fm.title = Some(value.trim_matches('"').trim_matches('\'').to_string());
+    let format = [
+        FrontmatterFormat::Yaml,
+        FrontmatterFormat::Toml,
+        FrontmatterFormat::Json,
+    ]
+    .into_iter()
+    .find(|f| trimmed.starts_with(f.fence()))?;
+    let fence = format.fence();
+    let rest = &trimmed[fence.len()..];
+    let open_end = rest.find('\n')?;
+    if !rest[..open_end].trim().is_empty() {
+        return None;
+    }
+    let start = content.len() - rest.len() + open_end + 1;
+    // Search from the newline ending the opening line, so an empty block
+    // closes straight away.
+    let end = start - 1
+        + content[start - 1..]
+            .match_indices('\n')
+            .find(|&(i, _)| content[start + i..].starts_with(fence))?
+            .0;
+    Some(Block {
+        format,
+        text: &content[start..end.max(start)],
+        start,
+        close: end + 1,
+        line: content[..start].matches('\n').count() + 1,
+        rest: &content[end + 1 + fence.len()..],
+    })
+}
+
+/// Split YAML, TOML or JSON frontmatter from Markdown content.
+///
+/// Returns the parsed frontmatter, `None` if there is none, and the content
+/// after it. The content is split off even if the block cannot be parsed.
+pub fn parse_frontmatter(content: &str) -> (Result<Option<Frontmatter>, FrontmatterError>, &str) {
+    match split_block(content) {
+        Some(block) => (
+            Frontmatter::parse(block.format, block.text, block.line).map(Some),
+            block.rest,
+        ),
+        None => (Ok(None), content),
+    }
+}
+
+/// Strip YAML, TOML or JSON frontmatter from Markdown content.
+/// Returns the frontmatter (if present and valid) and the remaining content.
+pub fn strip_frontmatter(content: &str) -> (Option<Frontmatter>, &str) {
+    let (frontmatter, rest) = parse_frontmatter(content);
+    (frontmatter.ok().flatten(), rest)
+}
+
+/// A change to one top-level frontmatter field.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum FieldEdit {
+    /// Set the field to `value`, adding it after the other fields if missing.
+    Set { key: String, value: Value },
+    /// Remove the field, if present.
+    Remove { key: String },
+    /// Add `value` to the list in the field, unless it is already there.
+    /// A missing field becomes a one-item list, and a single value or
+    /// `a, b` text becomes a list of its items.
+    Append { key: String, value: Value },
+}
+
+impl FieldEdit {
+    fn key(&self) -> &str {
+        match self {
+            Self::Set { key, .. } | Self::Remove { key } | Self::Append { key, .. } => key,
+        }
+    }
+}
+
+/// Error returned when frontmatter cannot be edited.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FrontmatterEditError {
+    /// The frontmatter does not parse, so it cannot be edited safely.
+    Parse { line: usize, message: String },
+    /// Only YAML frontmatter can be edited.
+    UnsupportedFormat { format: FrontmatterFormat },
+    /// The field name is empty or spans lines.
+    InvalidKey { key: String },
+    /// An item was appended to a field holding a map.
+    NotAList { key: String },
+    /// The file is not inside the open folder.
+    OutsideRoot { path: PathBuf },
+    /// The file changed on disk since it was read.
+    Modified { path: PathBuf },
+    /// The file could not be read or written.
+    Io { path: PathBuf, message: String },
+}
+
+impl From<FrontmatterError> for FrontmatterEditError {
+    fn from(e: FrontmatterError) -> Self {
+        Self::Parse {
+            line: e.line,
+            message: e.message,
+        }
+    }
+}
+
+impl std::fmt::Display for FrontmatterEditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse { line, message } => {
+                write!(f, "frontmatter error on line {line}: {message}")
+            }
+            Self::UnsupportedFormat { format } => {
+                write!(f, "{} frontmatter cannot be edited", format.name())
+            }
+            Self::InvalidKey { key } => write!(f, "`{key}` is not a valid field name"),
+            Self::NotAList { key } => write!(f, "`{key}` holds a map, not a list"),
+            Self::OutsideRoot { path } => {
+                write!(f, "{} is outside the open folder", path.display())
+            }
+            Self::Modified { path } => write!(f, "{} changed since it was read", path.display()),
+            Self::Io { path, message } => write!(f, "{}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for FrontmatterEditError {}
+
+/// Apply `edits` to the YAML frontmatter of `content`, in order.
+///
+/// Only the lines of the edited fields change: other fields, comments,
+/// blank lines and the body are kept byte for byte. Edited fields are
+/// written on one line, with lists and maps in flow style, except that an
+/// item appended to a block list is added as a new `- item` line. A
+/// document without frontmatter gets a new block if a field is set.
+///
+/// # Errors
+///
+/// Returns [`FrontmatterEditError::Parse`] if the frontmatter does not parse,
+/// [`FrontmatterEditError::UnsupportedFormat`] for TOML or JSON frontmatter,
+/// [`FrontmatterEditError::InvalidKey`] for an empty or multi-line field
+/// name, or [`FrontmatterEditError::NotAList`] when appending to a map.
+pub fn edit_frontmatter(
+    content: &str,
+    edits: &[FieldEdit],
+) -> Result<String, FrontmatterEditError> {
+    let mut content = content.to_string();
+    for edit in edits {
+        let key = edit.key();
+        if key.trim().is_empty() || key.contains(['\n', '\r']) {
+            return Err(FrontmatterEditError::InvalidKey {
+                key: key.to_string(),
+            });
+        }
+        content = edit_field(&content, edit)?;
+    }
+    Ok(content)
+}
+
+fn edit_field(content: &str, edit: &FieldEdit) -> Result<String, FrontmatterEditError> {
+    let newline = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let key = edit.key();
+    let Some(block) = split_block(content) else {
+        let value = match edit {
+            FieldEdit::Set { value, .. } => value.clone(),
+            FieldEdit::Append { value, .. } => Value::Array(vec![value.clone()]),
+            FieldEdit::Remove { .. } => return Ok(content.to_string()),
+        };
+        let field = yaml_field(key, &value);
+        return Ok(format!("---{newline}{field}{newline}---{newline}{content}"));
+    };
+    if block.format != FrontmatterFormat::Yaml {
+        return Err(FrontmatterEditError::UnsupportedFormat {
+            format: block.format,
+        });
+    }
+    let fields = yaml_fields(block.text, block.line)?;
+    // The lines between the fences, each with its line ending.
+    let lines = &content[block.start..block.close];
+    let span = field_span(lines, key);
+
+    let replacement = match edit {
+        FieldEdit::Remove { .. } if span.is_none() => return Ok(content.to_string()),
+        FieldEdit::Remove { .. } => String::new(),
+        FieldEdit::Set { value, .. } => yaml_field(key, value) + newline,
+        FieldEdit::Append { value, .. } => {
+            let mut items = match fields.get(key) {
+                None | Some(Value::Null) => Vec::new(),
+                Some(Value::Array(items)) => items.clone(),
+                Some(Value::String(text)) => text
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| Value::String(item.to_string()))
+                    .collect(),
+                Some(Value::Object(_)) => {
+                    return Err(FrontmatterEditError::NotAList {
+                        key: key.to_string(),
+                    });
                 }
-                fm.fields.insert(key, value);
+                Some(single) => vec![single.clone()],
+            };
+            if items.contains(value) {
+                return Ok(content.to_string());
+            }
+            // A block list keeps its layout and gets one more line.
+            let block_list = span
+                .clone()
+                .filter(|_| matches!(fields.get(key), Some(Value::Array(_))))
+                .and_then(|span| Some((block_list_indent(&lines[span.clone()])?, span)));
+            if let Some((indent, span)) = block_list {
+                format!("{}{indent}- {}{newline}", &lines[span], yaml_inline(value))
+            } else {
+                items.push(value.clone());
+                yaml_field(key, &Value::Array(items)) + newline
             }
         }
+    };
+
+    // A new field goes after the others.
+    let span = span.unwrap_or(lines.len()..lines.len());
+    let lines = format!(
+        "{}{replacement}{}",
+        &lines[..span.start],
+        &lines[span.end..]
+    );
+    // Make sure the edit left valid YAML behind.
+    yaml_fields(&lines, block.line)?;
+    Ok(format!(
+        "{}{lines}{}",
+        &content[..block.start],
+        &content[block.close..]
+    ))
+}
 
-        (Some(fm), rest)
+/// Byte range in `text` of the top-level field `key`: its key line and
+/// the indented or `- item` lines below it, up to the last such line.
+fn field_span(text: &str, key: &str) -> Option<std::ops::Range<usize>> {
+    let mut offset = 0;
+    let mut span: Option<std::ops::Range<usize>> = None;
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim_end();
+        if let Some(span) = span.as_mut() {
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if line.starts_with([' ', '\t', '-']) {
+                span.end = offset;
+                continue;
+            }
+            break;
+        }
+        if line_key(trimmed).as_deref() == Some(key) {
+            span = Some(start..offset);
+        }
+    }
+    span
+}
+
+/// The key of a `key: value` line at the top level of a YAML map.
+fn line_key(line: &str) -> Option<String> {
+    if line.starts_with([' ', '\t', '#', '-']) {
+        return None;
+    }
+    let (key, rest) = if line.starts_with('"') {
+        // Find the closing quote by parsing growing prefixes as JSON.
+        let end = line
+            .match_indices('"')
+            .skip(1)
+            .map(|(i, _)| i + 1)
+            .find(|&end| serde_json::from_str::<String>(&line[..end]).is_ok())?;
+        (serde_json::from_str(&line[..end]).ok()?, &line[end..])
+    } else if let Some(quoted) = line.strip_prefix('\'') {
+        let end = quoted.match_indices('\'').map(|(i, _)| i).find(|&i| {
+            // `''` is an escaped quote.
+            !quoted[i + 1..].starts_with('\'') && !quoted[..i].ends_with('\'')
+        })?;
+        (quoted[..end].replace("''", "'"), &quoted[end + 1..])
     } else {
-        (None, content)
+        let colon = line
+            .match_indices(':')
+            .map(|(i, _)| i)
+            .find(|&i| line[i + 1..].is_empty() || line[i + 1..].starts_with([' ', '\t']))?;
+        (line[..colon].trim_end().to_string(), &line[colon..])
+    };
+    let rest = rest.trim_start();
+    (rest == ":" || rest.starts_with(": ") || rest.starts_with(":\t")).then_some(key)
+}
+
+/// The indentation of the `- item` lines of a field written as a block
+/// list, or `None` if it is written some other way.
+fn block_list_indent(field: &str) -> Option<&str> {
+    let mut lines = field.lines();
+    let (_, value) = lines.next()?.split_once(':')?;
+    let value = value.trim();
+    if !(value.is_empty() || value.starts_with('#')) {
+        return None;
     }
+    lines
+        .find(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+        .filter(|l| l.trim_start().starts_with('-'))
+        .map(|l| &l[..l.len() - l.trim_start().len()])
+}
+
+/// A `key: value` line.
+fn yaml_field(key: &str, value: &Value) -> String {
+    format!("{}: {}", yaml_text(key), yaml_inline(value))
+}
+
+/// `value` written on one line, with lists and maps in flow style.
+fn yaml_inline(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(text) => yaml_text(text),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(yaml_inline).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) => {
+            let fields: Vec<String> = map.iter().map(|(k, v)| yaml_field(k, v)).collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+    }
+}
+
+/// `text` as a YAML string: plain if it reads back as the same string, and
+/// double-quoted otherwise.
+fn yaml_text(text: &str) -> String {
+    let plain = !text.contains(['\n', '\r', ',', '[', ']', '{', '}', '#', '"', '\''])
+        && text.trim() == text
+        && YamlLoader::load_from_str(text)
+            .is_ok_and(|docs| docs == [Yaml::String(text.to_string())]);
+    if plain {
+        text.to_string()
+    } else {
+        Value::String(text.to_string()).to_string()
+    }
+}
+
+/// Apply `edits` to the frontmatter of the file at `path` under `root`,
+/// refusing if its modification time is no longer `mtime`, and return its
+/// new modification time. Times are in milliseconds since the Unix epoch, as
+/// from [`modified_millis`]. The file is replaced in one step, so a crash
+/// never leaves it half written.
+///
+/// # Errors
+///
+/// Returns [`FrontmatterEditError::OutsideRoot`] if the file is not under
+/// `root`, [`FrontmatterEditError::Modified`] if it changed since `mtime`,
+/// [`FrontmatterEditError::Io`] if it cannot be read or written, or any
+/// error of [`edit_frontmatter`].
+pub fn write_frontmatter_edits(
+    root: &Path,
+    path: &Path,
+    mtime: u64,
+    edits: &[FieldEdit],
+) -> Result<u64, FrontmatterEditError> {
+    let io_error = |e: std::io::Error| FrontmatterEditError::Io {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    };
+    if !is_within_root(root, path).map_err(io_error)? {
+        return Err(FrontmatterEditError::OutsideRoot {
+            path: path.to_path_buf(),
+        });
+    }
+    if modified_millis(path) != Some(mtime) {
+        return Err(FrontmatterEditError::Modified {
+            path: path.to_path_buf(),
+        });
+    }
+    let content = fs::read_to_string(path).map_err(io_error)?;
+    let updated = edit_frontmatter(&content, edits)?;
+    if updated != content {
+        write_atomic(path, &updated).map_err(io_error)?;
+    }
+    modified_millis(path).ok_or_else(|| FrontmatterEditError::Modified {
+        path: path.to_path_buf(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
 
     #[test]
     fn test_strip_frontmatter() {
@@ -53,11 +1010,387 @@ mod tests {
         assert!(rest.contains("# Hello"));
     }
 
+    #[test]
+    fn test_list_field() {
+        let content =
+            "---\ntags: [ops, \"on-call\"]\nkeywords: runbook, linux\nstatus: draft\n---\n";
+        let fm = strip_frontmatter(content).0.unwrap();
+        assert_eq!(fm.list("tags"), vec!["ops", "on-call"]);
+        assert_eq!(fm.list("keywords"), vec!["runbook", "linux"]);
+        assert_eq!(fm.list("status"), vec!["draft"]);
+        assert!(fm.list("missing").is_empty());
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let content = "---\n\
+            title: \"Deploy: production\" # pinned\n\
+            version: 3\n\
+            ratio: 0.5\n\
+            draft: false\n\
+            reviewed:\n\
+            tags:\n  - ops\n  - 2024\n\
+            owner:\n  name: Ada\n  teams: [infra]\n\
+            summary: |\n  First line\n  second line\n\
+            ---\nBody";
+        let (fm, rest) = parse_frontmatter(content);
+        let fm = fm.unwrap().unwrap();
+        assert_eq!(rest, "\nBody");
+        assert_eq!(fm.title.as_deref(), Some("Deploy: production"));
+        assert_eq!(
+            Value::Object(fm.fields.clone()),
+            json!({
+                "title": "Deploy: production",
+                "version": 3,
+                "ratio": 0.5,
+                "draft": false,
+                "reviewed": null,
+                "tags": ["ops", 2024],
+                "owner": {"name": "Ada", "teams": ["infra"]},
+                "summary": "First line\nsecond line\n",
+            })
+        );
+        assert_eq!(fm.get("version").as_deref(), Some("3"));
+        assert_eq!(fm.get("owner"), None);
+        assert_eq!(fm.list("tags"), vec!["ops", "2024"]);
+    }
+
+    #[test]
+    fn test_malformed_yaml_reports_line() {
+        let content = "\n---\ntitle: Notes\ntags: [ops\nstatus: draft\n---\n# Notes\n";
+        let (fm, rest) = parse_frontmatter(content);
+        let err = fm.unwrap_err();
+        assert_eq!(err.line, 5, "{err}");
+        assert_eq!(rest, "\n# Notes\n");
+        assert!(strip_frontmatter(content).0.is_none());
+
+        let (fm, _) = parse_frontmatter("---\n- a\n- b\n---\n");
+        assert_eq!(fm.unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn test_yaml_alias_expansion_is_limited() {
+        let mut lines = vec![
+            "---".to_string(),
+            "a0: &a0 [x, x, x, x, x, x, x, x, x, x]".to_string(),
+        ];
+        for i in 1..10 {
+            let aliases = vec![format!("*a{}", i - 1); 10].join(", ");
+            lines.push(format!("a{i}: &a{i} [{aliases}]"));
+        }
+        lines.extend(["---".to_string(), "# Body\n".to_string()]);
+        let content = lines.join("\n");
+        let (fm, rest) = parse_frontmatter(&content);
+        let err = fm.unwrap_err();
+        assert_eq!(err.line, 5, "{err}");
+        assert_eq!(rest, "\n# Body\n");
+
+        // Aliases that expand to a few values still work.
+        let (fm, _) = parse_frontmatter("---\nbase: &base [ops, docs]\ntags: *base\n---\n");
+        assert_eq!(fm.unwrap().unwrap().list("tags"), vec!["ops", "docs"]);
+    }
+
+    #[test]
+    fn test_yaml_alias_expansion_of_long_strings_is_limited() {
+        let copies = |name: &str| vec![format!("*{name}"); 10].join(", ");
+        let lines = [
+            "---".to_string(),
+            format!("a: &a {}", "x".repeat(100_000)),
+            format!("b: &b [{}]", copies("a")),
+            format!("c: &c [{}]", copies("b")),
+            "d: [*c, *c]".to_string(),
+            "---".to_string(),
+        ];
+        let content = lines.join("\n");
+        let err = parse_frontmatter(&content).0.unwrap_err();
+        assert_eq!(err.line, 5, "{err}");
+
+        // The string itself, and a few copies of it, are fine.
+        let content = lines[..4].join("\n") + "\n---\n";
+        assert!(parse_frontmatter(&content).0.unwrap().is_some());
+    }
+
+    #[test]
+    fn test_toml_frontmatter() {
+        let content = "+++\ntitle = \"Hugo page\"\ndate = 2024-03-01\ntags = [\"ops\", \"hugo\"]\n\
+            draft = true\n\n[params]\nweight = 2.5\n+++\n# Body\n";
+        let (fm, rest) = parse_frontmatter(content);
+        let fm = fm.unwrap().unwrap();
+        assert_eq!(rest, "\n# Body\n");
+        assert_eq!(fm.format, FrontmatterFormat::Toml);
+        assert_eq!(fm.title.as_deref(), Some("Hugo page"));
+        assert_eq!(
+            Value::Object(fm.fields),
+            json!({
+                "title": "Hugo page",
+                "date": "2024-03-01",
+                "tags": ["ops", "hugo"],
+                "draft": true,
+                "params": {"weight": 2.5},
+            })
+        );
+
+        let (fm, _) = parse_frontmatter("+++\ntitle = \"x\"\nbroken =\n+++\n");
+        assert_eq!(fm.unwrap_err().line, 3);
+    }
+
+    #[test]
+    fn test_json_frontmatter() {
+        let content = ";;;\n{\n  \"title\": \"Generated\",\n  \"tags\": [\"api\"]\n}\n;;;\nBody";
+        let (fm, rest) = parse_frontmatter(content);
+        let fm = fm.unwrap().unwrap();
+        assert_eq!(rest, "\nBody");
+        assert_eq!(fm.format, FrontmatterFormat::Json);
+        assert_eq!(fm.title.as_deref(), Some("Generated"));
+        assert_eq!(fm.list("tags"), vec!["api"]);
+
+        let (fm, rest) = parse_frontmatter(";;;\n{\n  \"title\": oops\n}\n;;;\nBody");
+        assert_eq!(fm.unwrap_err().line, 3);
+        assert_eq!(rest, "\nBody");
+    }
+
+    #[test]
+    fn test_empty_frontmatter() {
+        let (fm, rest) = parse_frontmatter("---\n---\nText");
+        let fm = fm.unwrap().unwrap();
+        assert_eq!(fm.format, FrontmatterFormat::Yaml);
+        assert!(fm.fields.is_empty());
+        assert_eq!(fm.title, None);
+        assert_eq!(rest, "\nText");
+    }
+
+    #[test]
+    fn test_edit_keeps_formatting() {
+        let content = "---\n\
+            # Runbook metadata\n\
+            title: 'Restore: database'\n\
+            status: draft   # wip\n\
+            tags:\n    - ops\n    # on-call only\n    - backup\n\n\
+            obsolete:\n  since: 2020\n  by: nobody\n\
+            owner: sre\n\
+            ---\n# Restore\n\nstatus: not frontmatter\n";
+        let edits = [
+            FieldEdit::Set {
+                key: "status".to_string(),
+                value: json!("done"),
+            },
+            FieldEdit::Append {
+                key: "tags".to_string(),
+                value: json!("db"),
+            },
+            FieldEdit::Append {
+                key: "tags".to_string(),
+                value: json!("ops"),
+            },
+            FieldEdit::Remove {
+                key: "obsolete".to_string(),
+            },
+            FieldEdit::Set {
+                key: "reviewed_by".to_string(),
+                value: json!("Ada Lovelace"),
+            },
+        ];
+        assert_eq!(
+            edit_frontmatter(content, &edits).unwrap(),
+            "---\n\
+            # Runbook metadata\n\
+            title: 'Restore: database'\n\
+            status: done\n\
+            tags:\n    - ops\n    # on-call only\n    - backup\n    - db\n\n\
+            owner: sre\n\
+            reviewed_by: Ada Lovelace\n\
+            ---\n# Restore\n\nstatus: not frontmatter\n"
+        );
+    }
+
+    #[test]
+    fn test_edit_values() {
+        let set = |key: &str, value: Value| FieldEdit::Set {
+            key: key.to_string(),
+            value,
+        };
+        let append = |key: &str, value: Value| FieldEdit::Append {
+            key: key.to_string(),
+            value,
+        };
+        let edit = |content: &str, edit: FieldEdit| edit_frontmatter(content, &[edit]);
+
+        let content = "---\r\ntags: [a, b]\r\nkeywords: x, y\r\nowner: {name: Ada}\r\n---\r\nBody";
+        assert_eq!(
+            edit(content, append("tags", json!("c d"))).unwrap(),
+            "---\r\ntags: [a, b, c d]\r\nkeywords: x, y\r\nowner: {name: Ada}\r\n---\r\nBody"
+        );
+        assert_eq!(edit(content, append("tags", json!("a"))).unwrap(), content);
+        assert!(
+            edit(content, append("keywords", json!("z")))
+                .unwrap()
+                .contains("keywords: [x, y, z]\r\n")
+        );
+        assert_eq!(
+            edit(content, append("owner", json!("x"))),
+            Err(FrontmatterEditError::NotAList {
+                key: "owner".to_string()
+            })
+        );
+        assert_eq!(
+            edit(
+                "---\n---\n",
+                set(
+                    "note",
+                    json!({"flag": "true", "n": [1, null], "text": "a: b"})
+                )
+            )
+            .unwrap(),
+            "---\nnote: {flag: \"true\", n: [1, null], text: \"a: b\"}\n---\n"
+        );
+        assert_eq!(
+            edit("# Title\n", set("draft", json!(true))).unwrap(),
+            "---\ndraft: true\n---\n# Title\n"
+        );
+        let remove = FieldEdit::Remove {
+            key: "draft".to_string(),
+        };
+        assert_eq!(edit("# Title\n", remove).unwrap(), "# Title\n");
+        assert_eq!(
+            edit("+++\ntitle = \"x\"\n+++\n", set("a", json!(1))),
+            Err(FrontmatterEditError::UnsupportedFormat {
+                format: FrontmatterFormat::Toml
+            })
+        );
+        assert!(matches!(
+            edit("---\ntags: [a\n---\n", set("a", json!(1))),
+            Err(FrontmatterEditError::Parse { .. })
+        ));
+        assert_eq!(
+            edit("---\n---\n", set(" ", json!(1))),
+            Err(FrontmatterEditError::InvalidKey {
+                key: " ".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_write_frontmatter_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("docs");
+        fs::create_dir(&root).unwrap();
+        let path = root.join("a.md");
+        fs::write(&path, "---\nstatus: draft\n---\nBody\n").unwrap();
+        let edits = [FieldEdit::Set {
+            key: "status".to_string(),
+            value: json!("done"),
+        }];
+
+        let mtime = modified_millis(&path).unwrap();
+        assert_eq!(
+            write_frontmatter_edits(&root, &path, mtime + 1, &edits),
+            Err(FrontmatterEditError::Modified { path: path.clone() })
+        );
+        let new_mtime = write_frontmatter_edits(&root, &path, mtime, &edits).unwrap();
+        assert_eq!(Some(new_mtime), modified_millis(&path));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "---\nstatus: done\n---\nBody\n"
+        );
+
+        let outside = dir.path().join("b.md");
+        fs::write(&outside, "---\nstatus: draft\n---\n").unwrap();
+        let escaped = root.join("../b.md");
+        let mtime = modified_millis(&outside).unwrap();
+        assert_eq!(
+            write_frontmatter_edits(&root, &escaped, mtime, &edits),
+            Err(FrontmatterEditError::OutsideRoot {
+                path: escaped.clone()
+            })
+        );
+        assert_eq!(
+            fs::read_to_string(&outside).unwrap(),
+            "---\nstatus: draft\n---\n"
+        );
+    }
+
+    #[test]
+    fn test_document_properties() {
+        let content = "---\n\
+            title: Release notes\n\
+            description: What changed in 2.0\n\
+            author: {name: Ada, email: ada@example.com}\n\
+            authors: [Grace, Ada]\n\
+            date: 2024-03-01T09:30:00+02:00\n\
+            tags: [release, notes]\n\
+            draft: yes\n\
+            lang: de-CH\n\
+            toc: false\n\
+            ---\n";
+        let fm = strip_frontmatter(content).0.unwrap();
+        assert_eq!(
+            DocumentProperties::from_frontmatter(Some(&fm)),
+            DocumentProperties {
+                title: Some("Release notes".to_string()),
+                description: Some("What changed in 2.0".to_string()),
+                authors: vec!["Ada".to_string(), "Grace".to_string()],
+                date: Some(DocumentDate {
+                    year: 2024,
+                    month: 3,
+                    day: 1,
+                    time: Some([9, 30, 0]),
+                    offset_minutes: Some(120),
+                }),
+                tags: vec!["release".to_string(), "notes".to_string()],
+                draft: true,
+                lang: Some("de-CH".to_string()),
+                toc: false,
+            }
+        );
+
+        let fm = strip_frontmatter("---\nlanguage: not a language\ndraft: maybe\n---\n")
+            .0
+            .unwrap();
+        let properties = DocumentProperties::from_frontmatter(Some(&fm));
+        assert_eq!(properties.lang, None);
+        assert!(!properties.draft);
+        assert!(properties.toc);
+        assert!(DocumentProperties::from_frontmatter(None).toc);
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let date = |text| {
+            DocumentDate::parse(text).map(|d| (d.year, d.month, d.day, d.time, d.offset_minutes))
+        };
+        assert_eq!(date("2024-02-29"), Some((2024, 2, 29, None, None)));
+        assert_eq!(
+            date("2024-02-29 13:05"),
+            Some((2024, 2, 29, Some([13, 5, 0]), None))
+        );
+        assert_eq!(
+            date("2001-12-14t21:59:43.10-05:00"),
+            Some((2001, 12, 14, Some([21, 59, 43]), Some(-300)))
+        );
+        assert_eq!(
+            date("1979-05-27T07:32:00Z"),
+            Some((1979, 5, 27, Some([7, 32, 0]), Some(0)))
+        );
+        for invalid in [
+            "2023-02-29",
+            "2024-13-01",
+            "24-01-01",
+            "2024-1-01",
+            "2024-01-01T25:00",
+            "2024-01-01T10",
+            "2024-01-01T10:00+5",
+            "soon",
+        ] {
+            assert_eq!(date(invalid), None, "{invalid}");
+        }
+    }
+
     #[test]
     fn test_no_frontmatter() {
         let content = "# Just a heading\n\nSome text";
         let (fm, rest) = strip_frontmatter(content);
         assert!(fm.is_none());
         assert_eq!(rest, content);
+        assert!(strip_frontmatter("---- rule\ntext\n---\n").0.is_none());
     }
 }
@@ -0,0 +1,671 @@
+use rayon::prelude::*;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+use crate::ast::SourceHeading;
+use crate::index::SearchIndex;
+use crate::search::{MatchRange, to_match_ranges};
+
+/// Score for each matched character.
+const SCORE_MATCH: i32 = 16;
+/// Bonus for a match at the start of a word: at the start of the text or
+/// after a character such as `/`, `-`, `_`, `.` or a space.
+const BONUS_BOUNDARY: i32 = 8;
+/// Bonus for an uppercase letter after a lowercase one, as in `camelCase`.
+const BONUS_CAMEL: i32 = 7;
+/// Bonus for a match directly after the previous one.
+const BONUS_CONSECUTIVE: i32 = 8;
+/// Bonus for a match in the file name rather than in a directory name.
+const BONUS_FILE_NAME: i32 = 2;
+/// Penalty for the first character skipped between two matches.
+const PENALTY_GAP_START: i32 = 3;
+/// Penalty for each further character skipped between two matches.
+const PENALTY_GAP_EXTENSION: i32 = 1;
+
+/// A file matched by [`FileFinder::find`].
+#[derive(Debug, Clone, Serialize)]
+pub struct FileMatch {
+    pub path: PathBuf,
+    /// Path relative to the root folder, with `/` separators.
+    pub rel_path: String,
+    pub title: Option<String>,
+    pub score: i32,
+    /// Matched spans of `rel_path`; empty if the title matched better.
+    pub path_ranges: Vec<MatchRange>,
+    /// Matched spans of `title`; empty if the path matched better.
+    pub title_ranges: Vec<MatchRange>,
+}
+
+/// Fuzzy matcher over the file paths and titles of a folder, for opening a
+/// file by name.
+///
+/// Candidates are prepared once up front, so a query only compares
+/// characters and allocates for the files that match.
+pub struct FileFinder {
+    root: PathBuf,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    rel_path: String,
+    title: Option<String>,
+    path_text: Text,
+    /// Index in `path_text` of the first character of the file name.
+    name_start: usize,
+    title_text: Option<Text>,
+}
+
+/// The characters of a candidate, with a lowercase copy for
+/// case-insensitive matching.
+struct Text {
+    chars: Vec<char>,
+    lower: Vec<char>,
+    /// [`char_mask`] of all characters, to skip texts that cannot match.
+    mask: u64,
+}
+
+impl Text {
+    fn new(text: &str) -> Self {
+        let chars: Vec<char> = text.chars().collect();
+        let lower: Vec<char> = chars.iter().map(|&c| to_lower(c)).collect();
+        let mask = lower.iter().fold(0, |mask, &c| mask | char_mask(c));
+        Self { chars, lower, mask }
+    }
+}
+
+/// A bit per ASCII letter (either case) and digit, and one for anything else.
+fn char_mask(c: char) -> u64 {
+    match to_lower(c) {
+        c @ 'a'..='z' => 1 << (c as u32 - 'a' as u32),
+        c @ '0'..='9' => 1 << (26 + c as u32 - '0' as u32),
+        _ => 1 << 63,
+    }
+}
+
+/// A heading matched by [`HeadingFinder::find`].
+#[derive(Debug, Clone, Serialize)]
+pub struct HeadingMatch {
+    /// The file containing the heading.
+    pub path: PathBuf,
+    /// Path relative to the root folder, with `/` separators.
+    pub rel_path: String,
+    pub level: u8,
+    pub text: String,
+    /// Id of the heading in the rendered document, to scroll to.
+    pub anchor: String,
+    pub score: i32,
+    /// Matched spans of `text`.
+    pub ranges: Vec<MatchRange>,
+}
+
+/// Fuzzy matcher over the headings of every file in a folder, for going to
+/// a section by name.
+pub struct HeadingFinder {
+    root: PathBuf,
+    entries: Vec<HeadingEntry>,
+}
+
+struct HeadingEntry {
+    rel_path: String,
+    heading: SourceHeading,
+    text: Text,
+}
+
+/// Which candidate text of an entry matched.
+#[derive(Clone, Copy)]
+enum Target {
+    Path,
+    Title,
+}
+
+impl FileFinder {
+    /// Prepare the files of `index` for matching.
+    pub fn from_index(index: &SearchIndex) -> Self {
+        Self::new(index.root(), index.files())
+    }
+
+    /// Prepare `files`, given as paths relative to `root` with their titles.
+    pub fn new<'a>(
+        root: &Path,
+        files: impl IntoIterator<Item = (&'a Path, Option<&'a str>)>,
+    ) -> Self {
+        let entries = files
+            .into_iter()
+            .map(|(rel, title)| {
+                let rel_path = rel
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                let path_text = Text::new(&rel_path);
+                let name_start = path_text
+                    .chars
+                    .iter()
+                    .rposition(|&c| c == '/')
+                    .map_or(0, |i| i + 1);
+                Entry {
+                    title: title.map(str::to_string),
+                    title_text: title.map(Text::new),
+                    rel_path,
+                    path_text,
+                    name_start,
+                }
+            })
+            .collect();
+        Self {
+            root: root.to_path_buf(),
+            entries,
+        }
+    }
+
+    /// The root folder the paths are relative to.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Number of candidate files.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no candidate files.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Find the `limit` files that best match `query`, best first.
+    ///
+    /// The characters of the query must occur in order in the relative path
+    /// or the title, though not necessarily next to each other. Whitespace
+    /// in the query is ignored. Matching is smart-case: it ignores case
+    /// unless the query contains an uppercase letter. Matches at word starts,
+    /// in the file name and in runs of adjacent characters score higher; ties
+    /// go to the shorter path. An empty query matches every file.
+    pub fn find(&self, query: &str, limit: usize) -> Vec<FileMatch> {
+        let (pattern, case_sensitive) = prepare_pattern(query);
+
+        // Score in parallel without keeping positions; only the returned
+        // files need them for highlighting.
+        let mut hits: Vec<(i32, usize, Target)> = self
+            .entries
+            .par_iter()
+            .enumerate()
+            .map_init(Vec::new, |positions, (i, entry)| {
+                best_match(entry, &pattern, case_sensitive, positions)
+                    .map(|(score, target)| (score, i, target))
+            })
+            .flatten_iter()
+            .collect();
+
+        // Best score first; shorter paths first among equals.
+        let order = |a: &(i32, usize, Target), b: &(i32, usize, Target)| {
+            let (pa, pb) = (&self.entries[a.1].rel_path, &self.entries[b.1].rel_path);
+            b.0.cmp(&a.0)
+                .then(pa.len().cmp(&pb.len()))
+                .then_with(|| pa.cmp(pb))
+        };
+        select_best(&mut hits, limit, order);
+
+        let mut positions = Vec::new();
+        hits.into_iter()
+            .map(|(score, i, target)| {
+                let entry = &self.entries[i];
+                let (text, name_start) = match target {
+                    Target::Path => (&entry.path_text, entry.name_start),
+                    Target::Title => (entry.title_text.as_ref().unwrap_or(&entry.path_text), 0),
+                };
+                fuzzy_match(text, &pattern, case_sensitive, name_start, &mut positions);
+                let (path_ranges, title_ranges) = match (target, &entry.title) {
+                    (Target::Title, Some(title)) => (
+                        Vec::new(),
+                        to_match_ranges(title, &char_spans(title, &positions)),
+                    ),
+                    _ => (
+                        to_match_ranges(&entry.rel_path, &char_spans(&entry.rel_path, &positions)),
+                        Vec::new(),
+                    ),
+                };
+                FileMatch {
+                    path: self.root.join(&entry.rel_path),
+                    rel_path: entry.rel_path.clone(),
+                    title: entry.title.clone(),
+                    score,
+                    path_ranges,
+                    title_ranges,
+                }
+            })
+            .collect()
+    }
+}
+
+impl HeadingFinder {
+    /// Prepare the headings of the files in `index` for matching.
+    pub fn from_index(index: &SearchIndex) -> Self {
+        Self::new(index.root(), index.headings())
+    }
+
+    /// Prepare `headings`, given with the paths of their files relative to `root`.
+    pub fn new<'a>(
+        root: &Path,
+        headings: impl IntoIterator<Item = (&'a Path, &'a SourceHeading)>,
+    ) -> Self {
+        let entries = headings
+            .into_iter()
+            .map(|(rel, heading)| HeadingEntry {
+                rel_path: rel
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                text: Text::new(&heading.text),
+                heading: heading.clone(),
+            })
+            .collect();
+        Self {
+            root: root.to_path_buf(),
+            entries,
+        }
+    }
+
+    /// The root folder the paths are relative to.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Number of candidate headings.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no candidate headings.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Find the `limit` headings whose text best matches `query`, best first.
+    ///
+    /// Matching works as in [`FileFinder::find`]. Among equal scores, higher
+    /// level headings come first, then shorter ones.
+    pub fn find(&self, query: &str, limit: usize) -> Vec<HeadingMatch> {
+        let (pattern, case_sensitive) = prepare_pattern(query);
+        let mut hits: Vec<(i32, usize)> = self
+            .entries
+            .par_iter()
+            .enumerate()
+            .map_init(Vec::new, |positions, (i, entry)| {
+                fuzzy_match(&entry.text, &pattern, case_sensitive, 0, positions)
+                    .map(|score| (score, i))
+            })
+            .flatten_iter()
+            .collect();
+
+        let order = |a: &(i32, usize), b: &(i32, usize)| {
+            let (ha, hb) = (&self.entries[a.1], &self.entries[b.1]);
+            b.0.cmp(&a.0)
+                .then(ha.heading.level.cmp(&hb.heading.level))
+                .then(ha.heading.text.len().cmp(&hb.heading.text.len()))
+                .then(a.1.cmp(&b.1))
+        };
+        select_best(&mut hits, limit, order);
+
+        let mut positions = Vec::new();
+        hits.into_iter()
+            .map(|(score, i)| {
+                let entry = &self.entries[i];
+                fuzzy_match(&entry.text, &pattern, case_sensitive, 0, &mut positions);
+                let text = &entry.heading.text;
+                HeadingMatch {
+                    path: self.root.join(&entry.rel_path),
+                    rel_path: entry.rel_path.clone(),
+                    level: entry.heading.level,
+                    text: text.clone(),
+                    anchor: entry.heading.id.clone(),
+                    score,
+                    ranges: to_match_ranges(text, &char_spans(text, &positions)),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The query's characters without whitespace, lowercased unless it contains
+/// an uppercase letter, and whether matching is case-sensitive.
+fn prepare_pattern(query: &str) -> (Vec<char>, bool) {
+    let pattern: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    let case_sensitive = pattern.iter().any(|c| c.is_uppercase());
+    if case_sensitive {
+        (pattern, true)
+    } else {
+        (pattern.iter().map(|&c| to_lower(c)).collect(), false)
+    }
+}
+
+/// Keep the `limit` best of `hits`, sorted by `order`.
+fn select_best<T>(hits: &mut Vec<T>, limit: usize, order: impl Fn(&T, &T) -> Ordering) {
+    if hits.len() > limit {
+        hits.select_nth_unstable_by(limit, &order);
+        hits.truncate(limit);
+    }
+    hits.sort_by(order);
+}
+
+/// The better of the path and title matches of `entry`, if either matches.
+fn best_match(
+    entry: &Entry,
+    pattern: &[char],
+    case_sensitive: bool,
+    positions: &mut Vec<usize>,
+) -> Option<(i32, Target)> {
+    let path = fuzzy_match(
+        &entry.path_text,
+        pattern,
+        case_sensitive,
+        entry.name_start,
+        positions,
+    );
+    let title = entry
+        .title_text
+        .as_ref()
+        .and_then(|text| fuzzy_match(text, pattern, case_sensitive, 0, positions));
+    match (path, title) {
+        (Some(p), Some(t)) if t > p => Some((t, Target::Title)),
+        (Some(p), _) => Some((p, Target::Path)),
+        (None, t) => t.map(|t| (t, Target::Title)),
+    }
+}
+
+fn to_lower(c: char) -> char {
+    if c.is_ascii() {
+        c.to_ascii_lowercase()
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+/// Score `pattern` against `text`, or `None` if it does not occur in order.
+///
+/// Tries the whole text and the file name alone, keeping the better match,
+/// whose character indices are left in `positions`.
+fn fuzzy_match(
+    text: &Text,
+    pattern: &[char],
+    case_sensitive: bool,
+    name_start: usize,
+    positions: &mut Vec<usize>,
+) -> Option<i32> {
+    positions.clear();
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    let needed = pattern.iter().fold(0, |mask, &c| mask | char_mask(c));
+    if text.mask & needed != needed {
+        return None;
+    }
+    let haystack = if case_sensitive {
+        &text.chars
+    } else {
+        &text.lower
+    };
+
+    if !match_positions(haystack, pattern, 0, positions) {
+        return None;
+    }
+    let best = score_positions(&text.chars, positions, name_start);
+    if positions[0] < name_start && match_positions(haystack, pattern, name_start, positions) {
+        let in_name = score_positions(&text.chars, positions, name_start);
+        if in_name > best {
+            return Some(in_name);
+        }
+        match_positions(haystack, pattern, 0, positions);
+    }
+    Some(best)
+}
+
+/// Find `pattern` in `haystack` from `from` onwards, within the shortest
+/// window that ends where the pattern first completes, and store the
+/// matched indices in `positions`. Returns whether it was found.
+///
+/// Walking back from that end prefers the latest occurrence of each
+/// character, which keeps matches together.
+fn match_positions(
+    haystack: &[char],
+    pattern: &[char],
+    from: usize,
+    positions: &mut Vec<usize>,
+) -> bool {
+    let mut pi = 0;
+    let mut end = None;
+    for (i, &c) in haystack.iter().enumerate().skip(from) {
+        if c == pattern[pi] {
+            pi += 1;
+            if pi == pattern.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let Some(end) = end else {
+        return false;
+    };
+
+    positions.clear();
+    for i in (from..=end).rev() {
+        if haystack[i] == pattern[pattern.len() - 1 - positions.len()] {
+            positions.push(i);
+            if positions.len() == pattern.len() {
+                break;
+            }
+        }
+    }
+    positions.reverse();
+    true
+}
+
+/// Score matches at the given character positions (sorted) of `chars`.
+fn score_positions(chars: &[char], positions: &[usize], name_start: usize) -> i32 {
+    let mut score = 0i32;
+    let mut prev: Option<usize> = None;
+    for &i in positions {
+        score += SCORE_MATCH + bonus_at(chars, i);
+        if i >= name_start {
+            score += BONUS_FILE_NAME;
+        }
+        if let Some(p) = prev {
+            let gap = i - p - 1;
+            if gap == 0 {
+                score += BONUS_CONSECUTIVE;
+            } else {
+                let extension = i32::try_from(gap - 1).unwrap_or(i32::MAX);
+                score = score
+                    .saturating_sub(PENALTY_GAP_START)
+                    .saturating_sub(PENALTY_GAP_EXTENSION.saturating_mul(extension));
+            }
+        }
+        prev = Some(i);
+    }
+    score
+}
+
+/// Bonus for a match at index `i` of `chars`, based on the preceding character.
+fn bonus_at(chars: &[char], i: usize) -> i32 {
+    let Some(&prev) = i.checked_sub(1).and_then(|p| chars.get(p)) else {
+        return BONUS_BOUNDARY;
+    };
+    if !prev.is_alphanumeric() {
+        BONUS_BOUNDARY
+    } else if prev.is_lowercase() && chars[i].is_uppercase() {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+/// Byte spans of `text` covering the characters at `positions` (sorted),
+/// merging adjacent characters into one span.
+fn char_spans(text: &str, positions: &[usize]) -> Vec<(usize, usize)> {
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    let mut wanted = positions.iter().peekable();
+    for (index, (byte, c)) in text.char_indices().enumerate() {
+        if wanted.next_if(|&&p| p == index).is_none() {
+            continue;
+        }
+        let end = byte + c.len_utf8();
+        match spans.last_mut() {
+            Some(last) if last.1 == byte => last.1 = end,
+            _ => spans.push((byte, end)),
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finder(files: &[(&str, Option<&str>)]) -> FileFinder {
+        FileFinder::new(
+            Path::new("/root"),
+            files.iter().map(|(rel, title)| (Path::new(*rel), *title)),
+        )
+    }
+
+    fn found(finder: &FileFinder, query: &str) -> Vec<String> {
+        finder
+            .find(query, 50)
+            .into_iter()
+            .map(|m| m.rel_path)
+            .collect()
+    }
+
+    #[test]
+    fn test_subsequence_match_with_ranges() {
+        let finder = finder(&[("docs/README.md", None), ("notes.md", None)]);
+        let matches = finder.find("dcrdm", 50);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].path, Path::new("/root/docs/README.md"));
+
+        let spans: Vec<&str> = matches[0]
+            .path_ranges
+            .iter()
+            .map(|r| &matches[0].rel_path[r.start..r.end])
+            .collect();
+        assert_eq!(spans, vec!["d", "c", "R", "DM"]);
+    }
+
+    #[test]
+    fn test_smart_case() {
+        let finder = finder(&[("Api.md", None), ("api.md", None)]);
+        assert_eq!(found(&finder, "api"), vec!["Api.md", "api.md"]);
+        assert_eq!(found(&finder, "Api"), vec!["Api.md"]);
+    }
+
+    #[test]
+    fn test_contiguous_matches_rank_first() {
+        let finder = finder(&[("lib/other/guide.md", None), ("docs/logging.md", None)]);
+        assert_eq!(found(&finder, "log")[0], "docs/logging.md");
+
+        let finder = self::finder(&[("docs/api/api.md", None)]);
+        let matches = finder.find("api", 50);
+        assert_eq!(matches[0].path_ranges[0].start, "docs/api/".len());
+    }
+
+    #[test]
+    fn test_file_name_ranks_above_directory() {
+        let finder = finder(&[("guide/intro.md", None), ("intro/guide.md", None)]);
+        assert_eq!(found(&finder, "guide")[0], "intro/guide.md");
+
+        // The tightest occurrence is highlighted, not the first character seen.
+        let matches = finder.find("intro", 50);
+        let guide = matches
+            .iter()
+            .find(|m| m.rel_path == "guide/intro.md")
+            .unwrap();
+        assert_eq!(guide.path_ranges[0].start, "guide/".len());
+    }
+
+    #[test]
+    fn test_word_starts_rank_first() {
+        let finder = finder(&[("abcdef.md", None), ("a-b-c.md", None)]);
+        assert_eq!(found(&finder, "abc")[0], "abcdef.md");
+        assert_eq!(found(&finder, "ac")[0], "a-b-c.md");
+    }
+
+    #[test]
+    fn test_title_match() {
+        let finder = finder(&[("x/2024-01.md", Some("Quarterly Report")), ("q.md", None)]);
+        let matches = finder.find("qreport", 50);
+        assert_eq!(matches.len(), 1);
+        assert!(matches[0].path_ranges.is_empty());
+        let title = matches[0].title.as_deref().unwrap();
+        let spans: Vec<&str> = matches[0]
+            .title_ranges
+            .iter()
+            .map(|r| &title[r.start..r.end])
+            .collect();
+        assert_eq!(spans, vec!["Q", "Report"]);
+    }
+
+    #[test]
+    fn test_empty_query_lists_files() {
+        let finder = finder(&[("a.md", None), ("b.md", None), ("c.md", None)]);
+        assert_eq!(found(&finder, ""), vec!["a.md", "b.md", "c.md"]);
+        assert_eq!(finder.find(" ", 2).len(), 2);
+    }
+
+    #[test]
+    fn test_heading_finder() {
+        let heading = |level: u8, text: &str, id: &str| SourceHeading {
+            level,
+            text: text.to_string(),
+            id: id.to_string(),
+            line: 1,
+        };
+        let headings = [
+            (
+                Path::new("spec/api.md"),
+                heading(2, "Rate limits", "rate-limits"),
+            ),
+            (
+                Path::new("spec/api.md"),
+                heading(3, "Retry after", "retry-after"),
+            ),
+            (
+                Path::new("guide.md"),
+                heading(1, "Rate limits", "rate-limits"),
+            ),
+        ];
+        let finder = HeadingFinder::new(
+            Path::new("/root"),
+            headings.iter().map(|(rel, h)| (*rel, h)),
+        );
+        let matches = finder.find("ratelim", 50);
+        let found: Vec<(&str, u8, &str)> = matches
+            .iter()
+            .map(|m| (m.rel_path.as_str(), m.level, m.anchor.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("guide.md", 1, "rate-limits"),
+                ("spec/api.md", 2, "rate-limits")
+            ]
+        );
+        assert_eq!(matches[0].path, Path::new("/root/guide.md"));
+        let spans: Vec<&str> = matches[0]
+            .ranges
+            .iter()
+            .map(|r| &matches[0].text[r.start..r.end])
+            .collect();
+        assert_eq!(spans, vec!["Rate", "lim"]);
+        assert_eq!(finder.find("retry", 50)[0].anchor, "retry-after");
+    }
+
+    #[test]
+    fn test_utf16_ranges() {
+        let finder = finder(&[("😀/café.md", None)]);
+        let matches = finder.find("é", 50);
+        let range = matches[0].path_ranges[0];
+        assert_eq!((range.start_utf16, range.end_utf16), (6, 7));
+    }
+}
@@ -0,0 +1,981 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::SourceHeading;
+use crate::config::FolderConfigs;
+use crate::files::{modified_nanos, write_atomic};
+use crate::links::{OutgoingLink, outgoing_links};
+use crate::query::{Field, Query};
+use crate::rank::{CorpusStats, DocumentTerms, FieldCounts, average, bm25, term_counts};
+use crate::search::{
+    CancelToken, Document, Ranking, Scan, SearchBatch, SearchError, SearchOptions, SearchResult,
+    build_query, collect_md_files, score_words, search_paths, tokenize,
+};
+use crate::slug::SlugStyle;
+
+/// Bumped whenever the on-disk format, tokenization or the fields read from
+/// files, such as titles, tags, headings and links, change.
+const INDEX_VERSION: u32 = 10;
+
+/// A persistent inverted index of the `.md` files under one root folder.
+///
+/// The index maps case-folded words to the files containing them, with their
+/// counts in each scored field. Queries use it to pick candidate files and
+/// score them, so only files that can possibly match are read, best first.
+/// It is kept current by [`SearchIndex::refresh`], which re-reads only files
+/// whose modification time changed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchIndex {
+    version: u32,
+    root: PathBuf,
+    /// Indexed files keyed by path relative to `root`.
+    files: BTreeMap<PathBuf, IndexedFile>,
+    /// Word to relative paths of the files containing it. Rebuilt on load.
+    #[serde(skip)]
+    postings: HashMap<String, Vec<PathBuf>>,
+    /// The words of `postings`, for substring lookup. Rebuilt with them.
+    #[serde(skip)]
+    vocabulary: Vocabulary,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexedFile {
+    /// Modification time in nanoseconds since the Unix epoch.
+    mtime: u64,
+    /// Slug style the heading ids were generated with.
+    slug_style: SlugStyle,
+    /// Distinct case-folded words in the file, sorted, with their occurrences
+    /// in each scored field.
+    terms: Vec<(String, FieldCounts)>,
+    /// Total number of words in the file.
+    len: usize,
+    /// Frontmatter title, if any.
+    title: Option<String>,
+    /// Frontmatter and inline tags, case-folded.
+    tags: Vec<String>,
+    /// Headings in document order, with lines counted from the end of the
+    /// frontmatter.
+    headings: Vec<SourceHeading>,
+    /// Links to files under the root, with lines counted from the start of
+    /// the file.
+    links: Vec<OutgoingLink>,
+}
+
+impl IndexedFile {
+    fn new(rel: &Path, mtime: u64, slug_style: SlugStyle, content: &str) -> Self {
+        let doc = Document::new(Path::new(""), rel, content, slug_style);
+        Self {
+            mtime,
+            slug_style,
+            terms: term_counts(&doc),
+            len: tokenize(content).len(),
+            title: doc.frontmatter.as_ref().and_then(|fm| fm.title.clone()),
+            tags: doc.tags(),
+            headings: doc.map().headings.clone(),
+            links: outgoing_links(rel, doc.body, doc.body_line),
+        }
+    }
+
+    /// Field-weighted frequencies of the query words in the file, given the
+    /// indexed words that contain each query word.
+    fn document_terms(&self, containing: &[HashSet<&str>]) -> DocumentTerms {
+        let freqs = containing
+            .iter()
+            .map(|words| {
+                self.terms
+                    .iter()
+                    .filter(|(term, _)| words.contains(term.as_str()))
+                    .map(|(_, counts)| counts.weighted())
+                    .sum()
+            })
+            .collect();
+        DocumentTerms {
+            freqs,
+            len: self.len,
+        }
+    }
+}
+
+/// Files read from disk by [`SearchIndex::scan`], to merge into the index
+/// with [`SearchIndex::apply`].
+#[derive(Debug, Default)]
+pub struct IndexUpdate {
+    /// New and changed files, keyed by path relative to the root.
+    changed: Vec<(PathBuf, IndexedFile)>,
+    /// Relative paths of every file found, sorted, or `None` if the scan was
+    /// cancelled before it saw them all.
+    seen: Option<Vec<PathBuf>>,
+}
+
+impl IndexUpdate {
+    /// Whether the scan saw every file, rather than being cancelled.
+    pub fn is_complete(&self) -> bool {
+        self.seen.is_some()
+    }
+}
+
+impl SearchIndex {
+    /// Create an empty index for `root`.
+    pub fn new(root: &Path) -> Self {
+        Self {
+            version: INDEX_VERSION,
+            root: root.to_path_buf(),
+            files: BTreeMap::new(),
+            postings: HashMap::new(),
+            vocabulary: Vocabulary::default(),
+        }
+    }
+
+    /// Load the index for `root` from `index_dir`, or start an empty one if
+    /// none exists or it cannot be read.
+    pub fn load(root: &Path, index_dir: &Path) -> Self {
+        let Ok(data) = fs::read_to_string(index_file(root, index_dir)) else {
+            return Self::new(root);
+        };
+        match serde_json::from_str::<Self>(&data) {
+            Ok(mut index) if index.version == INDEX_VERSION && index.root == root => {
+                index.rebuild_postings();
+                index
+            }
+            _ => Self::new(root),
+        }
+    }
+
+    /// Save the index to `index_dir`, creating it if needed.
+    ///
+    /// The file is replaced in one step, so a crash or another window saving
+    /// at the same time never leaves a truncated index behind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self, index_dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(index_dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        write_atomic(&index_file(&self.root, index_dir), &json).map_err(|e| e.to_string())
+    }
+
+    /// The root folder this index covers.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Number of indexed files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Whether no files are indexed.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Indexed files as paths relative to the root, in path order, with
+    /// their frontmatter titles.
+    pub fn files(&self) -> impl Iterator<Item = (&Path, Option<&str>)> {
+        self.files
+            .iter()
+            .map(|(rel, file)| (rel.as_path(), file.title.as_deref()))
+    }
+
+    /// Indexed files as paths relative to the root, in path order, with
+    /// their frontmatter titles and tags.
+    pub fn tagged_files(&self) -> impl Iterator<Item = (&Path, Option<&str>, &[String])> {
+        self.files
+            .iter()
+            .map(|(rel, file)| (rel.as_path(), file.title.as_deref(), file.tags.as_slice()))
+    }
+
+    /// Every heading of the indexed files, in path and then document order,
+    /// with the path of its file relative to the root.
+    pub fn headings(&self) -> impl Iterator<Item = (&Path, &SourceHeading)> {
+        self.files
+            .iter()
+            .flat_map(|(rel, file)| file.headings.iter().map(|h| (rel.as_path(), h)))
+    }
+
+    /// Indexed files as paths relative to the root, in path order, with the
+    /// links they contain.
+    pub fn links(&self) -> impl Iterator<Item = (&Path, &[OutgoingLink])> {
+        self.files
+            .iter()
+            .map(|(rel, file)| (rel.as_path(), file.links.as_slice()))
+    }
+
+    /// Bring the index up to date with the files on disk.
+    ///
+    /// Files are only read when new, when their modification time changed,
+    /// or when a folder config changed their slug style. Returns `true` if
+    /// anything was added, updated or removed.
+    pub fn refresh(&mut self) -> bool {
+        let update = self.scan(&CancelToken::default());
+        self.apply(update)
+    }
+
+    /// Read the files that [`SearchIndex::refresh`] would, without changing
+    /// the index, so it can be searched meanwhile. Pass the result to
+    /// [`SearchIndex::apply`].
+    ///
+    /// Stops early once `cancel` is set, keeping the files read so far.
+    pub fn scan(&self, cancel: &CancelToken) -> IndexUpdate {
+        let mut paths = Vec::new();
+        collect_md_files(&self.root, &mut paths);
+        let mut folders = FolderConfigs::new(&self.root);
+
+        let mut update = IndexUpdate::default();
+        let mut seen = Vec::with_capacity(paths.len());
+        for path in paths {
+            if cancel.is_cancelled() {
+                return update;
+            }
+            let Ok(rel) = path.strip_prefix(&self.root).map(Path::to_path_buf) else {
+                continue;
+            };
+            let Some(mtime) = modified_nanos(&path) else {
+                continue;
+            };
+            seen.push(rel.clone());
+
+            let slug_style = folders.for_file(&path).slug_style();
+            if self
+                .files
+                .get(&rel)
+                .is_some_and(|f| f.mtime == mtime && f.slug_style == slug_style)
+            {
+                continue;
+            }
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let file = IndexedFile::new(&rel, mtime, slug_style, &content);
+            update.changed.push((rel, file));
+        }
+        seen.sort();
+        update.seen = Some(seen);
+        update
+    }
+
+    /// Merge the files read by [`SearchIndex::scan`] into the index and,
+    /// unless the scan was cancelled, drop the files it no longer found.
+    /// Returns `true` if anything was added, updated or removed.
+    pub fn apply(&mut self, update: IndexUpdate) -> bool {
+        let changed = !update.changed.is_empty();
+        for (rel, file) in update.changed {
+            self.remove(&rel);
+            self.insert(rel, file);
+        }
+
+        let Some(seen) = update.seen else {
+            if changed {
+                self.vocabulary = Vocabulary::new(self.postings.keys());
+            }
+            return changed;
+        };
+        let removed: Vec<PathBuf> = self
+            .files
+            .keys()
+            .filter(|rel| seen.binary_search(rel).is_err())
+            .cloned()
+            .collect();
+        for rel in &removed {
+            self.remove(rel);
+        }
+
+        let changed = changed || !removed.is_empty();
+        if changed {
+            self.vocabulary = Vocabulary::new(self.postings.keys());
+        }
+        changed
+    }
+
+    /// Search the indexed files for matches of `query`.
+    ///
+    /// Behaves like [`crate::search::search_files`] but only reads files
+    /// whose indexed words can contain the query, and ranks them with
+    /// document frequencies from the whole index. Queries whose terms are
+    /// all words are scored from the word counts in the index, and files
+    /// are read best first until `max_results` of them match. Regex text and
+    /// text without word characters can only be scored by reading every
+    /// candidate file.
+    ///
+    /// Literal text, `title:`, `heading:` and `code:` terms narrow the files
+    /// read, and so do `AND` and `OR` of them. Some query shapes bypass the
+    /// index and read every indexed file: regex queries, and queries whose
+    /// only terms besides exclusions are `path:`, `tag:` or frontmatter
+    /// fields, or an `OR` with such a branch.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SearchError::InvalidRegex`] if `options.regex` is set and the
+    /// query does not compile, or [`SearchError::InvalidQuery`] if a structured
+    /// query does not parse.
+    pub fn search(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+        max_results: usize,
+    ) -> Result<Vec<SearchResult>, SearchError> {
+        self.search_streaming(
+            query,
+            options,
+            max_results,
+            &CancelToken::default(),
+            &mut |_| {},
+        )
+    }
+
+    /// Like [`SearchIndex::search`], but streams matches and progress to
+    /// `on_batch` as candidate files are read, and stops once `cancel` is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`SearchIndex::search`], or
+    /// [`SearchError::Cancelled`] if the search was cancelled.
+    pub fn search_streaming(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+        max_results: usize,
+        cancel: &CancelToken,
+        on_batch: &mut dyn FnMut(SearchBatch),
+    ) -> Result<Vec<SearchResult>, SearchError> {
+        self.prepare_search(query, options)?
+            .run(max_results, cancel, on_batch)
+    }
+
+    /// Pick and rank the files to read for `query`, as
+    /// [`SearchIndex::search_streaming`] does, without reading any of them.
+    ///
+    /// The result owns everything the search needs, so callers sharing the
+    /// index behind a lock can release it before running the search.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`SearchIndex::search`].
+    pub fn prepare_search(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<PreparedSearch, SearchError> {
+        if query.is_empty() {
+            return Ok(PreparedSearch {
+                query: None,
+                options: options.clone(),
+                scan: Scan {
+                    root: self.root.clone(),
+                    files: Vec::new(),
+                    ranking: Ranking::Known(Vec::new()),
+                },
+            });
+        }
+        let query = build_query(query, options)?;
+        let (words, patterns) = score_words(&query, options)?;
+        let mut stats = CorpusStats {
+            doc_count: self.files.len(),
+            avg_len: average(self.files.values().map(|f| f.len)),
+            ..CorpusStats::default()
+        };
+
+        let mut candidates = self
+            .candidates(&query, options, &mut stats)
+            .unwrap_or_else(|| self.files.keys().cloned().collect());
+        for word in &words {
+            if !stats.doc_freq.contains_key(word) {
+                let files = self.files_containing(word);
+                stats.doc_freq.insert(word.clone(), files.len());
+            }
+        }
+
+        let ranking = if patterns {
+            Ranking::Read(Some(stats))
+        } else {
+            let containing: Vec<HashSet<&str>> = words
+                .iter()
+                .map(|word| self.vocabulary.containing(word).into_iter().collect())
+                .collect();
+            let mut by_score: Vec<(f64, PathBuf)> = candidates
+                .into_iter()
+                .map(|rel| {
+                    let terms = self.files[&rel].document_terms(&containing);
+                    (bm25(&terms, &words, &stats), rel)
+                })
+                .collect();
+            by_score.sort_by(|a, b| b.0.total_cmp(&a.0));
+            let (scores, ranked) = by_score.into_iter().unzip();
+            candidates = ranked;
+            Ranking::Known(scores)
+        };
+        let files = candidates
+            .into_iter()
+            .map(|rel| {
+                let slugs = self.files[&rel].slug_style;
+                (self.root.join(rel), slugs)
+            })
+            .collect();
+        let scan = Scan {
+            root: self.root.clone(),
+            files,
+            ranking,
+        };
+        Ok(PreparedSearch {
+            query: Some(query),
+            options: options.clone(),
+            scan,
+        })
+    }
+
+    /// Sorted relative paths of the files that can match `query`, or `None`
+    /// if the index cannot narrow it down: for regex text, negations and
+    /// terms not matched against words, such as `path:` and `tag:`.
+    ///
+    /// Records the document frequency of every word looked up in `stats`.
+    fn candidates(
+        &self,
+        query: &Query,
+        options: &SearchOptions,
+        stats: &mut CorpusStats,
+    ) -> Option<Vec<PathBuf>> {
+        match query {
+            Query::Text(text)
+            | Query::Field {
+                field: Field::Title | Field::Heading | Field::Code,
+                value: text,
+            } if !options.regex => {
+                let mut result: Option<Vec<PathBuf>> = None;
+                for word in tokenize(text) {
+                    let files = self.files_containing(&word);
+                    stats.doc_freq.insert(word, files.len());
+                    result = Some(intersect(result, &files));
+                }
+                result
+            }
+            Query::And(children) => {
+                let mut result: Option<Vec<PathBuf>> = None;
+                for child in children {
+                    if let Some(files) = self.candidates(child, options, stats) {
+                        result = Some(intersect(result, &files));
+                    }
+                }
+                result
+            }
+            Query::Or(children) => {
+                let mut union = Vec::new();
+                for child in children {
+                    union.extend(self.candidates(child, options, stats)?);
+                }
+                union.sort();
+                union.dedup();
+                Some(union)
+            }
+            Query::Text(_) | Query::Field { .. } | Query::Not(_) => None,
+        }
+    }
+
+    /// Sorted relative paths of files with a word containing `word`.
+    ///
+    /// Every word of a literal query occurs inside some word of a matching
+    /// file. The first and last query words may be partial, so words are
+    /// compared by substring rather than equality.
+    fn files_containing(&self, word: &str) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self
+            .vocabulary
+            .containing(word)
+            .into_iter()
+            .flat_map(|term| self.postings[term].iter().cloned())
+            .collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    fn insert(&mut self, rel: PathBuf, file: IndexedFile) {
+        for (term, _) in &file.terms {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .push(rel.clone());
+        }
+        self.files.insert(rel, file);
+    }
+
+    fn remove(&mut self, rel: &Path) {
+        let Some(file) = self.files.remove(rel) else {
+            return;
+        };
+        for (term, _) in &file.terms {
+            if let Some(files) = self.postings.get_mut(term) {
+                files.retain(|p| p != rel);
+                if files.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+    }
+
+    fn rebuild_postings(&mut self) {
+        self.postings.clear();
+        for (rel, file) in &self.files {
+            for (term, _) in &file.terms {
+                self.postings
+                    .entry(term.clone())
+                    .or_default()
+                    .push(rel.clone());
+            }
+        }
+        self.vocabulary = Vocabulary::new(self.postings.keys());
+    }
+}
+
+/// Every indexed word with its suffixes in sorted order, so the words that
+/// contain a query word are found by binary search rather than by comparing
+/// against the whole vocabulary.
+#[derive(Debug, Default)]
+struct Vocabulary {
+    /// Distinct words, sorted.
+    words: Vec<String>,
+    /// Word index and byte offset of every suffix starting at a character
+    /// boundary, sorted by the suffix text.
+    suffixes: Vec<(usize, usize)>,
+}
+
+impl Vocabulary {
+    fn new<'a>(words: impl Iterator<Item = &'a String>) -> Self {
+        let mut words: Vec<String> = words.cloned().collect();
+        words.sort_unstable();
+        let mut suffixes: Vec<(usize, usize)> = words
+            .iter()
+            .enumerate()
+            .flat_map(|(i, word)| word.char_indices().map(move |(offset, _)| (i, offset)))
+            .collect();
+        suffixes.sort_unstable_by(|&(a, i), &(b, j)| words[a][i..].cmp(&words[b][j..]));
+        Self { words, suffixes }
+    }
+
+    fn suffix(&self, (word, offset): (usize, usize)) -> &str {
+        &self.words[word][offset..]
+    }
+
+    /// Words containing `part`, each once. Every occurrence of `part` starts
+    /// a suffix, and suffixes starting with `part` are adjacent once sorted.
+    fn containing(&self, part: &str) -> Vec<&str> {
+        let start = self.suffixes.partition_point(|&s| self.suffix(s) < part);
+        let mut found: Vec<usize> = self.suffixes[start..]
+            .iter()
+            .take_while(|&&s| self.suffix(s).starts_with(part))
+            .map(|&(word, _)| word)
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found.into_iter().map(|i| self.words[i].as_str()).collect()
+    }
+}
+
+/// A search prepared by [`SearchIndex::prepare_search`]: the files to read,
+/// in order, with their slug styles and scores.
+pub struct PreparedSearch {
+    /// The parsed query, or `None` if it was empty.
+    query: Option<Query>,
+    options: SearchOptions,
+    scan: Scan,
+}
+
+impl PreparedSearch {
+    /// Read the chosen files and return the `max_results` best matches,
+    /// streaming them to `on_batch` as [`SearchIndex::search_streaming`] does.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SearchError::Cancelled`] if the search was cancelled.
+    pub fn run(
+        &self,
+        max_results: usize,
+        cancel: &CancelToken,
+        on_batch: &mut dyn FnMut(SearchBatch),
+    ) -> Result<Vec<SearchResult>, SearchError> {
+        let Some(query) = &self.query else {
+            return Ok(Vec::new());
+        };
+        search_paths(
+            &self.scan,
+            query,
+            &self.options,
+            max_results,
+            cancel,
+            on_batch,
+        )
+    }
+}
+
+/// Files in both `prev` and `files`; just `files` if there is no `prev` yet.
+fn intersect(prev: Option<Vec<PathBuf>>, files: &[PathBuf]) -> Vec<PathBuf> {
+    match prev {
+        None => files.to_vec(),
+        Some(prev) => prev
+            .into_iter()
+            .filter(|rel| files.binary_search(rel).is_ok())
+            .collect(),
+    }
+}
+
+/// File name for the index of `root`: a stable FNV-1a hash of its path.
+fn index_file(root: &Path, index_dir: &Path) -> PathBuf {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in root.to_string_lossy().bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    index_dir.join(format!("{hash:016x}.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::links::LinkGraph;
+    use crate::tags::TagIndex;
+    use std::fs;
+
+    fn index_of(root: &Path) -> SearchIndex {
+        let mut index = SearchIndex::new(root);
+        index.refresh();
+        index
+    }
+
+    /// Names of the files in `results`, sorted so tests don't depend on ranking.
+    fn result_names(results: &[SearchResult]) -> Vec<&str> {
+        let mut names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn test_search_matches_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "# Alpha\nhello world").unwrap();
+        fs::write(dir.path().join("b.md"), "# Beta\nnothing here").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/c.md"), "say Hello").unwrap();
+
+        let index = index_of(dir.path());
+        assert_eq!(index.len(), 3);
+        let results = index
+            .search("hello", &SearchOptions::default(), 50)
+            .unwrap();
+        assert_eq!(result_names(&results), vec!["a.md", "c.md"]);
+    }
+
+    #[test]
+    fn test_partial_words_match() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "hello world").unwrap();
+
+        let index = index_of(dir.path());
+        let results = index
+            .search("lo wor", &SearchOptions::default(), 50)
+            .unwrap();
+        assert_eq!(result_names(&results), vec!["a.md"]);
+        let results = index
+            .search("lo xyz", &SearchOptions::default(), 50)
+            .unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_vocabulary_finds_words_containing_part() {
+        let words = ["interest", "rest", "restore", "test", "über"].map(String::from);
+        let vocabulary = Vocabulary::new(words.iter());
+        assert_eq!(
+            vocabulary.containing("rest"),
+            vec!["interest", "rest", "restore"]
+        );
+        assert_eq!(vocabulary.containing("tor"), vec!["restore"]);
+        assert_eq!(vocabulary.containing("ber"), vec!["über"]);
+        assert!(vocabulary.containing("xyz").is_empty());
+    }
+
+    #[test]
+    fn test_folded_terms() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "Die STRASSE").unwrap();
+
+        let index = index_of(dir.path());
+        let results = index
+            .search("Straße", &SearchOptions::default(), 50)
+            .unwrap();
+        assert_eq!(result_names(&results), vec!["a.md"]);
+    }
+
+    #[test]
+    fn test_results_scored_from_index() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "# Intro\nmentions deploy once").unwrap();
+        fs::write(dir.path().join("b.md"), "# Deploy\nhow to deploy").unwrap();
+        fs::write(dir.path().join("c.md"), "deployment notes").unwrap();
+
+        let index = index_of(dir.path());
+        let all = index
+            .search("deploy", &SearchOptions::default(), 50)
+            .unwrap();
+        assert_eq!(result_names(&all), vec!["a.md", "b.md", "c.md"]);
+        assert_eq!(all[0].name, "b.md");
+        assert!(all.windows(2).all(|w| w[0].score >= w[1].score));
+
+        let best = index
+            .search("deploy", &SearchOptions::default(), 1)
+            .unwrap();
+        assert_eq!(best.len(), 1);
+        assert_eq!(best[0].name, "b.md");
+        assert!((best[0].score - all[0].score).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_regex_reads_all_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "version 1.2").unwrap();
+
+        let index = index_of(dir.path());
+        let options = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        let results = index.search(r"\d\.\d", &options, 50).unwrap();
+        assert_eq!(result_names(&results), vec!["a.md"]);
+    }
+
+    #[test]
+    fn test_structured_query_matches_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "install on linux").unwrap();
+        fs::write(dir.path().join("b.md"), "install on windows").unwrap();
+        fs::create_dir(dir.path().join("old")).unwrap();
+        fs::write(dir.path().join("old/c.md"), "install on linux").unwrap();
+
+        let index = index_of(dir.path());
+        let options = SearchOptions {
+            structured: true,
+            ..SearchOptions::default()
+        };
+        let results = index
+            .search("install (linux OR mac) -path:old", &options, 50)
+            .unwrap();
+        assert_eq!(result_names(&results), vec!["a.md"]);
+    }
+
+    #[test]
+    fn test_files_have_titles() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "---\ntitle: Alpha\n---\ntext").unwrap();
+        fs::write(dir.path().join("b.md"), "text").unwrap();
+
+        let index = index_of(dir.path());
+        let files: Vec<(&Path, Option<&str>)> = index.files().collect();
+        assert_eq!(
+            files,
+            vec![
+                (Path::new("a.md"), Some("Alpha")),
+                (Path::new("b.md"), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_headings_are_indexed() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("a.md"),
+            "---\ntitle: A\n---\n# Intro\n\n## Setup\n\n# Intro\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("b.md"), "no headings").unwrap();
+
+        let index = index_of(dir.path());
+        let headings: Vec<(&Path, u8, &str)> = index
+            .headings()
+            .map(|(rel, h)| (rel, h.level, h.id.as_str()))
+            .collect();
+        assert_eq!(
+            headings,
+            vec![
+                (Path::new("a.md"), 1, "intro"),
+                (Path::new("a.md"), 2, "setup"),
+                (Path::new("a.md"), 1, "intro-1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_backlinks_follow_refresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.md");
+        fs::write(&a, "---\ntitle: A\n---\nSee [B](b.md#intro).\n").unwrap();
+        fs::write(dir.path().join("b.md"), "# Intro\n").unwrap();
+
+        let mut index = index_of(dir.path());
+        let graph = LinkGraph::from_index(&index);
+        let backlinks = graph.backlinks(&dir.path().join("b.md"));
+        assert_eq!(backlinks.len(), 1);
+        assert_eq!(backlinks[0].rel_path, "a.md");
+        assert_eq!(backlinks[0].line, 4);
+        assert_eq!(backlinks[0].heading.as_deref(), Some("Intro"));
+
+        fs::write(&a, "No links now.\n").unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&a)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(index.refresh());
+        let graph = LinkGraph::from_index(&index);
+        assert!(graph.backlinks(Path::new("b.md")).is_empty());
+    }
+
+    #[test]
+    fn test_slug_style_change_reindexes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "# my_function — overview\n").unwrap();
+
+        let mut index = index_of(dir.path());
+        let id = |index: &SearchIndex| index.headings().next().unwrap().1.id.clone();
+        assert_eq!(id(&index), "myfunction-overview");
+
+        fs::write(
+            dir.path().join(crate::config::FOLDER_CONFIG_FILE),
+            r#"{"slug_style":"github"}"#,
+        )
+        .unwrap();
+        assert!(index.refresh());
+        assert_eq!(id(&index), "my_function--overview");
+        assert!(!index.refresh());
+    }
+
+    #[test]
+    fn test_scan_leaves_index_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "first").unwrap();
+
+        let mut index = index_of(dir.path());
+        fs::write(dir.path().join("b.md"), "second").unwrap();
+        let update = index.scan(&CancelToken::default());
+        assert!(
+            index
+                .search("second", &SearchOptions::default(), 50)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(index.apply(update));
+        let results = index
+            .search("second", &SearchOptions::default(), 50)
+            .unwrap();
+        assert_eq!(result_names(&results), vec!["b.md"]);
+    }
+
+    #[test]
+    fn test_cancelled_scan_keeps_unseen_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "first").unwrap();
+
+        let mut index = index_of(dir.path());
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let update = index.scan(&cancel);
+        assert!(!update.is_complete());
+        assert!(!index.apply(update));
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn test_refresh_is_incremental() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.md");
+        fs::write(&a, "old text").unwrap();
+        fs::write(dir.path().join("b.md"), "other").unwrap();
+
+        let mut index = index_of(dir.path());
+        assert!(!index.refresh(), "unchanged files must not be reindexed");
+
+        fs::write(&a, "new text").unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&a)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        fs::remove_file(dir.path().join("b.md")).unwrap();
+
+        assert!(index.refresh());
+        assert_eq!(index.len(), 1);
+        assert!(
+            index
+                .search("old", &SearchOptions::default(), 50)
+                .unwrap()
+                .is_empty()
+        );
+        let results = index.search("new", &SearchOptions::default(), 50).unwrap();
+        assert_eq!(result_names(&results), vec!["a.md"]);
+    }
+
+    #[test]
+    fn test_tags_follow_refresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.md");
+        fs::write(&a, "---\ntags: [ops]\n---\nSee #runbook").unwrap();
+        fs::write(dir.path().join("b.md"), "#ops only").unwrap();
+
+        let mut index = index_of(dir.path());
+        let tags: Vec<(String, usize)> = TagIndex::from_index(&index)
+            .tags()
+            .into_iter()
+            .map(|t| (t.tag, t.count))
+            .collect();
+        assert_eq!(
+            tags,
+            vec![("ops".to_string(), 2), ("runbook".to_string(), 1)]
+        );
+
+        fs::write(&a, "no tags now").unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&a)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(index.refresh());
+        let tags = TagIndex::from_index(&index);
+        assert!(tags.files("runbook").is_empty());
+        assert_eq!(tags.files("ops")[0].rel_path, "b.md");
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "persisted content").unwrap();
+
+        index_of(dir.path()).save(store.path()).unwrap();
+
+        let mut loaded = SearchIndex::load(dir.path(), store.path());
+        assert_eq!(loaded.len(), 1);
+        assert!(!loaded.refresh());
+        let results = loaded
+            .search("persisted", &SearchOptions::default(), 50)
+            .unwrap();
+        assert_eq!(result_names(&results), vec!["a.md"]);
+    }
+
+    #[test]
+    fn test_load_other_root_starts_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let other = tempfile::tempdir().unwrap();
+        let store = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "text").unwrap();
+        index_of(dir.path()).save(store.path()).unwrap();
+
+        assert!(SearchIndex::load(other.path(), store.path()).is_empty());
+    }
+}
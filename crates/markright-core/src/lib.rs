@@ -1,8 +1,19 @@
 pub mod ast;
 pub mod config;
+pub mod files;
 pub mod frontmatter;
+pub mod fuzzy;
+pub mod index;
 pub mod license;
+pub mod links;
 pub mod parser;
+pub mod query;
+mod rank;
+pub mod replace;
+pub mod saved_search;
+pub mod schema;
 pub mod search;
+pub mod slug;
+pub mod tags;
 pub mod toc;
 pub mod tree;
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{SourceHeading, source_links};
+use crate::index::SearchIndex;
+
+/// A link from a Markdown file to a file under the same root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutgoingLink {
+    /// Linked file, relative to the root.
+    pub target: PathBuf,
+    /// Fragment after the `#`, if any.
+    pub anchor: Option<String>,
+    /// Plain text of the link.
+    pub text: String,
+    /// 1-based line of the link in the linking file.
+    pub line: usize,
+    /// Plain text of that line.
+    pub context: String,
+}
+
+/// A link to a file, seen from the file it points to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Backlink {
+    /// The linking file.
+    pub path: PathBuf,
+    /// Path of the linking file relative to the root, with `/` separators.
+    pub rel_path: String,
+    pub text: String,
+    /// 1-based line of the link in the linking file.
+    pub line: usize,
+    /// Plain text of that line.
+    pub context: String,
+    pub anchor: Option<String>,
+    /// Text of the heading the anchor points to, or `None` if there is no
+    /// anchor or no heading has that ID.
+    pub heading: Option<String>,
+}
+
+/// The links of a file at `rel` whose body follows `body_line` lines of
+/// frontmatter. Links that leave the root or point elsewhere than a file,
+/// such as web and mail links, are left out.
+pub fn outgoing_links(rel: &Path, body: &str, body_line: usize) -> Vec<OutgoingLink> {
+    source_links(body)
+        .into_iter()
+        .filter_map(|link| {
+            let (target, anchor) = resolve_link(rel, &link.url)?;
+            Some(OutgoingLink {
+                target,
+                anchor,
+                text: link.text,
+                line: body_line + link.line,
+                context: link.context,
+            })
+        })
+        .collect()
+}
+
+/// Resolve a link destination written in the file at `from`, relative to the
+/// root, to the linked file and anchor.
+///
+/// Relative destinations are resolved against the directory of `from`, and
+/// destinations starting with `/` against the root. An anchor on its own
+/// points into `from` itself. Returns `None` for URLs with a scheme and for
+/// paths that climb out of the root.
+pub fn resolve_link(from: &Path, url: &str) -> Option<(PathBuf, Option<String>)> {
+    if url.starts_with("//") || has_scheme(url) {
+        return None;
+    }
+    let (path, anchor) = match url.split_once('#') {
+        Some((path, anchor)) => (path, Some(anchor)),
+        None => (url, None),
+    };
+    let path = path.split_once('?').map_or(path, |(path, _)| path);
+    let anchor = anchor.filter(|a| !a.is_empty()).map(percent_decode);
+
+    if path.is_empty() {
+        return anchor.is_some().then(|| (from.to_path_buf(), anchor));
+    }
+    let path = percent_decode(path);
+    let (base, path) = match path.strip_prefix('/') {
+        Some(path) => (Path::new(""), path),
+        None => (from.parent().unwrap_or(Path::new("")), path.as_str()),
+    };
+
+    let mut target = PathBuf::new();
+    for component in base.join(path).components() {
+        match component {
+            Component::Normal(part) => target.push(part),
+            Component::ParentDir => {
+                if !target.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    if target.as_os_str().is_empty() {
+        return None;
+    }
+    Some((target, anchor))
+}
+
+/// Whether `url` starts with a scheme such as `https:` or `mailto:`.
+fn has_scheme(url: &str) -> bool {
+    let Some(colon) = url.find(':') else {
+        return false;
+    };
+    let scheme = &url[..colon];
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+/// Decode `%XX` escapes, as in `my%20notes.md`.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) if bytes[i] == b'%' => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The links between the files of a folder, looked up by linked file.
+///
+/// Built from the [`SearchIndex`], which reads each file's links when it is
+/// indexed, so refreshing the index and rebuilding the graph only reads the
+/// files that changed.
+pub struct LinkGraph {
+    root: PathBuf,
+    /// Linked file, relative to the root, to the links pointing at it in
+    /// path and then line order.
+    backlinks: HashMap<PathBuf, Vec<Backlink>>,
+}
+
+impl LinkGraph {
+    /// Collect the links of the files in `index`.
+    pub fn from_index(index: &SearchIndex) -> Self {
+        Self::new(index.root(), index.links(), index.headings())
+    }
+
+    /// Collect `links`, given per linking file relative to `root`, resolving
+    /// anchors against `headings`.
+    pub fn new<'a>(
+        root: &Path,
+        links: impl IntoIterator<Item = (&'a Path, &'a [OutgoingLink])>,
+        headings: impl IntoIterator<Item = (&'a Path, &'a SourceHeading)>,
+    ) -> Self {
+        let mut heading_text: HashMap<(&Path, &str), &str> = HashMap::new();
+        for (rel, heading) in headings {
+            heading_text
+                .entry((rel, heading.id.as_str()))
+                .or_insert(heading.text.as_str());
+        }
+
+        let mut backlinks: HashMap<PathBuf, Vec<Backlink>> = HashMap::new();
+        for (rel, file_links) in links {
+            let rel_path = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            // Links within a file are not backlinks.
+            for link in file_links.iter().filter(|l| l.target != rel) {
+                let heading = link
+                    .anchor
+                    .as_deref()
+                    .and_then(|anchor| heading_text.get(&(link.target.as_path(), anchor)));
+                backlinks
+                    .entry(link.target.clone())
+                    .or_default()
+                    .push(Backlink {
+                        path: root.join(rel),
+                        rel_path: rel_path.clone(),
+                        text: link.text.clone(),
+                        line: link.line,
+                        context: link.context.clone(),
+                        anchor: link.anchor.clone(),
+                        heading: heading.map(|text| (*text).to_string()),
+                    });
+            }
+        }
+        Self {
+            root: root.to_path_buf(),
+            backlinks,
+        }
+    }
+
+    /// The root folder the links were collected from.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The links pointing at `path`, given as an absolute path or relative
+    /// to the root.
+    pub fn backlinks(&self, path: &Path) -> &[Backlink] {
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        self.backlinks.get(rel).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(from: &str, url: &str) -> Option<(String, Option<String>)> {
+        resolve_link(Path::new(from), url)
+            .map(|(target, anchor)| (target.to_string_lossy().into_owned(), anchor))
+    }
+
+    #[test]
+    fn test_resolve_link() {
+        let found = |target: &str, anchor: Option<&str>| {
+            Some((target.to_string(), anchor.map(str::to_string)))
+        };
+        assert_eq!(resolve("docs/a.md", "b.md"), found("docs/b.md", None));
+        assert_eq!(
+            resolve("docs/a.md", "../guide/./setup.md#linux"),
+            found("guide/setup.md", Some("linux"))
+        );
+        assert_eq!(resolve("docs/a.md", "/index.md"), found("index.md", None));
+        assert_eq!(
+            resolve("docs/a.md", "#see-also"),
+            found("docs/a.md", Some("see-also"))
+        );
+        assert_eq!(
+            resolve("a.md", "my%20notes.md?raw=1"),
+            found("my notes.md", None)
+        );
+        assert_eq!(resolve("a.md", "../outside.md"), None);
+        assert_eq!(resolve("a.md", "https://example.com/b.md"), None);
+        assert_eq!(resolve("a.md", "mailto:me@example.com"), None);
+        assert_eq!(resolve("a.md", "//example.com/b.md"), None);
+        assert_eq!(resolve("a.md", "#"), None);
+    }
+
+    #[test]
+    fn test_backlinks() {
+        let a_links = outgoing_links(
+            Path::new("a.md"),
+            "See [setup](docs/setup.md#install) and [top](#top).\n",
+            0,
+        );
+        let b_links = outgoing_links(
+            Path::new("docs/b.md"),
+            "\n# B\n\nBack to [the guide](setup.md#missing).\n",
+            3,
+        );
+        let headings = [SourceHeading {
+            level: 2,
+            text: "Install".to_string(),
+            id: "install".to_string(),
+            line: 5,
+        }];
+        let graph = LinkGraph::new(
+            Path::new("/root"),
+            [
+                (Path::new("a.md"), a_links.as_slice()),
+                (Path::new("docs/b.md"), b_links.as_slice()),
+            ],
+            [(Path::new("docs/setup.md"), &headings[0])],
+        );
+
+        let backlinks = graph.backlinks(Path::new("/root/docs/setup.md"));
+        assert_eq!(
+            backlinks,
+            vec![
+                Backlink {
+                    path: PathBuf::from("/root/a.md"),
+                    rel_path: "a.md".to_string(),
+                    text: "setup".to_string(),
+                    line: 1,
+                    context: "See setup and top.".to_string(),
+                    anchor: Some("install".to_string()),
+                    heading: Some("Install".to_string()),
+                },
+                Backlink {
+                    path: PathBuf::from("/root/docs/b.md"),
+                    rel_path: "docs/b.md".to_string(),
+                    text: "the guide".to_string(),
+                    line: 7,
+                    context: "Back to the guide.".to_string(),
+                    anchor: Some("missing".to_string()),
+                    heading: None,
+                },
+            ]
+        );
+        assert!(graph.backlinks(Path::new("a.md")).is_empty());
+    }
+}
@@ -0,0 +1,464 @@
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// A parsed structured search query.
+///
+/// Syntax, loosest binding first:
+///
+/// - `a OR b` matches documents matching either side.
+/// - `a AND b`, or just `a b`, matches documents matching both.
+/// - `-a` excludes documents matching `a`. A `-` only negates at the start
+///   of a term, so `foo-bar` and `--verbose` are plain words.
+/// - `(a OR b) c` groups with parentheses.
+/// - `word` or `"quoted phrase"` matches text anywhere in the document.
+/// - `path:`, `title:`, `tag:`, `heading:` and `code:` restrict a word or
+///   phrase to one part of the document; any other `name:value` compares a
+///   frontmatter field. URLs such as `https://example.com` are plain words.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// A word or phrase matched anywhere in the document.
+    Text(String),
+    /// A word or phrase matched against one part of the document.
+    Field {
+        field: Field,
+        value: String,
+    },
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+/// The part of a document a [`Query::Field`] applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Field {
+    /// The file path relative to the searched folder.
+    Path,
+    /// The frontmatter title, or the first level-1 heading.
+    Title,
+    /// A frontmatter tag, compared as a whole.
+    Tag,
+    /// Heading lines.
+    Heading,
+    /// Lines inside fenced code blocks.
+    Code,
+    /// A frontmatter field, compared as a whole.
+    Frontmatter(String),
+}
+
+/// A syntax error in a structured query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub message: String,
+    /// Character offset of the error within the query.
+    pub position: usize,
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Parse a structured query.
+///
+/// # Errors
+///
+/// Returns a [`QueryError`] for unbalanced quotes or parentheses, operators
+/// without an operand, and queries with no terms at all. Queries that only
+/// exclude, such as `-draft` or `foo OR -bar`, are rejected too, as they
+/// would match nearly every file.
+pub fn parse_query(input: &str) -> Result<Query, QueryError> {
+    let tokens = lex(input)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        end: input.chars().count(),
+    };
+    let query = parser.parse_or()?;
+    match parser.peek() {
+        None if !requires_match(&query) => Err(error("expected a term that is not excluded", 0)),
+        None => Ok(query),
+        Some((Token::Close, at)) => Err(error("unmatched `)`", *at)),
+        Some((_, at)) => Err(error("unexpected token", *at)),
+    }
+}
+
+/// Whether every document matching `query` must match one of its terms,
+/// rather than just lack the excluded ones.
+fn requires_match(query: &Query) -> bool {
+    match query {
+        Query::Text(_) | Query::Field { .. } => true,
+        Query::And(terms) => terms.iter().any(requires_match),
+        Query::Or(branches) => branches.iter().all(requires_match),
+        Query::Not(_) => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    Field(String, String),
+    And,
+    Or,
+    Minus,
+    Open,
+    Close,
+}
+
+fn error(message: &str, position: usize) -> QueryError {
+    QueryError {
+        message: message.to_string(),
+        position,
+    }
+}
+
+/// Split the query into tokens, each paired with its character offset.
+fn lex(input: &str) -> Result<Vec<(Token, usize)>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    // Character offset of the byte offset `counted`, advanced token by token.
+    let (mut counted, mut at) = (0, 0);
+
+    while let Some(&(byte, c)) = chars.peek() {
+        at += input[counted..byte].chars().count();
+        counted = byte;
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push((Token::Open, at));
+            }
+            ')' => {
+                chars.next();
+                tokens.push((Token::Close, at));
+            }
+            '"' => {
+                chars.next();
+                let phrase = read_phrase(&mut chars).ok_or_else(|| error("unclosed `\"`", at))?;
+                tokens.push((Token::Phrase(phrase), at));
+            }
+            '-' if negates(input, byte) => {
+                chars.next();
+                tokens.push((Token::Minus, at));
+            }
+            _ => {
+                let word = read_word(&mut chars);
+                let token = match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    _ => match field_name(&word) {
+                        Some(name) if word.len() == name.len() + 1 => {
+                            // `name:"quoted value"`
+                            if chars.peek().is_some_and(|&(_, c)| c == '"') {
+                                chars.next();
+                                let value = read_phrase(&mut chars)
+                                    .ok_or_else(|| error("unclosed `\"`", at))?;
+                                Token::Field(name.to_string(), value)
+                            } else {
+                                Token::Word(word)
+                            }
+                        }
+                        Some(name) => {
+                            Token::Field(name.to_string(), word[name.len() + 1..].to_string())
+                        }
+                        None => Token::Word(word),
+                    },
+                };
+                tokens.push((token, at));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Whether the `-` at `byte` negates what follows: it must start a token and
+/// be followed by the start of a term.
+fn negates(input: &str, byte: usize) -> bool {
+    let starts_token = input[..byte]
+        .chars()
+        .next_back()
+        .is_none_or(|c| c.is_whitespace() || c == '(');
+    let before_term = input[byte + 1..]
+        .chars()
+        .next()
+        .is_some_and(|c| !c.is_whitespace() && c != '-' && c != ')');
+    starts_token && before_term
+}
+
+/// Read up to the closing quote, which is consumed. `None` if it is missing.
+fn read_phrase(chars: &mut Peekable<CharIndices<'_>>) -> Option<String> {
+    let mut phrase = String::new();
+    for (_, c) in chars.by_ref() {
+        if c == '"' {
+            return Some(phrase);
+        }
+        phrase.push(c);
+    }
+    None
+}
+
+fn read_word(chars: &mut Peekable<CharIndices<'_>>) -> String {
+    let mut word = String::new();
+    while let Some(&(_, c)) = chars.peek() {
+        // A quote ends the word so `name:"value"` can be read by the caller.
+        if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+            break;
+        }
+        word.push(c);
+        chars.next();
+    }
+    word
+}
+
+/// The field name of a `name:value` word, if it has one. A value starting
+/// with `//` makes the word a URL rather than a field.
+fn field_name(word: &str) -> Option<&str> {
+    let (name, value) = word.split_once(':')?;
+    let valid = !name.is_empty()
+        && !value.starts_with("//")
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    valid.then_some(name)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// Character length of the input, reported for errors at the end.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&(Token, usize)> {
+        self.tokens.get(self.pos)
+    }
+
+    fn position(&self) -> usize {
+        self.peek().map_or(self.end, |(_, at)| *at)
+    }
+
+    fn parse_or(&mut self) -> Result<Query, QueryError> {
+        let mut branches = vec![self.parse_and()?];
+        while let Some((Token::Or, _)) = self.peek() {
+            self.pos += 1;
+            branches.push(self.parse_and()?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.remove(0)
+        } else {
+            Query::Or(branches)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Query, QueryError> {
+        let mut terms = vec![self.parse_unary()?];
+        loop {
+            match self.peek() {
+                None | Some((Token::Or | Token::Close, _)) => break,
+                Some((Token::And, _)) => {
+                    self.pos += 1;
+                    terms.push(self.parse_unary()?);
+                }
+                Some(_) => terms.push(self.parse_unary()?),
+            }
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            Query::And(terms)
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, QueryError> {
+        if let Some((Token::Minus, _)) = self.peek() {
+            self.pos += 1;
+            return Ok(Query::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Query, QueryError> {
+        let at = self.position();
+        let Some((token, _)) = self.tokens.get(self.pos).cloned() else {
+            return Err(error("expected a search term", at));
+        };
+        self.pos += 1;
+        match token {
+            Token::Word(text) | Token::Phrase(text) => Ok(Query::Text(text)),
+            Token::Field(name, value) => {
+                let field = match name.as_str() {
+                    "path" => Field::Path,
+                    "title" => Field::Title,
+                    "tag" => Field::Tag,
+                    "heading" => Field::Heading,
+                    "code" => Field::Code,
+                    _ => Field::Frontmatter(name),
+                };
+                Ok(Query::Field { field, value })
+            }
+            Token::Open => {
+                let inner = self.parse_or()?;
+                match self.peek() {
+                    Some((Token::Close, _)) => {
+                        self.pos += 1;
+                        Ok(inner)
+                    }
+                    _ => Err(error("missing `)`", at)),
+                }
+            }
+            Token::And | Token::Or | Token::Close | Token::Minus => {
+                Err(error("expected a search term", at))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Query {
+        Query::Text(s.to_string())
+    }
+
+    fn field(field: Field, value: &str) -> Query {
+        Query::Field {
+            field,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_implicit_and() {
+        assert_eq!(
+            parse_query("foo bar").unwrap(),
+            Query::And(vec![text("foo"), text("bar")])
+        );
+        assert_eq!(
+            parse_query("foo AND bar").unwrap(),
+            Query::And(vec![text("foo"), text("bar")])
+        );
+    }
+
+    #[test]
+    fn test_or_binds_looser_than_and() {
+        assert_eq!(
+            parse_query("a b OR c").unwrap(),
+            Query::Or(vec![Query::And(vec![text("a"), text("b")]), text("c")])
+        );
+    }
+
+    #[test]
+    fn test_phrase_and_exclude() {
+        assert_eq!(
+            parse_query("\"getting started\" -draft").unwrap(),
+            Query::And(vec![
+                text("getting started"),
+                Query::Not(Box::new(text("draft"))),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_fields() {
+        assert_eq!(
+            parse_query("path:docs/api tag:ops status:draft").unwrap(),
+            Query::And(vec![
+                field(Field::Path, "docs/api"),
+                field(Field::Tag, "ops"),
+                field(Field::Frontmatter("status".to_string()), "draft"),
+            ])
+        );
+        assert_eq!(
+            parse_query("heading:\"Debian setup\" code:apt").unwrap(),
+            Query::And(vec![
+                field(Field::Heading, "Debian setup"),
+                field(Field::Code, "apt"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_groups() {
+        assert_eq!(
+            parse_query("(title:install OR title:setup) -path:archive").unwrap(),
+            Query::And(vec![
+                Query::Or(vec![
+                    field(Field::Title, "install"),
+                    field(Field::Title, "setup"),
+                ]),
+                Query::Not(Box::new(field(Field::Path, "archive"))),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_colon_without_valid_name_is_text() {
+        assert_eq!(parse_query(":foo").unwrap(), text(":foo"));
+        assert_eq!(parse_query("a.b:c").unwrap(), text("a.b:c"));
+    }
+
+    #[test]
+    fn test_minus_only_negates_terms() {
+        assert_eq!(parse_query("--verbose").unwrap(), text("--verbose"));
+        assert_eq!(parse_query("foo-bar").unwrap(), text("foo-bar"));
+        assert_eq!(
+            parse_query("foo - bar").unwrap(),
+            Query::And(vec![text("foo"), text("-"), text("bar")])
+        );
+        assert_eq!(
+            parse_query("x -(a OR b) -\"c d\"").unwrap(),
+            Query::And(vec![
+                text("x"),
+                Query::Not(Box::new(Query::Or(vec![text("a"), text("b")]))),
+                Query::Not(Box::new(text("c d"))),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_urls_are_text() {
+        assert_eq!(
+            parse_query("https://example.com/a:b").unwrap(),
+            text("https://example.com/a:b")
+        );
+        assert_eq!(
+            parse_query("see -http://old.example.com").unwrap(),
+            Query::And(vec![
+                text("see"),
+                Query::Not(Box::new(text("http://old.example.com"))),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse_query("\"open").unwrap_err().position, 0);
+        assert_eq!(parse_query("foo OR").unwrap_err().position, 6);
+        assert_eq!(parse_query("(foo").unwrap_err().message, "missing `)`");
+        assert_eq!(parse_query("foo)").unwrap_err().message, "unmatched `)`");
+        assert!(parse_query("").is_err());
+    }
+
+    #[test]
+    fn test_exclusions_need_a_term() {
+        let message = "expected a term that is not excluded";
+        assert_eq!(parse_query("-draft").unwrap_err().message, message);
+        assert_eq!(parse_query("-a -b").unwrap_err().message, message);
+        assert_eq!(parse_query("foo OR -bar").unwrap_err().message, message);
+        assert!(parse_query("foo -bar").is_ok());
+        assert!(parse_query("(foo OR bar) -baz").is_ok());
+    }
+
+    #[test]
+    fn test_error_positions_count_characters() {
+        assert_eq!(parse_query("ünï (foo").unwrap_err().position, 4);
+        assert_eq!(parse_query("日本 \"語").unwrap_err().position, 3);
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::search::{Document, LineKind, tokenize};
+
+/// BM25 term-frequency saturation.
+const BM25_K1: f64 = 1.2;
+/// BM25 document-length normalization strength.
+const BM25_B: f64 = 0.75;
+
+/// How much a term occurrence counts in each field, relative to body text.
+const TITLE_WEIGHT: f64 = 4.0;
+const FILENAME_WEIGHT: f64 = 3.0;
+const HEADING_WEIGHT: f64 = 2.0;
+const BODY_WEIGHT: f64 = 1.0;
+
+/// Corpus-wide statistics for BM25 scoring.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CorpusStats {
+    /// Number of documents in the corpus.
+    pub doc_count: usize,
+    /// Average document length in words.
+    pub avg_len: f64,
+    /// Number of documents containing each query term.
+    pub doc_freq: HashMap<String, usize>,
+}
+
+/// Field-weighted frequencies of the query terms in one document.
+#[derive(Debug)]
+pub(crate) struct DocumentTerms {
+    /// Weighted frequency of each query term, in query order.
+    pub freqs: Vec<f64>,
+    /// Document length in words.
+    pub len: usize,
+}
+
+impl DocumentTerms {
+    /// Count query terms in each field of a document.
+    ///
+    /// `count` returns the number of occurrences of each query term in a
+    /// piece of text.
+    pub fn new(doc: &Document, count: impl Fn(&str) -> Vec<usize>) -> Self {
+        let mut freqs = Vec::new();
+        for (text, weight) in fields(doc).iter().zip(FIELD_WEIGHTS) {
+            for (i, n) in count(text).into_iter().enumerate() {
+                if freqs.len() <= i {
+                    freqs.push(0.0);
+                }
+                freqs[i] += weight * f64::from(u32::try_from(n).unwrap_or(u32::MAX));
+            }
+        }
+
+        Self {
+            freqs,
+            len: tokenize(doc.content).len(),
+        }
+    }
+}
+
+/// Weights of the fields returned by [`fields`], in order.
+const FIELD_WEIGHTS: [f64; 4] = [TITLE_WEIGHT, FILENAME_WEIGHT, HEADING_WEIGHT, BODY_WEIGHT];
+
+/// The text of each scored field of a document: the frontmatter title, the
+/// file name, headings and the remaining text outside frontmatter.
+fn fields(doc: &Document) -> [String; 4] {
+    // A title taken from the first heading is already counted as a heading.
+    let title = doc
+        .frontmatter
+        .as_ref()
+        .and_then(|fm| fm.title.clone())
+        .unwrap_or_default();
+    let stem = doc
+        .name
+        .strip_suffix(".md")
+        .unwrap_or(&doc.name)
+        .to_string();
+    let mut headings = String::new();
+    let mut prose = String::new();
+    for (i, line) in doc.lines.iter().enumerate() {
+        let target = match doc.kind(i) {
+            LineKind::Frontmatter => continue,
+            LineKind::Heading => &mut headings,
+            _ => &mut prose,
+        };
+        target.push_str(line);
+        target.push('\n');
+    }
+    [title, stem, headings, prose]
+}
+
+/// Occurrences of one word in each scored field of a document, in the
+/// order of [`fields`]. Stored in the search index, so documents can be
+/// scored without reading them.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub(crate) struct FieldCounts([u32; 4]);
+
+impl FieldCounts {
+    /// The occurrences weighted by field.
+    pub fn weighted(self) -> f64 {
+        self.0
+            .iter()
+            .zip(FIELD_WEIGHTS)
+            .map(|(&n, weight)| weight * f64::from(n))
+            .sum()
+    }
+}
+
+/// Every word of a document with its occurrences in each field, sorted by
+/// word. Words that only occur in the frontmatter outside the title are
+/// included with no occurrences.
+pub(crate) fn term_counts(doc: &Document) -> Vec<(String, FieldCounts)> {
+    let mut counts: BTreeMap<String, FieldCounts> = tokenize(doc.content)
+        .into_iter()
+        .map(|word| (word, FieldCounts::default()))
+        .collect();
+    for (i, text) in fields(doc).iter().enumerate() {
+        for word in tokenize(text) {
+            let n = &mut counts.entry(word).or_default().0[i];
+            *n = n.saturating_add(1);
+        }
+    }
+    counts.into_iter().collect()
+}
+
+/// Score a document against the query `terms` with BM25.
+///
+/// Uses the non-negative IDF variant so that terms present in every
+/// document still contribute a small positive weight.
+pub(crate) fn bm25(doc: &DocumentTerms, terms: &[String], stats: &CorpusStats) -> f64 {
+    let n = as_f64(stats.doc_count);
+    let avg_len = if stats.avg_len > 0.0 {
+        stats.avg_len
+    } else {
+        1.0
+    };
+    let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * as_f64(doc.len) / avg_len);
+
+    terms
+        .iter()
+        .zip(&doc.freqs)
+        .map(|(term, &tf)| {
+            let df = as_f64(stats.doc_freq.get(term).copied().unwrap_or(0));
+            let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+            idf * tf * (BM25_K1 + 1.0) / (tf + norm)
+        })
+        .sum()
+}
+
+/// Arithmetic mean of `values`, or zero if there are none.
+pub(crate) fn average(values: impl Iterator<Item = usize>) -> f64 {
+    let (sum, count) = values.fold((0, 0), |(sum, count), v| (sum + v, count + 1));
+    if count == 0 {
+        0.0
+    } else {
+        as_f64(sum) / as_f64(count)
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn as_f64(n: usize) -> f64 {
+    n as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slug::SlugStyle;
+    use std::path::Path;
+
+    fn terms_of(name: &str, content: &str) -> DocumentTerms {
+        let doc = Document::new(
+            Path::new("/"),
+            &Path::new("/").join(name),
+            content,
+            SlugStyle::default(),
+        );
+        DocumentTerms::new(&doc, |text| {
+            vec![tokenize(text).iter().filter(|t| *t == "rust").count()]
+        })
+    }
+
+    #[test]
+    fn test_fields_are_weighted() {
+        let body = terms_of("a.md", "text about rust");
+        let heading = terms_of("a.md", "## Rust\n\ntext");
+        let title = terms_of("a.md", "---\ntitle: Rust\n---\ntext");
+        let filename = terms_of("rust.md", "text");
+
+        assert!((body.freqs[0] - BODY_WEIGHT).abs() < f64::EPSILON);
+        assert!((heading.freqs[0] - HEADING_WEIGHT).abs() < f64::EPSILON);
+        assert!((title.freqs[0] - TITLE_WEIGHT).abs() < f64::EPSILON);
+        assert!((filename.freqs[0] - FILENAME_WEIGHT).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_term_counts_match_document_terms() {
+        let content = "---\ntitle: Rust notes\ntags: [lang]\n---\n# Rust\n\nRust and more rust.\n";
+        let doc = Document::new(
+            Path::new("/"),
+            Path::new("/rust.md"),
+            content,
+            SlugStyle::default(),
+        );
+        let counts = term_counts(&doc);
+        let rust = counts.iter().find(|(word, _)| word == "rust").unwrap().1;
+        assert!((rust.weighted() - terms_of("rust.md", content).freqs[0]).abs() < f64::EPSILON);
+        // Frontmatter words are kept for lookup but not counted.
+        let lang = counts.iter().find(|(word, _)| word == "lang").unwrap().1;
+        assert!(lang.weighted().abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_fenced_comment_is_not_heading() {
+        let doc = terms_of("a.md", "```sh\n# rust\n```");
+        assert!((doc.freqs[0] - BODY_WEIGHT).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_rare_terms_score_higher() {
+        let stats = CorpusStats {
+            doc_count: 100,
+            avg_len: 10.0,
+            doc_freq: HashMap::from([("common".to_string(), 90), ("rare".to_string(), 2)]),
+        };
+        let doc = DocumentTerms {
+            freqs: vec![1.0],
+            len: 10,
+        };
+        let common = bm25(&doc, &["common".to_string()], &stats);
+        let rare = bm25(&doc, &["rare".to_string()], &stats);
+        assert!(rare > common);
+        assert!(common > 0.0);
+    }
+
+    #[test]
+    fn test_longer_documents_score_lower() {
+        let stats = CorpusStats {
+            doc_count: 10,
+            avg_len: 100.0,
+            doc_freq: HashMap::from([("term".to_string(), 3)]),
+        };
+        let short = DocumentTerms {
+            freqs: vec![2.0],
+            len: 50,
+        };
+        let long = DocumentTerms {
+            freqs: vec![2.0],
+            len: 500,
+        };
+        let terms = ["term".to_string()];
+        assert!(bm25(&short, &terms, &stats) > bm25(&long, &terms, &stats));
+    }
+}
@@ -0,0 +1,410 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::files::{is_within_root, modified_millis, stage_write};
+use crate::search::{
+    MatchRange, Matcher, SearchError, SearchOptions, collect_md_files, to_match_ranges,
+};
+
+/// The planned replacements in one file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilePreview {
+    pub path: PathBuf,
+    /// Modification time when the preview was made, in milliseconds since
+    /// the Unix epoch, which survives a round trip through JavaScript. The
+    /// file is not written if it has changed since.
+    pub mtime: u64,
+    pub edits: Vec<LineEdit>,
+}
+
+/// One changed line of a [`FilePreview`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineEdit {
+    pub line_number: usize,
+    pub before: String,
+    pub after: String,
+    /// Replaced spans within `before`.
+    #[serde(default)]
+    pub before_ranges: Vec<MatchRange>,
+    /// Replacement text spans within `after`.
+    #[serde(default)]
+    pub after_ranges: Vec<MatchRange>,
+}
+
+/// Error returned when replacements cannot be previewed or applied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReplaceError {
+    /// The query could not be compiled.
+    Search { detail: SearchError },
+    /// The file changed on disk after the preview was made.
+    Modified { path: PathBuf },
+    /// The file is not under the folder being edited.
+    OutsideRoot { path: PathBuf },
+    /// The file could not be read or written.
+    Io { path: PathBuf, message: String },
+    /// Replacing `path` failed after the files in `written` were already
+    /// replaced. No other file was changed.
+    Incomplete {
+        path: PathBuf,
+        message: String,
+        written: Vec<PathBuf>,
+    },
+}
+
+impl From<SearchError> for ReplaceError {
+    fn from(e: SearchError) -> Self {
+        Self::Search { detail: e }
+    }
+}
+
+impl std::fmt::Display for ReplaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Search { detail } => write!(f, "{detail}"),
+            Self::Modified { path } => {
+                write!(f, "{} changed since the preview was made", path.display())
+            }
+            Self::OutsideRoot { path } => {
+                write!(f, "{} is outside the open folder", path.display())
+            }
+            Self::Io { path, message } => write!(f, "{}: {message}", path.display()),
+            Self::Incomplete {
+                path,
+                message,
+                written,
+            } => write!(
+                f,
+                "{}: {message}; {} other files were already replaced",
+                path.display(),
+                written.len()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplaceError {}
+
+/// Preview replacing every match of `query` with `replacement` in the `.md`
+/// files under `root`.
+///
+/// Matching works line by line as in [`crate::search::search_files`], with
+/// the query taken as a whole even if `options.structured` is set. In regex
+/// mode, `replacement` may refer to capture groups as `$1` or `${name}`.
+/// Nothing is written; pass the previews, or a subset of their edits, to
+/// [`apply_replacements`].
+///
+/// # Errors
+///
+/// Returns [`ReplaceError::Search`] if `options.regex` is set and the query
+/// does not compile.
+pub fn preview_replacements(
+    root: &Path,
+    query: &str,
+    replacement: &str,
+    options: &SearchOptions,
+) -> Result<Vec<FilePreview>, ReplaceError> {
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let matcher = Matcher::new(query, options)?;
+    let mut paths = Vec::new();
+    collect_md_files(root, &mut paths);
+
+    let mut previews = Vec::new();
+    for path in paths {
+        // Read the mtime first so a write racing the read is caught on apply.
+        let Some(mtime) = modified_millis(&path) else {
+            continue;
+        };
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let edits: Vec<LineEdit> = content
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                let replaced = matcher.replace_in(line, replacement)?;
+                Some(LineEdit {
+                    line_number: i + 1,
+                    before: line.to_string(),
+                    before_ranges: to_match_ranges(line, &replaced.matched),
+                    after_ranges: to_match_ranges(&replaced.line, &replaced.inserted),
+                    after: replaced.line,
+                })
+            })
+            .collect();
+        if !edits.is_empty() {
+            previews.push(FilePreview { path, mtime, edits });
+        }
+    }
+    Ok(previews)
+}
+
+/// Apply previewed edits to files under `root`, leaving out any edits the
+/// user did not choose.
+///
+/// Edits must stay in line order, as produced by [`preview_replacements`].
+///
+/// All files are checked before any is written: if one is outside `root` or
+/// changed since its preview was made, nothing is written. Each file is
+/// replaced atomically by writing a temporary file next to it and renaming
+/// it into place, but the files are not replaced together: if a rename
+/// fails, the files before it stay replaced and the rest are left as they
+/// were. Returns the paths of the files written.
+///
+/// # Errors
+///
+/// Returns [`ReplaceError::OutsideRoot`] if a file is not under `root`,
+/// [`ReplaceError::Modified`] if a file's modification time or the text of
+/// an edited line no longer matches the preview, [`ReplaceError::Io`] if a
+/// file cannot be read or written before any is replaced, or
+/// [`ReplaceError::Incomplete`] with the files already replaced if one
+/// cannot be renamed into place.
+pub fn apply_replacements(
+    root: &Path,
+    previews: &[FilePreview],
+) -> Result<Vec<PathBuf>, ReplaceError> {
+    let mut updated = Vec::new();
+    for preview in previews.iter().filter(|p| !p.edits.is_empty()) {
+        let path = &preview.path;
+        if !is_within_root(root, path).map_err(|e| io_error(path, &e))? {
+            return Err(ReplaceError::OutsideRoot { path: path.clone() });
+        }
+        if modified_millis(path) != Some(preview.mtime) {
+            return Err(ReplaceError::Modified { path: path.clone() });
+        }
+        let content = fs::read_to_string(path).map_err(|e| io_error(path, &e))?;
+        let content = apply_edits(&content, &preview.edits)
+            .ok_or_else(|| ReplaceError::Modified { path: path.clone() })?;
+        updated.push((path, content));
+    }
+
+    let mut staged = Vec::new();
+    for (path, content) in &updated {
+        match stage_write(path, content).map_err(|e| io_error(path, &e)) {
+            Ok(temp) => staged.push((temp, *path)),
+            Err(e) => {
+                for (temp, _) in &staged {
+                    let _ = fs::remove_file(temp);
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    let mut written = Vec::new();
+    for (i, (temp, path)) in staged.iter().enumerate() {
+        if let Err(e) = fs::rename(temp, path) {
+            for (temp, _) in &staged[i..] {
+                let _ = fs::remove_file(temp);
+            }
+            return Err(ReplaceError::Incomplete {
+                path: (*path).clone(),
+                message: e.to_string(),
+                written,
+            });
+        }
+        written.push((*path).clone());
+    }
+    Ok(written)
+}
+
+/// Replace the edited lines of `content`, keeping its line endings.
+///
+/// Returns `None` if an edited line does not read as the preview expects.
+fn apply_edits(content: &str, edits: &[LineEdit]) -> Option<String> {
+    let mut out = String::with_capacity(content.len());
+    let mut edits = edits.iter().peekable();
+    for (i, raw) in content.split_inclusive('\n').enumerate() {
+        let line = raw
+            .strip_suffix('\n')
+            .map_or(raw, |l| l.strip_suffix('\r').unwrap_or(l));
+        match edits.next_if(|e| e.line_number == i + 1) {
+            Some(edit) if edit.before == line => {
+                out.push_str(&edit.after);
+                out.push_str(&raw[line.len()..]);
+            }
+            Some(_) => return None,
+            None => out.push_str(raw),
+        }
+    }
+    // Edits past the end of the file mean it is shorter than previewed.
+    edits.peek().is_none().then_some(out)
+}
+
+fn io_error(path: &Path, e: &std::io::Error) -> ReplaceError {
+    ReplaceError::Io {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regex() -> SearchOptions {
+        SearchOptions {
+            regex: true,
+            case_sensitive: true,
+            ..SearchOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_preview_does_not_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.md");
+        fs::write(&a, "Acme Widget\nother\nacme again\n").unwrap();
+
+        let previews =
+            preview_replacements(dir.path(), "acme", "Globex", &SearchOptions::default()).unwrap();
+        assert_eq!(previews.len(), 1);
+        let edits = &previews[0].edits;
+        assert_eq!(edits.len(), 2);
+        assert_eq!(
+            (edits[0].line_number, edits[0].after.as_str()),
+            (1, "Globex Widget")
+        );
+        assert_eq!(
+            (edits[1].line_number, edits[1].after.as_str()),
+            (3, "Globex again")
+        );
+        assert_eq!(edits[0].before_ranges[0].end, 4);
+        assert_eq!(edits[0].after_ranges[0].end, 6);
+        assert_eq!(
+            fs::read_to_string(&a).unwrap(),
+            "Acme Widget\nother\nacme again\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_with_capture_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.md");
+        fs::write(&a, "see v1.2 and v3.4\r\nkeep\r\n").unwrap();
+
+        let previews =
+            preview_replacements(dir.path(), r"v(\d+)\.(\d+)", "version $1-$2", &regex()).unwrap();
+        let written = apply_replacements(dir.path(), &previews).unwrap();
+        assert_eq!(written, vec![a.clone()]);
+        assert_eq!(
+            fs::read_to_string(&a).unwrap(),
+            "see version 1-2 and version 3-4\r\nkeep\r\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_only_chosen_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.md");
+        fs::write(&a, "foo\nfoo\n").unwrap();
+
+        let mut previews =
+            preview_replacements(dir.path(), "foo", "bar", &SearchOptions::default()).unwrap();
+        previews[0].edits.remove(0);
+        apply_replacements(dir.path(), &previews).unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "foo\nbar\n");
+    }
+
+    #[test]
+    fn test_refuses_modified_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.md");
+        let b = dir.path().join("b.md");
+        fs::write(&a, "foo\n").unwrap();
+        fs::write(&b, "foo\n").unwrap();
+
+        let previews =
+            preview_replacements(dir.path(), "foo", "bar", &SearchOptions::default()).unwrap();
+        fs::write(&b, "foo changed\n").unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&b)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        let err = apply_replacements(dir.path(), &previews).unwrap_err();
+        assert_eq!(err, ReplaceError::Modified { path: b });
+        // Nothing is written when any file fails the check.
+        assert_eq!(fs::read_to_string(&a).unwrap(), "foo\n");
+    }
+
+    #[test]
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn test_previews_survive_javascript_numbers() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.md");
+        fs::write(&a, "foo\n").unwrap();
+
+        let previews =
+            preview_replacements(dir.path(), "foo", "bar", &SearchOptions::default()).unwrap();
+        // The frontend holds the mtime as a JavaScript number, an f64.
+        let mut json = serde_json::to_value(&previews).unwrap();
+        let through_js = json[0]["mtime"].as_u64().unwrap() as f64;
+        json[0]["mtime"] = serde_json::json!(through_js as u64);
+        let previews: Vec<FilePreview> = serde_json::from_value(json).unwrap();
+
+        apply_replacements(dir.path(), &previews).unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "bar\n");
+    }
+
+    #[test]
+    fn test_refuses_files_outside_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("docs/a.md"), "foo\n").unwrap();
+        let outside = dir.path().join("outside.md");
+        fs::write(&outside, "foo\n").unwrap();
+
+        let mut previews =
+            preview_replacements(&root, "foo", "bar", &SearchOptions::default()).unwrap();
+        let mut escape = previews[0].clone();
+        escape.path = root.join("docs/../../outside.md");
+        escape.mtime = modified_millis(&outside).unwrap();
+        previews.push(escape.clone());
+
+        let err = apply_replacements(&root, &previews).unwrap_err();
+        assert_eq!(err, ReplaceError::OutsideRoot { path: escape.path });
+        assert_eq!(fs::read_to_string(&outside).unwrap(), "foo\n");
+        assert_eq!(fs::read_to_string(root.join("docs/a.md")).unwrap(), "foo\n");
+    }
+
+    #[test]
+    fn test_apply_edits_checks_lines() {
+        let edit = |line_number: usize, before: &str| LineEdit {
+            line_number,
+            before: before.to_string(),
+            after: "new".to_string(),
+            before_ranges: Vec::new(),
+            after_ranges: Vec::new(),
+        };
+        assert_eq!(
+            apply_edits("a\nb", &[edit(2, "b")]).as_deref(),
+            Some("a\nnew")
+        );
+        assert!(apply_edits("a\nb", &[edit(2, "x")]).is_none());
+        assert!(apply_edits("a\nb", &[edit(3, "c")]).is_none());
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = preview_replacements(dir.path(), "(", "x", &regex()).unwrap_err();
+        assert!(matches!(
+            err,
+            ReplaceError::Search {
+                detail: SearchError::InvalidRegex { .. }
+            }
+        ));
+    }
+}
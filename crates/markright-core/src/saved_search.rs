@@ -0,0 +1,348 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::search::SearchOptions;
+
+/// Queries kept in a workspace's search history.
+const MAX_HISTORY: usize = 50;
+
+/// Longest gap, in milliseconds, between two searches for the second to be
+/// taken as a correction of the first.
+const MERGE_WINDOW_MS: u64 = 5_000;
+
+/// A named search with the options it runs with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub name: String,
+    pub query: String,
+    #[serde(default)]
+    pub options: SearchOptions,
+}
+
+/// A query from the search history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentSearch {
+    pub query: String,
+    #[serde(default)]
+    pub options: SearchOptions,
+    /// When the query was searched, in milliseconds since the Unix epoch.
+    #[serde(default)]
+    pub searched_at: u64,
+}
+
+/// Saved searches and search history of one workspace.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkspaceSearches {
+    /// Saved searches in the order they were created.
+    pub saved: Vec<SavedSearch>,
+    /// Recent queries, most recent first.
+    pub history: Vec<RecentSearch>,
+}
+
+/// Error returned when a saved search cannot be changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SavedSearchError {
+    /// No saved search has this name.
+    NotFound { name: String },
+    /// Another saved search already has this name.
+    NameTaken { name: String },
+    /// The name is empty or only whitespace.
+    EmptyName,
+}
+
+impl std::fmt::Display for SavedSearchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound { name } => write!(f, "no saved search named `{name}`"),
+            Self::NameTaken { name } => write!(f, "a saved search named `{name}` already exists"),
+            Self::EmptyName => write!(f, "saved search name is empty"),
+        }
+    }
+}
+
+impl std::error::Error for SavedSearchError {}
+
+impl WorkspaceSearches {
+    /// The saved search called `name`.
+    pub fn get(&self, name: &str) -> Option<&SavedSearch> {
+        self.saved.iter().find(|s| s.name == name)
+    }
+
+    /// Save a search under `name`, replacing the query and options of any
+    /// saved search already called that.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SavedSearchError::EmptyName`] if `name` is blank.
+    pub fn save(
+        &mut self,
+        name: &str,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<(), SavedSearchError> {
+        let name = valid_name(name)?;
+        let search = SavedSearch {
+            name: name.to_string(),
+            query: query.to_string(),
+            options: options.clone(),
+        };
+        match self.saved.iter_mut().find(|s| s.name == name) {
+            Some(existing) => *existing = search,
+            None => self.saved.push(search),
+        }
+        Ok(())
+    }
+
+    /// Rename the saved search `name` to `new_name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SavedSearchError::NotFound`] if there is no search called
+    /// `name`, [`SavedSearchError::NameTaken`] if another search is already
+    /// called `new_name`, or [`SavedSearchError::EmptyName`] if it is blank.
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), SavedSearchError> {
+        let new_name = valid_name(new_name)?;
+        if new_name != name && self.get(new_name).is_some() {
+            return Err(SavedSearchError::NameTaken {
+                name: new_name.to_string(),
+            });
+        }
+        let search = self
+            .saved
+            .iter_mut()
+            .find(|s| s.name == name)
+            .ok_or_else(|| SavedSearchError::NotFound {
+                name: name.to_string(),
+            })?;
+        search.name = new_name.to_string();
+        Ok(())
+    }
+
+    /// Delete the saved search `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SavedSearchError::NotFound`] if there is no search called `name`.
+    pub fn delete(&mut self, name: &str) -> Result<(), SavedSearchError> {
+        let len = self.saved.len();
+        self.saved.retain(|s| s.name != name);
+        if self.saved.len() == len {
+            return Err(SavedSearchError::NotFound {
+                name: name.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Add a query searched at `now`, in milliseconds since the Unix epoch,
+    /// to the front of the history.
+    ///
+    /// An earlier entry for the same query and options moves to the front.
+    /// A query that extends or shortens the most recent one and follows it
+    /// within a few seconds, as when a query is corrected, replaces it
+    /// instead of adding an entry.
+    pub fn record(&mut self, query: &str, options: &SearchOptions, now: u64) {
+        let query = query.trim();
+        if query.is_empty() {
+            return;
+        }
+        if self.history.first().is_some_and(|last| {
+            last.options == *options
+                && now.saturating_sub(last.searched_at) < MERGE_WINDOW_MS
+                && (query.starts_with(last.query.as_str()) || last.query.starts_with(query))
+        }) {
+            self.history.remove(0);
+        }
+        self.history
+            .retain(|h| !(h.query == query && h.options == *options));
+        self.history.insert(
+            0,
+            RecentSearch {
+                query: query.to_string(),
+                options: options.clone(),
+                searched_at: now,
+            },
+        );
+        self.history.truncate(MAX_HISTORY);
+    }
+}
+
+fn valid_name(name: &str) -> Result<&str, SavedSearchError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(SavedSearchError::EmptyName);
+    }
+    Ok(name)
+}
+
+/// Saved searches and history of every workspace, keyed by root folder.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchStore {
+    pub workspaces: BTreeMap<String, WorkspaceSearches>,
+}
+
+impl SearchStore {
+    /// Load the store from a JSON file, falling back to an empty store on any error.
+    pub fn load(path: &Path) -> Self {
+        let Ok(data) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&data).unwrap_or_default()
+    }
+
+    /// Save the store to a JSON file, creating parent directories if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    /// The searches of the workspace rooted at `root`.
+    pub fn workspace(&self, root: &Path) -> WorkspaceSearches {
+        self.workspaces
+            .get(&workspace_key(root))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The searches of the workspace rooted at `root`, for changing.
+    pub fn workspace_mut(&mut self, root: &Path) -> &mut WorkspaceSearches {
+        self.workspaces.entry(workspace_key(root)).or_default()
+    }
+}
+
+fn workspace_key(root: &Path) -> String {
+    root.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regex() -> SearchOptions {
+        SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_save_rename_delete() {
+        let mut searches = WorkspaceSearches::default();
+        searches
+            .save(" Runbook ", "alert OR page", &SearchOptions::default())
+            .unwrap();
+        searches.save("Errors", "err.*", &regex()).unwrap();
+        searches
+            .save("Runbook", "alert", &SearchOptions::default())
+            .unwrap();
+        assert_eq!(searches.saved.len(), 2);
+        assert_eq!(searches.get("Runbook").unwrap().query, "alert");
+
+        assert_eq!(
+            searches.rename("Runbook", "Errors"),
+            Err(SavedSearchError::NameTaken {
+                name: "Errors".to_string()
+            })
+        );
+        searches.rename("Runbook", "On-call").unwrap();
+        assert!(searches.get("Runbook").is_none());
+        assert_eq!(
+            searches.rename("On-call", "  "),
+            Err(SavedSearchError::EmptyName)
+        );
+
+        searches.delete("Errors").unwrap();
+        assert_eq!(
+            searches.delete("Errors"),
+            Err(SavedSearchError::NotFound {
+                name: "Errors".to_string()
+            })
+        );
+        assert_eq!(searches.saved[0].name, "On-call");
+    }
+
+    #[test]
+    fn test_history_collapses_corrections_and_repeats() {
+        let mut searches = WorkspaceSearches::default();
+        for (query, at) in [
+            ("c", 0),
+            ("con", 1_000),
+            ("config", 2_000),
+            ("confi", 3_000),
+        ] {
+            searches.record(query, &SearchOptions::default(), at);
+        }
+        searches.record("deploy", &SearchOptions::default(), 4_000);
+        searches.record("config", &regex(), 5_000);
+        searches.record("confi", &SearchOptions::default(), 6_000);
+        let queries: Vec<(&str, bool)> = searches
+            .history
+            .iter()
+            .map(|h| (h.query.as_str(), h.options.regex))
+            .collect();
+        assert_eq!(
+            queries,
+            vec![("confi", false), ("config", true), ("deploy", false)]
+        );
+    }
+
+    #[test]
+    fn test_history_keeps_prefixes_searched_apart() {
+        let mut searches = WorkspaceSearches::default();
+        searches.record("rust", &SearchOptions::default(), 0);
+        searches.record("rustc", &SearchOptions::default(), 60_000);
+        let queries: Vec<&str> = searches.history.iter().map(|h| h.query.as_str()).collect();
+        assert_eq!(queries, vec!["rustc", "rust"]);
+    }
+
+    #[test]
+    fn test_history_is_capped() {
+        let mut searches = WorkspaceSearches::default();
+        for i in 0..MAX_HISTORY + 5 {
+            searches.record(&format!("{i}-query"), &SearchOptions::default(), 0);
+        }
+        assert_eq!(searches.history.len(), MAX_HISTORY);
+        assert_eq!(
+            searches.history[0].query,
+            format!("{}-query", MAX_HISTORY + 4)
+        );
+    }
+
+    #[test]
+    fn test_store_roundtrip_per_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("searches.json");
+        let mut store = SearchStore::default();
+        store
+            .workspace_mut(Path::new("/docs"))
+            .save("Runbook", "alert", &regex())
+            .unwrap();
+        store
+            .workspace_mut(Path::new("/notes"))
+            .record("todo", &SearchOptions::default(), 0);
+        store.save(&path).unwrap();
+
+        let loaded = SearchStore::load(&path);
+        let docs = loaded.workspace(Path::new("/docs"));
+        assert_eq!(docs.get("Runbook").unwrap().options, regex());
+        assert!(docs.history.is_empty());
+        assert_eq!(loaded.workspace(Path::new("/notes")).history.len(), 1);
+        assert_eq!(
+            loaded.workspace(Path::new("/other")),
+            WorkspaceSearches::default()
+        );
+    }
+}
@@ -0,0 +1,702 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::config::{FolderConfig, FolderConfigs};
+use crate::frontmatter::{DocumentDate, Frontmatter, parse_frontmatter};
+use crate::search::collect_md_files;
+
+/// A JSON Schema that the frontmatter of a folder's documents must match.
+///
+/// Supports the keywords needed to require and constrain fields: `type`,
+/// `enum`, `const`, `required`, `properties`, `additionalProperties`,
+/// `items`, `minItems`, `maxItems`, `uniqueItems`, `minLength`,
+/// `maxLength`, `pattern`, `format` (`date` and `date-time`), `minimum`,
+/// `maximum`, `exclusiveMinimum`, `exclusiveMaximum`, `allOf`, `anyOf`,
+/// `oneOf` and `not`, along with annotations such as `title` and
+/// `description`. A schema with any other keyword, such as `$ref`, is
+/// rejected rather than applied in part.
+#[derive(Debug)]
+pub struct FrontmatterSchema {
+    schema: Value,
+    /// Every `pattern` in the schema, compiled.
+    patterns: HashMap<String, Regex>,
+}
+
+/// A way in which a document's frontmatter does not match its schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SchemaViolation {
+    /// The offending field, as in `owner` or `links[1].url`, or empty for the
+    /// frontmatter as a whole.
+    pub field: String,
+    pub message: String,
+}
+
+/// The violations found in one document of a tree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileViolations {
+    pub path: PathBuf,
+    /// Path relative to the root folder, with `/` separators.
+    pub rel_path: String,
+    pub violations: Vec<SchemaViolation>,
+}
+
+/// Error returned when a frontmatter schema cannot be loaded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SchemaError {
+    /// The schema file could not be read.
+    Io { path: PathBuf, message: String },
+    /// The schema file is not valid JSON, has an invalid `pattern`, or uses
+    /// a keyword that is not supported.
+    Invalid { path: PathBuf, message: String },
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, message } => write!(f, "{}: {message}", path.display()),
+            Self::Invalid { path, message } => {
+                write!(f, "invalid schema {}: {message}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl FrontmatterSchema {
+    /// Load a schema from a JSON file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SchemaError::Io`] if the file cannot be read, or
+    /// [`SchemaError::Invalid`] if it is not JSON, has a `pattern` that is
+    /// not a valid regex, or uses an unsupported keyword.
+    pub fn load(path: &Path) -> Result<Self, SchemaError> {
+        let data = fs::read_to_string(path).map_err(|e| SchemaError::Io {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        let invalid = |message: String| SchemaError::Invalid {
+            path: path.to_path_buf(),
+            message,
+        };
+        let schema = serde_json::from_str(&data).map_err(|e| invalid(e.to_string()))?;
+        Self::new(schema).map_err(invalid)
+    }
+
+    /// Build a schema from its JSON value.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if the schema uses an unsupported keyword or a
+    /// `pattern` in it is not a valid regex.
+    pub fn new(schema: Value) -> Result<Self, String> {
+        check_keywords(&schema, "")?;
+        let mut patterns = HashMap::new();
+        collect_patterns(&schema, &mut patterns)?;
+        Ok(Self { schema, patterns })
+    }
+
+    /// Check `frontmatter` against the schema. A document without
+    /// frontmatter is checked as if it had no fields.
+    pub fn validate(&self, frontmatter: Option<&Frontmatter>) -> Vec<SchemaViolation> {
+        let fields = Value::Object(frontmatter.map_or_else(Map::new, |fm| fm.fields.clone()));
+        let mut violations = Vec::new();
+        self.check(&self.schema, &fields, "", &mut violations);
+        violations
+    }
+
+    fn check(&self, schema: &Value, value: &Value, at: &str, out: &mut Vec<SchemaViolation>) {
+        let mut report = |message: String| {
+            out.push(SchemaViolation {
+                field: at.to_string(),
+                message,
+            });
+        };
+        let schema = match schema {
+            Value::Object(schema) => schema,
+            Value::Bool(false) => {
+                report("is not allowed".to_string());
+                return;
+            }
+            _ => return,
+        };
+
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&str> = match types {
+                Value::String(t) => vec![t.as_str()],
+                Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !types.is_empty() && !types.iter().any(|t| has_type(value, t)) {
+                report(format!(
+                    "must be {}, not {}",
+                    types.join(" or "),
+                    type_name(value)
+                ));
+                // The other keywords would only repeat the mismatch.
+                return;
+            }
+        }
+        if let Some(Value::Array(allowed)) = schema.get("enum")
+            && !allowed.contains(value)
+        {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            report(format!("must be one of {}", allowed.join(", ")));
+        }
+        if let Some(expected) = schema.get("const")
+            && expected != value
+        {
+            report(format!("must be {expected}"));
+        }
+
+        match value {
+            Value::String(text) => self.check_string(schema, text, &mut report),
+            Value::Number(n) => check_number(schema, n.as_f64().unwrap_or(f64::NAN), &mut report),
+            Value::Array(items) => {
+                check_array(schema, items, &mut report);
+                if let Some(item_schema) = schema.get("items") {
+                    for (i, item) in items.iter().enumerate() {
+                        self.check(item_schema, item, &format!("{at}[{i}]"), out);
+                    }
+                }
+            }
+            Value::Object(fields) => self.check_object(schema, fields, at, out),
+            Value::Null | Value::Bool(_) => {}
+        }
+
+        self.check_combinators(schema, value, at, out);
+    }
+
+    fn check_string(
+        &self,
+        schema: &Map<String, Value>,
+        text: &str,
+        report: &mut impl FnMut(String),
+    ) {
+        let len = text.chars().count();
+        if let Some(min) = schema.get("minLength").and_then(as_len)
+            && len < min
+        {
+            report(format!("must be at least {min} characters long"));
+        }
+        if let Some(max) = schema.get("maxLength").and_then(as_len)
+            && len > max
+        {
+            report(format!("must be at most {max} characters long"));
+        }
+        if let Some(pattern) = schema.get("pattern").and_then(Value::as_str)
+            && self
+                .patterns
+                .get(pattern)
+                .is_some_and(|re| !re.is_match(text))
+        {
+            report(format!("must match `{pattern}`"));
+        }
+        match schema.get("format").and_then(Value::as_str) {
+            Some("date") if DocumentDate::parse(text).is_none_or(|d| d.time.is_some()) => {
+                report("must be a date (YYYY-MM-DD)".to_string());
+            }
+            Some("date-time") if DocumentDate::parse(text).is_none_or(|d| d.time.is_none()) => {
+                report("must be a date and time (YYYY-MM-DDTHH:MM:SS)".to_string());
+            }
+            _ => {}
+        }
+    }
+
+    fn check_object(
+        &self,
+        schema: &Map<String, Value>,
+        fields: &Map<String, Value>,
+        at: &str,
+        out: &mut Vec<SchemaViolation>,
+    ) {
+        let field_path = |name: &str| {
+            if at.is_empty() {
+                name.to_string()
+            } else {
+                format!("{at}.{name}")
+            }
+        };
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !fields.contains_key(name) {
+                    out.push(SchemaViolation {
+                        field: field_path(name),
+                        message: "is required".to_string(),
+                    });
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (name, value) in fields {
+            match properties.and_then(|p| p.get(name)) {
+                Some(field_schema) => self.check(field_schema, value, &field_path(name), out),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => out.push(SchemaViolation {
+                        field: field_path(name),
+                        message: "is not an allowed field".to_string(),
+                    }),
+                    Some(extra) => self.check(extra, value, &field_path(name), out),
+                    None => {}
+                },
+            }
+        }
+    }
+
+    fn check_combinators(
+        &self,
+        schema: &Map<String, Value>,
+        value: &Value,
+        at: &str,
+        out: &mut Vec<SchemaViolation>,
+    ) {
+        let matches = |sub: &Value| {
+            let mut found = Vec::new();
+            self.check(sub, value, at, &mut found);
+            found.is_empty()
+        };
+        if let Some(Value::Array(all)) = schema.get("allOf") {
+            for sub in all {
+                self.check(sub, value, at, out);
+            }
+        }
+        let mut report = |message: &str| {
+            out.push(SchemaViolation {
+                field: at.to_string(),
+                message: message.to_string(),
+            });
+        };
+        if let Some(Value::Array(any)) = schema.get("anyOf")
+            && !any.iter().any(matches)
+        {
+            report("does not match any of the allowed forms");
+        }
+        if let Some(Value::Array(one)) = schema.get("oneOf")
+            && one.iter().filter(|sub| matches(sub)).count() != 1
+        {
+            report("must match exactly one of the allowed forms");
+        }
+        if let Some(not) = schema.get("not")
+            && matches(not)
+        {
+            report("matches a form that is not allowed");
+        }
+    }
+}
+
+fn check_number(schema: &Map<String, Value>, n: f64, report: &mut impl FnMut(String)) {
+    let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+    if let Some(min) = bound("minimum")
+        && n < min
+    {
+        report(format!("must be at least {min}"));
+    }
+    if let Some(max) = bound("maximum")
+        && n > max
+    {
+        report(format!("must be at most {max}"));
+    }
+    if let Some(min) = bound("exclusiveMinimum")
+        && n <= min
+    {
+        report(format!("must be greater than {min}"));
+    }
+    if let Some(max) = bound("exclusiveMaximum")
+        && n >= max
+    {
+        report(format!("must be less than {max}"));
+    }
+}
+
+fn check_array(schema: &Map<String, Value>, items: &[Value], report: &mut impl FnMut(String)) {
+    if let Some(min) = schema.get("minItems").and_then(as_len)
+        && items.len() < min
+    {
+        report(format!("must have at least {min} items"));
+    }
+    if let Some(max) = schema.get("maxItems").and_then(as_len)
+        && items.len() > max
+    {
+        report(format!("must have at most {max} items"));
+    }
+    if schema.get("uniqueItems") == Some(&Value::Bool(true))
+        && items
+            .iter()
+            .enumerate()
+            .any(|(i, item)| items[..i].contains(item))
+    {
+        report("must not repeat items".to_string());
+    }
+}
+
+/// Keywords that [`FrontmatterSchema`] checks.
+const KEYWORDS: &[&str] = &[
+    "type",
+    "enum",
+    "const",
+    "required",
+    "properties",
+    "additionalProperties",
+    "items",
+    "minItems",
+    "maxItems",
+    "uniqueItems",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "format",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    "allOf",
+    "anyOf",
+    "oneOf",
+    "not",
+];
+
+/// Keywords that describe a schema without constraining values.
+const ANNOTATIONS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+];
+
+/// Check that `schema`, found at `at`, and the schemas inside it use only
+/// supported keywords.
+fn check_keywords(schema: &Value, at: &str) -> Result<(), String> {
+    let schema = match schema {
+        Value::Object(schema) => schema,
+        Value::Bool(_) => return Ok(()),
+        _ if at.is_empty() => return Err("the schema is not an object".to_string()),
+        _ => return Err(format!("{at} is not a schema")),
+    };
+    for (key, value) in schema {
+        if !KEYWORDS.contains(&key.as_str()) && !ANNOTATIONS.contains(&key.as_str()) {
+            let at = if at.is_empty() { "the top level" } else { at };
+            return Err(format!("unsupported keyword `{key}` at {at}"));
+        }
+        let at = format!("{at}/{key}");
+        match (key.as_str(), value) {
+            ("properties", Value::Object(properties)) => {
+                for (name, sub) in properties {
+                    check_keywords(sub, &format!("{at}/{name}"))?;
+                }
+            }
+            ("additionalProperties" | "items" | "not", sub) => check_keywords(sub, &at)?,
+            ("allOf" | "anyOf" | "oneOf", Value::Array(subs)) => {
+                for (i, sub) in subs.iter().enumerate() {
+                    check_keywords(sub, &format!("{at}/{i}"))?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Compile every `pattern` in `schema` into `out`.
+fn collect_patterns(schema: &Value, out: &mut HashMap<String, Regex>) -> Result<(), String> {
+    match schema {
+        Value::Object(map) => {
+            if let Some(Value::String(pattern)) = map.get("pattern")
+                && !out.contains_key(pattern)
+            {
+                let re = Regex::new(pattern).map_err(|e| format!("pattern `{pattern}`: {e}"))?;
+                out.insert(pattern.clone(), re);
+            }
+            map.values().try_for_each(|v| collect_patterns(v, out))
+        }
+        Value::Array(items) => items.iter().try_for_each(|v| collect_patterns(v, out)),
+        _ => Ok(()),
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        "number" => value.is_number(),
+        _ => type_name(value) == name,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn as_len(value: &Value) -> Option<usize> {
+    value
+        .as_u64()
+        .map(|n| usize::try_from(n).unwrap_or(usize::MAX))
+}
+
+/// A violation of the frontmatter as a whole.
+fn whole(message: String) -> SchemaViolation {
+    SchemaViolation {
+        field: String::new(),
+        message,
+    }
+}
+
+/// Check the frontmatter of one document against the schema at
+/// `schema_path`. A schema that cannot be loaded is reported as a violation
+/// of the whole frontmatter.
+pub fn validate_frontmatter(
+    schema_path: &Path,
+    frontmatter: Option<&Frontmatter>,
+) -> Vec<SchemaViolation> {
+    match FrontmatterSchema::load(schema_path) {
+        Ok(schema) => schema.validate(frontmatter),
+        Err(e) => vec![whole(e.to_string())],
+    }
+}
+
+/// The config files in `config` that could not be read, as violations of
+/// the whole frontmatter, since the schema they may set is not applied.
+pub fn config_violations(config: &FolderConfig) -> Vec<SchemaViolation> {
+    config.errors.iter().cloned().map(whole).collect()
+}
+
+/// Check the frontmatter of every Markdown file under `root` against the
+/// schema set for its folder in `.markright.json`.
+///
+/// Returns the files with violations, in path order. Frontmatter that cannot
+/// be parsed, a schema that cannot be loaded, and a config file that cannot
+/// be read count as violations.
+pub fn validate_tree(root: &Path) -> Vec<FileViolations> {
+    let mut paths = Vec::new();
+    collect_md_files(root, &mut paths);
+    let mut folders = FolderConfigs::new(root);
+    let mut schemas: HashMap<PathBuf, Result<FrontmatterSchema, SchemaError>> = HashMap::new();
+
+    let mut files = Vec::new();
+    for path in paths {
+        let config = folders.for_file(&path);
+        let mut violations = config_violations(&config);
+        if let Some(schema_path) = config.frontmatter_schema
+            && let Ok(content) = fs::read_to_string(&path)
+        {
+            let schema = schemas
+                .entry(schema_path)
+                .or_insert_with_key(|p| FrontmatterSchema::load(p));
+            match (schema, parse_frontmatter(&content).0) {
+                (Err(e), _) => violations.push(whole(e.to_string())),
+                (Ok(_), Err(e)) => violations.push(whole(e.to_string())),
+                (Ok(schema), Ok(frontmatter)) => {
+                    violations.extend(schema.validate(frontmatter.as_ref()));
+                }
+            }
+        }
+        if violations.is_empty() {
+            continue;
+        }
+        let rel_path = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        files.push(FileViolations {
+            path,
+            rel_path,
+            violations,
+        });
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontmatter::strip_frontmatter;
+    use serde_json::json;
+
+    fn violations(schema: &Value, content: &str) -> Vec<(String, String)> {
+        let schema = FrontmatterSchema::new(schema.clone()).unwrap();
+        let (fm, _) = strip_frontmatter(content);
+        let mut found: Vec<(String, String)> = schema
+            .validate(fm.as_ref())
+            .into_iter()
+            .map(|v| (v.field, v.message))
+            .collect();
+        // Fields are checked in map order, which depends on serde_json's
+        // features.
+        found.sort();
+        found
+    }
+
+    fn runbook_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["owner", "status", "last_reviewed"],
+            "properties": {
+                "owner": {"type": "string", "minLength": 1},
+                "status": {"enum": ["draft", "active", "retired"]},
+                "last_reviewed": {"type": "string", "format": "date"},
+                "tags": {"type": "array", "items": {"type": "string", "pattern": "^[a-z-]+$"}},
+                "severity": {"type": "integer", "minimum": 1, "maximum": 4},
+            },
+            "additionalProperties": {"type": ["string", "number", "array"]},
+        })
+    }
+
+    #[test]
+    fn test_valid_frontmatter() {
+        let content = "---\nowner: sre\nstatus: active\nlast_reviewed: 2024-02-29\n\
+            tags: [db, on-call]\nseverity: 2\nteam: infra\n---\n";
+        assert!(violations(&runbook_schema(), content).is_empty());
+    }
+
+    #[test]
+    fn test_violations() {
+        let content = "---\nowner: \"\"\nstatus: done\nlast_reviewed: 2023-02-29\n\
+            tags: [db, On Call]\nseverity: 2.5\nextra: true\n---\n";
+        let found = |field: &str, message: &str| (field.to_string(), message.to_string());
+        assert_eq!(
+            violations(&runbook_schema(), content),
+            vec![
+                found("extra", "must be string or number or array, not boolean"),
+                found("last_reviewed", "must be a date (YYYY-MM-DD)"),
+                found("owner", "must be at least 1 characters long"),
+                found("severity", "must be integer, not number"),
+                found("status", r#"must be one of "draft", "active", "retired""#),
+                found("tags[1]", "must match `^[a-z-]+$`"),
+            ]
+        );
+        assert_eq!(
+            violations(&runbook_schema(), "# No frontmatter\n"),
+            vec![
+                found("last_reviewed", "is required"),
+                found("owner", "is required"),
+                found("status", "is required"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_combinators() {
+        let schema = json!({
+            "properties": {
+                "date": {"anyOf": [{"format": "date"}, {"format": "date-time"}]},
+                "id": {"not": {"const": "todo"}},
+            },
+            "additionalProperties": false,
+        });
+        assert!(violations(&schema, "---\ndate: 2024-01-05 10:30\n---\n").is_empty());
+        assert_eq!(
+            violations(&schema, "---\ndate: soon\nid: todo\nother: 1\n---\n"),
+            vec![
+                (
+                    "date".to_string(),
+                    "does not match any of the allowed forms".to_string()
+                ),
+                (
+                    "id".to_string(),
+                    "matches a form that is not allowed".to_string()
+                ),
+                ("other".to_string(), "is not an allowed field".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let err = FrontmatterSchema::new(json!({"properties": {"id": {"pattern": "("}}}));
+        assert!(err.unwrap_err().starts_with("pattern `(`"));
+    }
+
+    #[test]
+    fn test_unsupported_keywords() {
+        let err = |schema: Value| FrontmatterSchema::new(schema).unwrap_err();
+        assert_eq!(
+            err(json!({"$defs": {}, "required": ["owner"]})),
+            "unsupported keyword `$defs` at the top level"
+        );
+        assert_eq!(
+            err(json!({"properties": {"owner": {"$ref": "#/$defs/owner"}}})),
+            "unsupported keyword `$ref` at /properties/owner"
+        );
+        assert_eq!(
+            err(json!({"anyOf": [{"required": ["a"]}, {"if": {}, "then": {}}]})),
+            "unsupported keyword `if` at /anyOf/1"
+        );
+        assert_eq!(
+            err(json!({"items": [{"type": "string"}]})),
+            "/items is not a schema"
+        );
+        assert!(
+            FrontmatterSchema::new(json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "title": "Runbook",
+                "properties": {"owner": {"description": "Team", "default": "sre"}},
+            }))
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_validate_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("runbooks/db")).unwrap();
+        fs::write(
+            root.join("runbooks/schema.json"),
+            json!({"required": ["owner"]}).to_string(),
+        )
+        .unwrap();
+        fs::write(
+            root.join("runbooks/.markright.json"),
+            r#"{"frontmatter_schema": "schema.json"}"#,
+        )
+        .unwrap();
+        fs::write(root.join("runbooks/ok.md"), "---\nowner: sre\n---\n").unwrap();
+        fs::write(root.join("runbooks/db/missing.md"), "# Restore\n").unwrap();
+        fs::write(root.join("runbooks/db/broken.md"), "---\nowner: [\n---\n").unwrap();
+        fs::write(root.join("notes.md"), "# Not a runbook\n").unwrap();
+        fs::create_dir(root.join("drafts")).unwrap();
+        fs::write(
+            root.join("drafts/.markright.json"),
+            "{\"frontmatter_schema\": [",
+        )
+        .unwrap();
+        fs::write(root.join("drafts/plan.md"), "# Plan\n").unwrap();
+
+        let files = validate_tree(root);
+        let found: Vec<(&str, &str)> = files
+            .iter()
+            .map(|f| (f.rel_path.as_str(), f.violations[0].field.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("drafts/plan.md", ""),
+                ("runbooks/db/broken.md", ""),
+                ("runbooks/db/missing.md", "owner")
+            ]
+        );
+    }
+}
@@ -1,6 +1,96 @@
-use serde::Serialize;
+use caseless::Caseless;
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::ast::{BlockKind, SourceHeading, SourceMap, source_map};
+use crate::config::FolderConfigs;
+use crate::frontmatter::{Frontmatter, strip_frontmatter};
+use crate::query::{Field, Query, QueryError, parse_query};
+use crate::rank::{CorpusStats, DocumentTerms, average, bm25};
+use crate::slug::SlugStyle;
+use crate::tags::document_tags;
+
+/// Matching modes for a search query.
+///
+/// The defaults reproduce plain case-insensitive substring search, reporting
+/// every match on each line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct SearchOptions {
+    /// Treat the query as a regular expression instead of a literal string.
+    pub regex: bool,
+    /// Only match the query at word boundaries.
+    pub whole_word: bool,
+    /// Match letter case exactly.
+    pub case_sensitive: bool,
+    /// Report every match on a line instead of only the first one.
+    pub all_matches: bool,
+    /// Parse the query with the [`crate::query`] syntax instead of matching
+    /// it as a whole. Each word or phrase is then matched using the modes above.
+    pub structured: bool,
+    /// Lines of context reported before and after each matching line.
+    pub context_lines: usize,
+    /// Only match lines in blocks of these kinds; every kind if empty.
+    pub blocks: Vec<BlockKind>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            regex: false,
+            whole_word: false,
+            case_sensitive: false,
+            all_matches: true,
+            structured: false,
+            context_lines: 0,
+            blocks: Vec::new(),
+        }
+    }
+}
+
+/// Error returned when a search query cannot be executed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SearchError {
+    /// The query is not a valid regular expression.
+    InvalidRegex { pattern: String, message: String },
+    /// The structured query does not parse.
+    InvalidQuery { message: String, position: usize },
+    /// The search was cancelled through its [`CancelToken`].
+    Cancelled,
+}
+
+impl From<QueryError> for SearchError {
+    fn from(e: QueryError) -> Self {
+        Self::InvalidQuery {
+            message: e.message,
+            position: e.position,
+        }
+    }
+}
+
+impl std::fmt::Display for SearchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidRegex { pattern, message } => {
+                write!(f, "invalid regex `{pattern}`: {message}")
+            }
+            Self::InvalidQuery { message, position } => {
+                write!(f, "invalid query: {message} at position {position}")
+            }
+            Self::Cancelled => write!(f, "search cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
 
 /// A file that contains search matches.
 #[derive(Debug, Clone, Serialize)]
@@ -8,61 +98,951 @@ pub struct SearchResult {
     pub path: PathBuf,
     pub name: String,
     pub matches: Vec<SearchMatch>,
+    /// BM25 relevance score; results are sorted by it, highest first.
+    pub score: f64,
 }
 
-/// A single line matching the search query.
+/// A line containing one or more matches of the search query.
 #[derive(Debug, Clone, Serialize)]
 pub struct SearchMatch {
     pub line_number: usize,
     pub line_text: String,
-    pub col_start: usize,
-    pub col_end: usize,
+    /// Matched spans within `line_text`, in order and non-overlapping.
+    pub ranges: Vec<MatchRange>,
+    /// The line as plain text rendered from its block, without emphasis
+    /// markers, link syntax or HTML tags. Code and frontmatter lines are
+    /// kept as they are.
+    pub snippet: String,
+    /// Matched spans within `snippet`.
+    pub snippet_ranges: Vec<MatchRange>,
+    /// The kind of block the line belongs to.
+    pub block: BlockKind,
+    /// Up to [`SearchOptions::context_lines`] lines before the match, in order.
+    pub context_before: Vec<String>,
+    /// Up to [`SearchOptions::context_lines`] lines after the match, in order.
+    pub context_after: Vec<String>,
+    /// Texts of the enclosing headings, outermost first, e.g.
+    /// `["Install", "Linux", "Debian"]`. Includes the line itself if it is a heading.
+    pub heading_path: Vec<String>,
+    /// Id of the innermost enclosing heading in the rendered document.
+    pub anchor: Option<String>,
+}
+
+/// Location of a match within a line.
+///
+/// Byte offsets index the line as a Rust string; UTF-16 offsets index the
+/// same line as a JavaScript string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchRange {
+    pub start: usize,
+    pub end: usize,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+}
+
+/// Matches found in one batch of files by a running search, with progress.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchBatch {
+    /// Matching files of this batch in scan order. They are not scored yet;
+    /// the ranked list is returned when the search completes.
+    pub results: Vec<SearchResult>,
+    /// Files read so far.
+    pub scanned: usize,
+    /// Files the search reads at most. A search of the index stops early
+    /// once it has found enough of the best-scoring files.
+    pub total: usize,
+}
+
+/// Shared flag that stops a running search.
+///
+/// Clones share the flag, so a token kept by the caller can cancel a search
+/// running on another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
 }
 
 /// Maximum matching lines returned per file.
 const MAX_MATCHES_PER_FILE: usize = 10;
 
-/// Search all `.md` files under `root` for case-insensitive substring matches.
+/// Files read in parallel between progress reports and cancellation checks.
+const SCAN_BATCH_SIZE: usize = 256;
+
+/// Search all `.md` files under `root` for matches of `query`.
+///
+/// Returns the `max_results` most relevant files with matches, ranked by
+/// BM25 with extra weight for matches in the title, file name and headings.
+/// Each file includes up to [`MAX_MATCHES_PER_FILE`] matching lines with
+/// position information. With [`SearchOptions::structured`], the query is
+/// parsed with the [`crate::query`] syntax.
+///
+/// # Errors
+///
+/// Returns [`SearchError::InvalidRegex`] if `options.regex` is set and the
+/// query does not compile, or [`SearchError::InvalidQuery`] if a structured
+/// query does not parse.
+pub fn search_files(
+    root: &Path,
+    query: &str,
+    options: &SearchOptions,
+    max_results: usize,
+) -> Result<Vec<SearchResult>, SearchError> {
+    search_files_streaming(
+        root,
+        query,
+        options,
+        max_results,
+        &CancelToken::default(),
+        &mut |_| {},
+    )
+}
+
+/// Like [`search_files`], but reads files in parallel batches, passing each
+/// batch's matches and progress to `on_batch` and stopping early once
+/// `cancel` is set.
+///
+/// # Errors
 ///
-/// Returns up to `max_results` files with matches. Each file includes up to
-/// [`MAX_MATCHES_PER_FILE`] matching lines with position information.
-pub fn search_files(root: &Path, query: &str, max_results: usize) -> Vec<SearchResult> {
+/// Returns the errors of [`search_files`], or [`SearchError::Cancelled`] if
+/// the search was cancelled before it finished.
+pub fn search_files_streaming(
+    root: &Path,
+    query: &str,
+    options: &SearchOptions,
+    max_results: usize,
+    cancel: &CancelToken,
+    on_batch: &mut dyn FnMut(SearchBatch),
+) -> Result<Vec<SearchResult>, SearchError> {
     if query.is_empty() {
-        return Vec::new();
+        return Ok(Vec::new());
     }
-
-    let query_lower = query.to_lowercase();
-    let mut results = Vec::new();
+    let query = build_query(query, options)?;
     let mut paths = Vec::new();
     collect_md_files(root, &mut paths);
+    let mut folders = FolderConfigs::new(root);
+    let files = paths
+        .into_iter()
+        .map(|path| {
+            let slugs = folders.for_file(&path).slug_style();
+            (path, slugs)
+        })
+        .collect();
+    let scan = Scan {
+        root: root.to_path_buf(),
+        files,
+        ranking: Ranking::Read(None),
+    };
+    search_paths(&scan, &query, options, max_results, cancel, on_batch)
+}
 
-    for path in paths {
-        if results.len() >= max_results {
-            break;
+/// Turn the raw query into an AST: parsed if structured, one text term otherwise.
+pub(crate) fn build_query(query: &str, options: &SearchOptions) -> Result<Query, SearchError> {
+    if options.structured {
+        parse_query(query).map_err(SearchError::from)
+    } else {
+        Ok(Query::Text(query.to_string()))
+    }
+}
+
+/// Words to score `query` by, and whether it also has terms that can only
+/// be scored by reading files: regex text and literal text without word
+/// characters.
+///
+/// # Errors
+///
+/// Returns [`SearchError::InvalidRegex`] if a regex in the query does not compile.
+pub(crate) fn score_words(
+    query: &Query,
+    options: &SearchOptions,
+) -> Result<(Vec<String>, bool), SearchError> {
+    let plan = Plan::new(query, options)?;
+    let mut words = Vec::new();
+    let mut patterns = false;
+    for term in plan.score_terms(options) {
+        match term {
+            ScoreTerm::Word(word) => words.push(word),
+            ScoreTerm::Pattern { .. } => patterns = true,
         }
+    }
+    Ok((words, patterns))
+}
 
-        let Ok(content) = fs::read_to_string(&path) else {
-            continue;
+/// The files [`search_paths`] reads and how it ranks them.
+pub(crate) struct Scan {
+    /// Folder that `path:` queries match relative paths against.
+    pub root: PathBuf,
+    /// Files to read with their slug styles, in the order to read them.
+    pub files: Vec<(PathBuf, SlugStyle)>,
+    pub ranking: Ranking,
+}
+
+/// How [`search_paths`] scores the files it reads.
+pub(crate) enum Ranking {
+    /// Score each matching file from its content, with corpus-wide
+    /// statistics, or statistics estimated from the matching files if `None`.
+    Read(Option<CorpusStats>),
+    /// The score of each file, known in advance from the search index. Files
+    /// are given highest score first, so reading stops once `max_results`
+    /// files match.
+    Known(Vec<f64>),
+}
+
+/// Search the files of `scan` for matches of `query` and rank them.
+///
+/// Shared by [`search_files_streaming`] and the persistent index, which
+/// narrows the file list before any file is read and supplies corpus-wide
+/// stats or scores. Files are read in parallel, [`SCAN_BATCH_SIZE`] at a
+/// time; each batch is reported to `on_batch` and cancellation is checked
+/// between batches. Files with equal scores keep the order given.
+pub(crate) fn search_paths(
+    scan: &Scan,
+    query: &Query,
+    options: &SearchOptions,
+    max_results: usize,
+    cancel: &CancelToken,
+    on_batch: &mut dyn FnMut(SearchBatch),
+) -> Result<Vec<SearchResult>, SearchError> {
+    let plan = Plan::new(query, options)?;
+    let terms = plan.score_terms(options);
+    let keys: Vec<String> = terms.iter().map(|t| t.key().to_string()).collect();
+    let total = scan.files.len();
+    let mut scanned = 0;
+    let mut hits: Vec<(SearchResult, Option<DocumentTerms>)> = Vec::new();
+
+    for batch in scan.files.chunks(SCAN_BATCH_SIZE) {
+        if cancel.is_cancelled() {
+            return Err(SearchError::Cancelled);
+        }
+        let known = match &scan.ranking {
+            Ranking::Known(_) if hits.len() >= max_results => break,
+            Ranking::Known(scores) => Some(&scores[scanned..]),
+            Ranking::Read(_) => None,
         };
+        let found: Vec<(SearchResult, Option<DocumentTerms>)> = batch
+            .par_iter()
+            .enumerate()
+            .filter_map(|(i, (path, slugs))| {
+                let content = fs::read_to_string(path).ok()?;
+                let doc = Document::new(&scan.root, path, &content, *slugs);
+                if !plan.eval(&doc) {
+                    return None;
+                }
+                let freqs = known.is_none().then(|| {
+                    DocumentTerms::new(&doc, |text| terms.iter().map(|t| t.count(text)).collect())
+                });
+                let result = SearchResult {
+                    name: doc.name.clone(),
+                    path: path.clone(),
+                    matches: plan.line_matches(&doc, options),
+                    score: known.map_or(0.0, |scores| scores[i]),
+                };
+                Some((result, freqs))
+            })
+            .collect();
+        scanned += batch.len();
+        on_batch(SearchBatch {
+            results: found.iter().map(|(result, _)| result.clone()).collect(),
+            scanned,
+            total,
+        });
+        hits.extend(found);
+    }
 
-        let matches = find_matches(&content, &query_lower, query.len());
-        if !matches.is_empty() {
-            let name = path
-                .file_name()
-                .map_or_else(String::new, |n| n.to_string_lossy().to_string());
-            results.push(SearchResult {
-                path,
-                name,
-                matches,
+    let mut results: Vec<SearchResult> = match &scan.ranking {
+        Ranking::Known(_) => hits.into_iter().map(|(result, _)| result).collect(),
+        Ranking::Read(stats) => {
+            let mut stats = stats.clone().unwrap_or_else(|| CorpusStats {
+                doc_count: total,
+                avg_len: average(hits.iter().filter_map(|(_, f)| f.as_ref().map(|f| f.len))),
+                ..CorpusStats::default()
+            });
+            // Terms without a known document frequency occur in at least every hit.
+            for key in &keys {
+                stats.doc_freq.entry(key.clone()).or_insert(hits.len());
+            }
+            hits.into_iter()
+                .map(|(mut result, freqs)| {
+                    if let Some(freqs) = freqs {
+                        result.score = bm25(&freqs, &keys, &stats);
+                    }
+                    result
+                })
+                .collect()
+        }
+    };
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    results.truncate(max_results);
+    Ok(results)
+}
+
+/// The part of a document a line belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LineKind {
+    Frontmatter,
+    Heading,
+    /// A code fence delimiter such as ` ```rust `.
+    Fence,
+    /// A line of code inside a fenced or indented code block.
+    Code,
+    Table,
+    /// Prose, and blank lines between blocks.
+    Body,
+}
+
+static PROSE: BlockKind = BlockKind::Prose;
+static FRONTMATTER: BlockKind = BlockKind::Frontmatter;
+
+/// A file's content split into the parts that queries can target.
+pub(crate) struct Document<'a> {
+    /// Path relative to the searched folder, with `/` separators.
+    pub rel_path: String,
+    pub name: String,
+    pub content: &'a str,
+    /// The content after the frontmatter.
+    pub body: &'a str,
+    /// Lines of the content, without line endings.
+    pub lines: Vec<&'a str>,
+    pub frontmatter: Option<Frontmatter>,
+    /// Lines of the content before the one the body starts on.
+    pub body_line: usize,
+    /// Lines of the content that start within the frontmatter.
+    frontmatter_lines: usize,
+    slugs: SlugStyle,
+    /// Headings and blocks of the body, with lines counted from its start.
+    /// Parsed on first use, as most files a query reads do not match it.
+    map: OnceCell<SourceMap>,
+}
+
+impl<'a> Document<'a> {
+    pub fn new(root: &Path, path: &Path, content: &'a str, slugs: SlugStyle) -> Self {
+        let rel = path.strip_prefix(root).unwrap_or(path);
+        let rel_path = rel
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let name = path
+            .file_name()
+            .map_or_else(String::new, |n| n.to_string_lossy().to_string());
+
+        let (frontmatter, body) = strip_frontmatter(content);
+        let body_start = content.len() - body.len();
+        // The body starts partway through the line that closes the frontmatter.
+        let body_line = content[..body_start].matches('\n').count();
+        let frontmatter_lines = content[..body_start].split_inclusive('\n').count();
+        let lines = content
+            .split_inclusive('\n')
+            .map(|raw| {
+                raw.strip_suffix('\n')
+                    .map_or(raw, |l| l.strip_suffix('\r').unwrap_or(l))
+            })
+            .collect();
+
+        Self {
+            rel_path,
+            name,
+            content,
+            body,
+            lines,
+            frontmatter,
+            body_line,
+            frontmatter_lines,
+            slugs,
+            map: OnceCell::new(),
+        }
+    }
+
+    /// Headings and blocks of the body, parsing it on first use.
+    pub fn map(&self) -> &SourceMap {
+        self.map.get_or_init(|| source_map(self.body, self.slugs))
+    }
+
+    /// The frontmatter title, or the text of the first level-1 heading.
+    pub fn title(&self) -> Option<&str> {
+        let title = self.frontmatter.as_ref().and_then(|fm| fm.title.as_deref());
+        title.or_else(|| {
+            self.map()
+                .headings
+                .iter()
+                .find(|h| h.level == 1)
+                .map(|h| h.text.as_str())
+        })
+    }
+
+    /// The part of the document the line at `line_idx` belongs to.
+    pub fn kind(&self, line_idx: usize) -> LineKind {
+        if line_idx < self.frontmatter_lines {
+            return LineKind::Frontmatter;
+        }
+        let line_number = line_idx + 1 - self.body_line;
+        match self.map().block_at(line_number) {
+            None => LineKind::Body,
+            Some(b) => match b.kind {
+                BlockKind::Heading => LineKind::Heading,
+                BlockKind::Code { .. }
+                    if (b.opening_fence && line_number == b.start_line)
+                        || (b.closing_fence && line_number == b.end_line) =>
+                {
+                    LineKind::Fence
+                }
+                BlockKind::Code { .. } => LineKind::Code,
+                BlockKind::Table => LineKind::Table,
+                BlockKind::Prose => LineKind::Body,
+                BlockKind::Frontmatter => LineKind::Frontmatter,
+            },
+        }
+    }
+
+    /// The kind of block that the line at `line_idx` belongs to.
+    fn block_of(&self, line_idx: usize) -> &BlockKind {
+        if line_idx < self.frontmatter_lines {
+            return &FRONTMATTER;
+        }
+        self.map()
+            .block_at(line_idx + 1 - self.body_line)
+            .map_or(&PROSE, |b| &b.kind)
+    }
+
+    /// The line at `line_idx` as plain text, or `None` for lines outside
+    /// prose, headings and tables.
+    fn plain_line(&self, line_idx: usize) -> Option<&str> {
+        let (LineKind::Body | LineKind::Heading | LineKind::Table) = self.kind(line_idx) else {
+            return None;
+        };
+        let line = line_idx + 1 - self.body_line;
+        let block = self.map().block_at(line)?;
+        block.text.get(line - block.start_line).map(String::as_str)
+    }
+
+    /// Fill in the enclosing heading path and anchor of each match.
+    ///
+    /// Headings come from parsing the body, so setext headings count and
+    /// anchors agree with the ids of the rendered document.
+    fn locate_in_headings(&self, matches: &mut [SearchMatch]) {
+        let mut headings = self.map().headings.iter().peekable();
+        let mut stack: Vec<&SourceHeading> = Vec::new();
+
+        // Matches are in line order, so one pass over the headings suffices.
+        for m in matches {
+            while let Some(h) = headings.next_if(|h| self.body_line + h.line <= m.line_number) {
+                while stack.last().is_some_and(|top| top.level >= h.level) {
+                    stack.pop();
+                }
+                stack.push(h);
+            }
+            m.heading_path = stack.iter().map(|h| h.text.clone()).collect();
+            m.anchor = stack.last().map(|h| h.id.clone());
+        }
+    }
+
+    /// Frontmatter `tags` and `keywords` and inline `#tags`, case-folded.
+    pub fn tags(&self) -> Vec<String> {
+        document_tags(self.frontmatter.as_ref(), self.body)
+    }
+}
+
+/// The lines a text leaf of a [`Plan`] searches.
+struct LineFilter {
+    /// Line kinds targeted by the query field; all if `None`.
+    kinds: Option<&'static [LineKind]>,
+    /// Block kinds from [`SearchOptions::blocks`]; all if empty.
+    blocks: Vec<BlockKind>,
+}
+
+impl LineFilter {
+    fn new(kinds: Option<&'static [LineKind]>, options: &SearchOptions) -> Self {
+        Self {
+            kinds,
+            blocks: options.blocks.clone(),
+        }
+    }
+
+    /// Whether the filter accepts the line at `line_idx`. Only parses the
+    /// document if the filter targets kinds of line or block.
+    fn accepts(&self, doc: &Document, line_idx: usize) -> bool {
+        self.kinds.is_none_or(|k| k.contains(&doc.kind(line_idx)))
+            && (self.blocks.is_empty() || {
+                let block = doc.block_of(line_idx);
+                self.blocks.iter().any(|b| b.includes(block))
+            })
+    }
+}
+
+/// A query compiled against the search options, ready to run on documents.
+enum Plan {
+    /// Text matched on the lines that pass the filter.
+    Lines {
+        filter: LineFilter,
+        matcher: Matcher,
+    },
+    /// Text matched against the document title.
+    Title(Matcher),
+    /// Text matched against the relative path.
+    Path(Matcher),
+    /// A tag compared as a whole, case-folded.
+    Tag(String),
+    /// A frontmatter field compared as a whole, case-folded.
+    Field {
+        name: String,
+        value: String,
+    },
+    And(Vec<Plan>),
+    Or(Vec<Plan>),
+    Not(Box<Plan>),
+}
+
+/// A query term used for BM25 scoring.
+enum ScoreTerm<'a> {
+    /// A case-folded word, counted in the words that contain it.
+    Word(String),
+    /// A whole leaf of the query, counted by its matches.
+    Pattern { text: String, matcher: &'a Matcher },
+}
+
+impl ScoreTerm<'_> {
+    fn key(&self) -> &str {
+        match self {
+            Self::Word(word) => word,
+            Self::Pattern { text, .. } => text,
+        }
+    }
+
+    fn count(&self, text: &str) -> usize {
+        match self {
+            Self::Word(word) => tokenize(text)
+                .iter()
+                .filter(|t| t.contains(word.as_str()))
+                .count(),
+            Self::Pattern { matcher, .. } => matcher.find_in(text).len(),
+        }
+    }
+}
+
+impl Plan {
+    fn new(query: &Query, options: &SearchOptions) -> Result<Self, SearchError> {
+        let fold = |s: &str| caseless::default_case_fold_str(s);
+        Ok(match query {
+            Query::Text(text) => Self::Lines {
+                filter: LineFilter::new(None, options),
+                matcher: Matcher::new(text, options)?,
+            },
+            Query::Field { field, value } => match field {
+                Field::Heading => Self::Lines {
+                    filter: LineFilter::new(Some(&[LineKind::Heading]), options),
+                    matcher: Matcher::new(value, options)?,
+                },
+                Field::Code => Self::Lines {
+                    filter: LineFilter::new(Some(&[LineKind::Code]), options),
+                    matcher: Matcher::new(value, options)?,
+                },
+                Field::Title => Self::Title(Matcher::new(value, options)?),
+                Field::Path => Self::Path(Matcher::new(value, options)?),
+                Field::Tag => Self::Tag(fold(value)),
+                Field::Frontmatter(name) => Self::Field {
+                    name: name.clone(),
+                    value: fold(value),
+                },
+            },
+            Query::And(children) => Self::And(
+                children
+                    .iter()
+                    .map(|c| Self::new(c, options))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Query::Or(children) => Self::Or(
+                children
+                    .iter()
+                    .map(|c| Self::new(c, options))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Query::Not(inner) => Self::Not(Box::new(Self::new(inner, options)?)),
+        })
+    }
+
+    fn eval(&self, doc: &Document) -> bool {
+        match self {
+            Self::Lines { filter, matcher } => (0..doc.lines.len())
+                .filter(|&i| filter.accepts(doc, i))
+                .any(|i| matcher.is_match(doc.lines[i])),
+            Self::Title(matcher) => doc.title().is_some_and(|t| matcher.is_match(t)),
+            Self::Path(matcher) => matcher.is_match(&doc.rel_path),
+            Self::Tag(tag) => doc.tags().contains(tag),
+            Self::Field { name, value } => doc.frontmatter.as_ref().is_some_and(|fm| {
+                fm.list(name)
+                    .iter()
+                    .any(|v| caseless::default_case_fold_str(v) == *value)
+            }),
+            Self::And(children) => children.iter().all(|c| c.eval(doc)),
+            Self::Or(children) => children.iter().any(|c| c.eval(doc)),
+            Self::Not(inner) => !inner.eval(doc),
+        }
+    }
+
+    /// Leaves outside any `Not`, which decide what is highlighted and scored.
+    fn positive_leaves(&self) -> Vec<&Self> {
+        match self {
+            Self::And(children) | Self::Or(children) => {
+                children.iter().flat_map(Self::positive_leaves).collect()
+            }
+            Self::Not(_) => Vec::new(),
+            leaf => vec![leaf],
+        }
+    }
+
+    /// Terms to score documents by.
+    ///
+    /// Literal text is scored per word. Regex text, and literal text without
+    /// word characters, is scored as one term counted by its matches.
+    fn score_terms(&self, options: &SearchOptions) -> Vec<ScoreTerm<'_>> {
+        let mut terms = Vec::new();
+        for leaf in self.positive_leaves() {
+            let (Self::Lines { matcher, .. } | Self::Title(matcher)) = leaf else {
+                continue;
+            };
+            let words = if options.regex {
+                Vec::new()
+            } else {
+                tokenize(matcher.text())
+            };
+            if words.is_empty() {
+                terms.push(ScoreTerm::Pattern {
+                    text: matcher.text().to_string(),
+                    matcher,
+                });
+            } else {
+                terms.extend(words.into_iter().map(ScoreTerm::Word));
+            }
+        }
+        terms
+    }
+
+    /// Lines with matches of the positive text leaves, with merged ranges,
+    /// context and enclosing headings.
+    fn line_matches(&self, doc: &Document, options: &SearchOptions) -> Vec<SearchMatch> {
+        let leaves: Vec<(&LineFilter, &Matcher)> = self
+            .positive_leaves()
+            .into_iter()
+            .filter_map(|leaf| match leaf {
+                Self::Lines { filter, matcher } => Some((filter, matcher)),
+                _ => None,
+            })
+            .collect();
+        let mut matches = Vec::new();
+        if leaves.is_empty() {
+            return matches;
+        }
+
+        let find = |line_idx: usize, text: &str| {
+            let mut spans: Vec<(usize, usize)> = leaves
+                .iter()
+                .filter(|(filter, _)| filter.accepts(doc, line_idx))
+                .flat_map(|(_, matcher)| matcher.find_in(text))
+                .collect();
+            merge_spans(&mut spans, options);
+            spans
+        };
+
+        for (line_idx, line) in doc.lines.iter().enumerate() {
+            if matches.len() >= MAX_MATCHES_PER_FILE {
+                break;
+            }
+
+            let spans = find(line_idx, line);
+            if spans.is_empty() {
+                continue;
+            }
+            let (snippet, snippet_ranges) = match doc.plain_line(line_idx) {
+                Some(text) => {
+                    let ranges = to_match_ranges(text, &find(line_idx, text));
+                    (text.to_string(), ranges)
+                }
+                None => ((*line).to_string(), to_match_ranges(line, &spans)),
+            };
+            let before = line_idx.saturating_sub(options.context_lines);
+            let after = (line_idx + 1 + options.context_lines).min(doc.lines.len());
+            let context = |range: std::ops::Range<usize>| {
+                doc.lines[range].iter().map(|l| (*l).to_string()).collect()
+            };
+            matches.push(SearchMatch {
+                line_number: line_idx + 1,
+                line_text: (*line).to_string(),
+                ranges: to_match_ranges(line, &spans),
+                snippet,
+                snippet_ranges,
+                block: doc.block_of(line_idx).clone(),
+                context_before: context(before..line_idx),
+                context_after: context(line_idx + 1..after),
+                heading_path: Vec::new(),
+                anchor: None,
             });
         }
+
+        if !matches.is_empty() {
+            doc.locate_in_headings(&mut matches);
+        }
+        matches
+    }
+}
+
+/// Sort spans, dropping any that overlap an earlier one, and keep only the
+/// first unless every match is wanted.
+fn merge_spans(spans: &mut Vec<(usize, usize)>, options: &SearchOptions) {
+    spans.sort_unstable();
+    spans.dedup();
+    // Keep spans from different leaves from overlapping.
+    let mut last_end = 0;
+    spans.retain(|&(start, end)| {
+        let keep = start >= last_end;
+        if keep {
+            last_end = end;
+        }
+        keep
+    });
+    if !options.all_matches {
+        spans.truncate(1);
+    }
+}
+
+/// Split text into case-folded words.
+///
+/// Uses the same folding as case-insensitive search, so any literal match
+/// implies that each word of the query occurs inside a word of the text.
+pub(crate) fn tokenize(text: &str) -> Vec<String> {
+    caseless::default_case_fold_str(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// A word, phrase or pattern compiled to locate matches within a line.
+pub(crate) struct Matcher {
+    /// The text as written in the query.
+    text: String,
+    kind: MatchKind,
+}
+
+enum MatchKind {
+    /// Literal text compared after full Unicode case folding.
+    Folded { needle: String, whole_word: bool },
+    /// Literal text compared exactly.
+    Exact { needle: String, whole_word: bool },
+    /// A regular expression, with word boundaries and case handled by the regex.
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub(crate) fn new(text: &str, options: &SearchOptions) -> Result<Self, SearchError> {
+        let kind = if options.regex {
+            let pattern = if options.whole_word {
+                format!(r"\b(?:{text})\b")
+            } else {
+                text.to_string()
+            };
+            RegexBuilder::new(&pattern)
+                .case_insensitive(!options.case_sensitive)
+                .build()
+                .map(MatchKind::Regex)
+                .map_err(|e| SearchError::InvalidRegex {
+                    pattern: text.to_string(),
+                    message: e.to_string(),
+                })?
+        } else if options.case_sensitive {
+            MatchKind::Exact {
+                needle: text.to_string(),
+                whole_word: options.whole_word,
+            }
+        } else {
+            MatchKind::Folded {
+                needle: caseless::default_case_fold_str(text),
+                whole_word: options.whole_word,
+            }
+        };
+        Ok(Self {
+            text: text.to_string(),
+            kind,
+        })
+    }
+
+    fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        !self.find_in(line).is_empty()
+    }
+
+    /// Return the byte ranges of all non-empty, non-overlapping matches in `line`.
+    fn find_in(&self, line: &str) -> Vec<(usize, usize)> {
+        match &self.kind {
+            MatchKind::Folded { needle, whole_word } => find_folded(line, needle, *whole_word),
+            MatchKind::Exact { needle, whole_word } => find_accepted(line, needle, |start, end| {
+                !whole_word || is_word_bounded(line, start, end)
+            }),
+            MatchKind::Regex(re) => re
+                .find_iter(line)
+                .filter(|m| !m.is_empty())
+                .map(|m| (m.start(), m.end()))
+                .collect(),
+        }
     }
 
-    results
+    /// Replace every match in `line` with `replacement`, expanding capture
+    /// group references such as `$1` or `${name}` for regex matches.
+    ///
+    /// Returns `None` if nothing matched.
+    pub(crate) fn replace_in(&self, line: &str, replacement: &str) -> Option<Replaced> {
+        let mut replaced = Replaced {
+            line: String::with_capacity(line.len()),
+            matched: Vec::new(),
+            inserted: Vec::new(),
+        };
+        let mut last = 0;
+        let mut splice = |start: usize, end: usize, expand: &dyn Fn(&mut String)| {
+            replaced.line.push_str(&line[last..start]);
+            let at = replaced.line.len();
+            expand(&mut replaced.line);
+            replaced.matched.push((start, end));
+            replaced.inserted.push((at, replaced.line.len()));
+            last = end;
+        };
+        match &self.kind {
+            MatchKind::Regex(re) => {
+                for caps in re.captures_iter(line) {
+                    let Some(m) = caps.get(0).filter(|m| !m.is_empty()) else {
+                        continue;
+                    };
+                    splice(m.start(), m.end(), &|out| caps.expand(replacement, out));
+                }
+            }
+            _ => {
+                for (start, end) in self.find_in(line) {
+                    splice(start, end, &|out| out.push_str(replacement));
+                }
+            }
+        }
+        if replaced.matched.is_empty() {
+            return None;
+        }
+        replaced.line.push_str(&line[last..]);
+        Some(replaced)
+    }
+}
+
+/// A line with its matches replaced, from [`Matcher::replace_in`].
+pub(crate) struct Replaced {
+    pub line: String,
+    /// Byte spans of the matches in the original line.
+    pub matched: Vec<(usize, usize)>,
+    /// Byte spans of the replacement text in `line`.
+    pub inserted: Vec<(usize, usize)>,
+}
+
+/// Find `needle` (already case-folded) in the case-folded form of `line`.
+///
+/// Folding can change byte lengths (`İ` folds to two code points, `ß` to
+/// `ss`), so each folded byte remembers the span of the original character
+/// it came from. A match that starts or ends inside an expansion is widened
+/// to the whole original character.
+fn find_folded(line: &str, needle: &str, whole_word: bool) -> Vec<(usize, usize)> {
+    let mut folded = String::with_capacity(line.len());
+    let mut origin = Vec::with_capacity(line.len());
+    for (start, c) in line.char_indices() {
+        let end = start + c.len_utf8();
+        for f in std::iter::once(c).default_case_fold() {
+            folded.push(f);
+            origin.extend(std::iter::repeat_n((start, end), f.len_utf8()));
+        }
+    }
+
+    let span = |i: usize, j: usize| (origin[i].0, origin[j - 1].1);
+    let mut last_end = 0;
+    find_accepted(&folded, needle, |i, j| {
+        let (start, end) = span(i, j);
+        // Two folded matches inside one expanded character map to the same span.
+        let accept = start >= last_end && (!whole_word || is_word_bounded(line, start, end));
+        if accept {
+            last_end = end;
+        }
+        accept
+    })
+    .into_iter()
+    .map(|(i, j)| span(i, j))
+    .collect()
+}
+
+/// Find the non-empty, non-overlapping occurrences of `needle` in `haystack`
+/// that `accept` allows.
+///
+/// A rejected occurrence does not consume its text: the scan resumes one
+/// character later, so `aa` still matches the last two letters of `aaa`
+/// when the first candidate is not a whole word.
+fn find_accepted(
+    haystack: &str,
+    needle: &str,
+    mut accept: impl FnMut(usize, usize) -> bool,
+) -> Vec<(usize, usize)> {
+    let mut found = Vec::new();
+    let mut from = 0;
+    while let Some(i) = haystack[from..].find(needle) {
+        let start = from + i;
+        let end = start + needle.len();
+        if end > start && accept(start, end) {
+            found.push((start, end));
+            from = end;
+        } else {
+            let Some(c) = haystack[start..].chars().next() else {
+                break;
+            };
+            from = start + c.len_utf8();
+        }
+    }
+    found
+}
+
+/// Whether `line[start..end]` is not directly preceded or followed by a word
+/// character, using the same Unicode `\w` class as the regex engine's `\b`.
+fn is_word_bounded(line: &str, start: usize, end: usize) -> bool {
+    let is_word = regex_syntax::is_word_character;
+    !line[..start].chars().next_back().is_some_and(is_word)
+        && !line[end..].chars().next().is_some_and(is_word)
+}
+
+/// Convert byte ranges within `line` to [`MatchRange`]s carrying UTF-16 offsets.
+///
+/// `spans` must be sorted and non-overlapping.
+pub(crate) fn to_match_ranges(line: &str, spans: &[(usize, usize)]) -> Vec<MatchRange> {
+    let mut ranges = Vec::with_capacity(spans.len());
+    let mut byte_pos = 0;
+    let mut utf16_pos = 0;
+    let mut advance = |to: usize| {
+        utf16_pos += line[byte_pos..to].encode_utf16().count();
+        byte_pos = to;
+        utf16_pos
+    };
+    for &(start, end) in spans {
+        let start_utf16 = advance(start);
+        let end_utf16 = advance(end);
+        ranges.push(MatchRange {
+            start,
+            end,
+            start_utf16,
+            end_utf16,
+        });
+    }
+    ranges
 }
 
 /// Recursively collect all `.md` file paths, skipping hidden entries.
-fn collect_md_files(dir: &Path, out: &mut Vec<PathBuf>) {
+pub(crate) fn collect_md_files(dir: &Path, out: &mut Vec<PathBuf>) {
     let Ok(entries) = fs::read_dir(dir) else {
         return;
     };
@@ -87,29 +1067,6 @@ fn collect_md_files(dir: &Path, out: &mut Vec<PathBuf>) {
     }
 }
 
-/// Find case-insensitive substring matches within file content.
-fn find_matches(content: &str, query_lower: &str, query_len: usize) -> Vec<SearchMatch> {
-    let mut matches = Vec::new();
-
-    for (line_idx, line) in content.lines().enumerate() {
-        if matches.len() >= MAX_MATCHES_PER_FILE {
-            break;
-        }
-
-        let line_lower = line.to_lowercase();
-        if let Some(col) = line_lower.find(query_lower) {
-            matches.push(SearchMatch {
-                line_number: line_idx + 1,
-                line_text: line.to_string(),
-                col_start: col,
-                col_end: col + query_len,
-            });
-        }
-    }
-
-    matches
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -119,7 +1076,7 @@ mod tests {
     fn test_empty_query_returns_empty() {
         let dir = tempfile::tempdir().unwrap();
         fs::write(dir.path().join("test.md"), "# Hello").unwrap();
-        let results = search_files(dir.path(), "", 50);
+        let results = search_files(dir.path(), "", &SearchOptions::default(), 50).unwrap();
         assert!(results.is_empty());
     }
 
@@ -127,7 +1084,7 @@ mod tests {
     fn test_no_matches() {
         let dir = tempfile::tempdir().unwrap();
         fs::write(dir.path().join("test.md"), "# Hello world").unwrap();
-        let results = search_files(dir.path(), "zebra", 50);
+        let results = search_files(dir.path(), "zebra", &SearchOptions::default(), 50).unwrap();
         assert!(results.is_empty());
     }
 
@@ -135,13 +1092,13 @@ mod tests {
     fn test_case_insensitive_match() {
         let dir = tempfile::tempdir().unwrap();
         fs::write(dir.path().join("test.md"), "# Hello World\nSome text").unwrap();
-        let results = search_files(dir.path(), "hello", 50);
+        let results = search_files(dir.path(), "hello", &SearchOptions::default(), 50).unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].name, "test.md");
         assert_eq!(results[0].matches.len(), 1);
         assert_eq!(results[0].matches[0].line_number, 1);
-        assert_eq!(results[0].matches[0].col_start, 2);
-        assert_eq!(results[0].matches[0].col_end, 7);
+        assert_eq!(results[0].matches[0].ranges[0].start, 2);
+        assert_eq!(results[0].matches[0].ranges[0].end, 7);
     }
 
     #[test]
@@ -151,7 +1108,7 @@ mod tests {
         fs::write(dir.path().join("b.md"), "# Beta\nbaz qux").unwrap();
         fs::write(dir.path().join("c.txt"), "foo bar").unwrap(); // not .md
 
-        let results = search_files(dir.path(), "foo", 50);
+        let results = search_files(dir.path(), "foo", &SearchOptions::default(), 50).unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].name, "a.md");
     }
@@ -163,7 +1120,7 @@ mod tests {
         fs::create_dir(&sub).unwrap();
         fs::write(sub.join("deep.md"), "nested content here").unwrap();
 
-        let results = search_files(dir.path(), "nested", 50);
+        let results = search_files(dir.path(), "nested", &SearchOptions::default(), 50).unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].name, "deep.md");
     }
@@ -175,7 +1132,7 @@ mod tests {
         fs::create_dir(&hidden).unwrap();
         fs::write(hidden.join("secret.md"), "secret content").unwrap();
 
-        let results = search_files(dir.path(), "secret", 50);
+        let results = search_files(dir.path(), "secret", &SearchOptions::default(), 50).unwrap();
         assert!(results.is_empty());
     }
 
@@ -186,7 +1143,7 @@ mod tests {
             fs::write(dir.path().join(format!("file{i}.md")), "match this").unwrap();
         }
 
-        let results = search_files(dir.path(), "match", 3);
+        let results = search_files(dir.path(), "match", &SearchOptions::default(), 3).unwrap();
         assert_eq!(results.len(), 3);
     }
 
@@ -199,10 +1156,529 @@ mod tests {
         )
         .unwrap();
 
-        let results = search_files(dir.path(), "foo", 50);
+        let results = search_files(dir.path(), "foo", &SearchOptions::default(), 50).unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].matches.len(), 2);
         assert_eq!(results[0].matches[0].line_number, 1);
         assert_eq!(results[0].matches[1].line_number, 3);
     }
+
+    fn search_with(content: &str, query: &str, options: &SearchOptions) -> Vec<SearchMatch> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("test.md"), content).unwrap();
+        search_files(dir.path(), query, options, 50)
+            .unwrap()
+            .into_iter()
+            .flat_map(|r| r.matches)
+            .collect()
+    }
+
+    #[test]
+    fn test_case_sensitive() {
+        let options = SearchOptions {
+            case_sensitive: true,
+            ..SearchOptions::default()
+        };
+        let matches = search_with("Hello\nhello", "hello", &options);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line_number, 2);
+    }
+
+    #[test]
+    fn test_whole_word() {
+        let options = SearchOptions {
+            whole_word: true,
+            ..SearchOptions::default()
+        };
+        let matches = search_with("configuration\nthe config file", "config", &options);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line_number, 2);
+        assert_eq!(matches[0].ranges[0].start, 4);
+        assert_eq!(matches[0].ranges[0].end, 10);
+    }
+
+    #[test]
+    fn test_literal_query_escapes_regex_syntax() {
+        let matches = search_with(
+            "call foo() here\nfoo bar",
+            "foo()",
+            &SearchOptions::default(),
+        );
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line_number, 1);
+    }
+
+    #[test]
+    fn test_regex_mode() {
+        let options = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        let matches = search_with("version 1.2\nno digits", r"\d+\.\d+", &options);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].ranges[0].start, 8);
+        assert_eq!(matches[0].ranges[0].end, 11);
+    }
+
+    #[test]
+    fn test_invalid_regex_is_error() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("test.md"), "text").unwrap();
+        let options = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        let err = search_files(dir.path(), "(unclosed", &options, 50).unwrap_err();
+        assert!(
+            matches!(err, SearchError::InvalidRegex { ref pattern, .. } if pattern == "(unclosed")
+        );
+    }
+
+    #[test]
+    fn test_all_matches_per_line() {
+        let content = "foo and foo and foo";
+        let matches = search_with(content, "foo", &SearchOptions::default());
+        assert_eq!(matches.len(), 1);
+        let starts: Vec<usize> = matches[0].ranges.iter().map(|r| r.start).collect();
+        assert_eq!(starts, vec![0, 8, 16]);
+
+        let options = SearchOptions {
+            all_matches: false,
+            ..SearchOptions::default()
+        };
+        let matches = search_with(content, "foo", &options);
+        assert_eq!(matches[0].ranges.len(), 1);
+    }
+
+    #[test]
+    fn test_offsets_map_to_original_line() {
+        // `İ` lowercases to three bytes, which used to shift every later offset.
+        let line = "İzmir and izmir";
+        let matches = search_with(line, "IZMIR", &SearchOptions::default());
+        assert_eq!(matches.len(), 1);
+        let range = matches[0].ranges.last().unwrap();
+        assert_eq!(&line[range.start..range.end], "izmir");
+        assert_eq!(range.start_utf16, 10);
+        assert_eq!(range.end_utf16, 15);
+    }
+
+    #[test]
+    fn test_full_case_folding() {
+        let line = "Die STRASSE und die Straße und die STRAẞE";
+        let matches = search_with(line, "straße", &SearchOptions::default());
+        let found: Vec<&str> = matches[0]
+            .ranges
+            .iter()
+            .map(|r| &line[r.start..r.end])
+            .collect();
+        assert_eq!(found, vec!["STRASSE", "Straße", "STRAẞE"]);
+    }
+
+    #[test]
+    fn test_partial_expansion_widens_to_character() {
+        let line = "Fuß";
+        let matches = search_with(line, "s", &SearchOptions::default());
+        assert_eq!(matches[0].ranges.len(), 1);
+        let range = matches[0].ranges[0];
+        assert_eq!(&line[range.start..range.end], "ß");
+    }
+
+    #[test]
+    fn test_utf16_offsets() {
+        // The emoji is four UTF-8 bytes but two UTF-16 units.
+        let line = "😀 café café";
+        let matches = search_with(line, "CAFÉ", &SearchOptions::default());
+        let ranges = &matches[0].ranges;
+        assert_eq!(ranges.len(), 2);
+        assert_eq!((ranges[0].start, ranges[0].end), (5, 10));
+        assert_eq!((ranges[0].start_utf16, ranges[0].end_utf16), (3, 7));
+        assert_eq!((ranges[1].start_utf16, ranges[1].end_utf16), (8, 12));
+    }
+
+    #[test]
+    fn test_whole_word_with_folding() {
+        let options = SearchOptions {
+            whole_word: true,
+            ..SearchOptions::default()
+        };
+        let line = "Maße maßeinheit MASSE";
+        let matches = search_with(line, "masse", &options);
+        let found: Vec<&str> = matches[0]
+            .ranges
+            .iter()
+            .map(|r| &line[r.start..r.end])
+            .collect();
+        assert_eq!(found, vec!["Maße", "MASSE"]);
+    }
+
+    #[test]
+    fn test_whole_word_follows_regex_word_boundaries() {
+        let line = "snake_case case café caféine naïve";
+        for case_sensitive in [false, true] {
+            for regex in [false, true] {
+                let options = SearchOptions {
+                    case_sensitive,
+                    whole_word: true,
+                    regex,
+                    ..SearchOptions::default()
+                };
+                let found = |query: &str| -> Vec<&str> {
+                    search_with(line, query, &options)
+                        .iter()
+                        .flat_map(|m| m.ranges.iter().map(|r| &line[r.start..r.end]))
+                        .collect()
+                };
+                // `_` joins words, and so do accented letters.
+                assert_eq!(found("case"), vec!["case"]);
+                assert_eq!(found("café"), vec!["café"]);
+                assert!(found("na").is_empty());
+                assert!(found("ve").is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_whole_word_retries_after_rejected_match() {
+        let options = SearchOptions {
+            whole_word: true,
+            ..SearchOptions::default()
+        };
+        // The first candidate starts inside `ba`; the overlapping one after it
+        // is a whole phrase.
+        let line = "ba a a";
+        let found = Matcher::new("a a", &options).unwrap().find_in(line);
+        assert_eq!(found, vec![(3, 6)]);
+    }
+
+    #[test]
+    fn test_results_sorted_by_score() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "# Intro\nmentions deploy once").unwrap();
+        fs::write(dir.path().join("b.md"), "# Deploy\nhow to deploy").unwrap();
+        fs::write(
+            dir.path().join("c.md"),
+            "---\ntitle: Deploy guide\n---\nsteps to deploy",
+        )
+        .unwrap();
+
+        let results = search_files(dir.path(), "deploy", &SearchOptions::default(), 50).unwrap();
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["c.md", "b.md", "a.md"]);
+        assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+        assert!(results[2].score > 0.0);
+    }
+
+    #[test]
+    fn test_best_match_survives_max_results() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..10 {
+            fs::write(dir.path().join(format!("file{i}.md")), "some widget text").unwrap();
+        }
+        fs::write(dir.path().join("widget.md"), "# Widget\nwidget text").unwrap();
+
+        let results = search_files(dir.path(), "widget", &SearchOptions::default(), 3).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].name, "widget.md");
+    }
+
+    const STRUCTURED: SearchOptions = SearchOptions {
+        regex: false,
+        whole_word: false,
+        case_sensitive: false,
+        all_matches: true,
+        structured: true,
+        context_lines: 0,
+        blocks: Vec::new(),
+    };
+
+    /// Names of the files matching a structured `query`, sorted.
+    fn structured_names(root: &Path, query: &str) -> Vec<String> {
+        let mut names: Vec<String> = search_files(root, query, &STRUCTURED, 50)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_structured_phrase_and_exclude() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "getting started guide").unwrap();
+        fs::write(dir.path().join("b.md"), "started getting lost").unwrap();
+        fs::write(dir.path().join("c.md"), "getting started draft").unwrap();
+
+        assert_eq!(
+            structured_names(dir.path(), "\"getting started\" -draft"),
+            vec!["a.md"]
+        );
+        assert_eq!(
+            structured_names(dir.path(), "guide OR lost"),
+            vec!["a.md", "b.md"]
+        );
+    }
+
+    #[test]
+    fn test_structured_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("a.md"),
+            "---\ntitle: Setup\nstatus: draft\ntags: [ops, linux]\n---\n## Debian\n```sh\napt install\n```\n",
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("guides")).unwrap();
+        fs::write(
+            dir.path().join("guides/b.md"),
+            "# Install\nDebian uses apt.\n",
+        )
+        .unwrap();
+
+        assert_eq!(structured_names(dir.path(), "status:draft"), vec!["a.md"]);
+        assert_eq!(structured_names(dir.path(), "tag:OPS"), vec!["a.md"]);
+        assert_eq!(structured_names(dir.path(), "title:setup"), vec!["a.md"]);
+        assert_eq!(structured_names(dir.path(), "title:install"), vec!["b.md"]);
+        assert_eq!(structured_names(dir.path(), "heading:debian"), vec!["a.md"]);
+        assert_eq!(structured_names(dir.path(), "code:apt"), vec!["a.md"]);
+        assert_eq!(structured_names(dir.path(), "path:guides/"), vec!["b.md"]);
+        assert_eq!(structured_names(dir.path(), "apt -tag:ops"), vec!["b.md"]);
+    }
+
+    #[test]
+    fn test_structured_highlights_positive_terms() {
+        let content = "alpha beta\ngamma";
+        let matches = search_with(content, "(alpha OR gamma) -beta", &STRUCTURED);
+        assert!(matches.is_empty());
+        let matches = search_with(content, "beta (alpha OR gamma)", &STRUCTURED);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].ranges.len(), 2);
+        assert_eq!(matches[1].line_number, 2);
+    }
+
+    #[test]
+    fn test_structured_syntax_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = search_files(dir.path(), "(foo", &STRUCTURED, 50).unwrap_err();
+        assert!(matches!(err, SearchError::InvalidQuery { position: 0, .. }));
+    }
+
+    #[test]
+    fn test_streaming_reports_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let count = SCAN_BATCH_SIZE + 10;
+        for i in 0..count {
+            let text = if i % 2 == 0 { "even needle" } else { "odd" };
+            fs::write(dir.path().join(format!("{i}.md")), text).unwrap();
+        }
+
+        let mut batches = Vec::new();
+        let results = search_files_streaming(
+            dir.path(),
+            "needle",
+            &SearchOptions::default(),
+            usize::MAX,
+            &CancelToken::default(),
+            &mut |batch| batches.push(batch),
+        )
+        .unwrap();
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].scanned, SCAN_BATCH_SIZE);
+        assert_eq!(batches[1].scanned, count);
+        assert!(batches.iter().all(|b| b.total == count));
+        let streamed: usize = batches.iter().map(|b| b.results.len()).sum();
+        assert_eq!(streamed, results.len());
+        assert_eq!(results.len(), count.div_ceil(2));
+    }
+
+    #[test]
+    fn test_cancelled_search_stops() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..SCAN_BATCH_SIZE * 2 {
+            fs::write(dir.path().join(format!("{i}.md")), "needle").unwrap();
+        }
+
+        let cancel = CancelToken::default();
+        let mut batches = 0;
+        let result = search_files_streaming(
+            dir.path(),
+            "needle",
+            &SearchOptions::default(),
+            50,
+            &cancel.clone(),
+            &mut |_| {
+                batches += 1;
+                cancel.cancel();
+            },
+        );
+        assert_eq!(result.unwrap_err(), SearchError::Cancelled);
+        assert_eq!(batches, 1);
+    }
+
+    #[test]
+    fn test_context_lines() {
+        let options = SearchOptions {
+            context_lines: 2,
+            ..SearchOptions::default()
+        };
+        let matches = search_with("one\ntwo\nthree\nfour\nfive", "two", &options);
+        assert_eq!(matches[0].context_before, vec!["one"]);
+        assert_eq!(matches[0].context_after, vec!["three", "four"]);
+
+        let matches = search_with("one\ntwo", "two", &SearchOptions::default());
+        assert!(matches[0].context_before.is_empty());
+        assert!(matches[0].context_after.is_empty());
+    }
+
+    #[test]
+    fn test_heading_path_and_anchor() {
+        let content = "---\ntitle: Guide\n---\nintro needle\n\n# Install\n\n## Linux\n\n\
+            ### Debian\n\napt needle\n\n## Windows\n\nneedle here\n\n# Linux\n\n\
+            Usage\n-----\n\nneedle again\n";
+        let matches = search_with(content, "needle", &SearchOptions::default());
+        let located: Vec<(Vec<&str>, Option<&str>)> = matches
+            .iter()
+            .map(|m| {
+                (
+                    m.heading_path.iter().map(String::as_str).collect(),
+                    m.anchor.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            located,
+            vec![
+                (vec![], None),
+                (vec!["Install", "Linux", "Debian"], Some("debian")),
+                (vec!["Install", "Windows"], Some("windows")),
+                (vec!["Linux", "Usage"], Some("usage")),
+            ]
+        );
+
+        let matches = search_with(content, "linux", &SearchOptions::default());
+        assert_eq!(matches[0].heading_path, vec!["Install", "Linux"]);
+        assert_eq!(matches[0].anchor.as_deref(), Some("linux"));
+        assert_eq!(matches[1].anchor.as_deref(), Some("linux-1"));
+    }
+
+    const BLOCKS_DOC: &str = "---\ntags: config\n---\n# Config\n\nSet the config file.\n\n\
+        ```toml\nconfig = true\n```\n\n| key | config |\n|-----|--------|\n\n    config()\n";
+
+    #[test]
+    fn test_matches_classified_by_block() {
+        let matches = search_with(BLOCKS_DOC, "config", &SearchOptions::default());
+        let blocks: Vec<(usize, &BlockKind)> =
+            matches.iter().map(|m| (m.line_number, &m.block)).collect();
+        let toml = BlockKind::Code {
+            language: Some("toml".to_string()),
+        };
+        let indented = BlockKind::Code { language: None };
+        assert_eq!(
+            blocks,
+            vec![
+                (2, &BlockKind::Frontmatter),
+                (4, &BlockKind::Heading),
+                (6, &BlockKind::Prose),
+                (9, &toml),
+                (12, &BlockKind::Table),
+                (15, &indented),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_by_block() {
+        let options = |blocks: Vec<BlockKind>| SearchOptions {
+            blocks,
+            ..SearchOptions::default()
+        };
+        let lines = |options: &SearchOptions| -> Vec<usize> {
+            search_with(BLOCKS_DOC, "config", options)
+                .iter()
+                .map(|m| m.line_number)
+                .collect()
+        };
+        assert_eq!(lines(&options(vec![BlockKind::Prose])), vec![6]);
+        assert_eq!(
+            lines(&options(vec![BlockKind::Code { language: None }])),
+            vec![9, 15]
+        );
+        assert_eq!(
+            lines(&options(vec![
+                BlockKind::Code {
+                    language: Some("toml".to_string())
+                },
+                BlockKind::Table,
+            ])),
+            vec![9, 12]
+        );
+
+        // A file with matches only outside the chosen blocks is not a result.
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.md"), "```\nconfig\n```\n").unwrap();
+        let results =
+            search_files(dir.path(), "config", &options(vec![BlockKind::Prose]), 50).unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_unmatched_files_are_not_parsed() {
+        let content = "# Setup\n\nInstall the widget.\n";
+        let doc = Document::new(
+            Path::new("/"),
+            Path::new("/a.md"),
+            content,
+            SlugStyle::default(),
+        );
+        let query = parse_query("gadget").unwrap();
+        assert!(
+            !Plan::new(&query, &SearchOptions::default())
+                .unwrap()
+                .eval(&doc)
+        );
+        assert!(doc.map.get().is_none());
+
+        // Block filters need the block map to reject a file.
+        let options = SearchOptions {
+            blocks: vec![BlockKind::Table],
+            ..SearchOptions::default()
+        };
+        let query = parse_query("widget").unwrap();
+        assert!(!Plan::new(&query, &options).unwrap().eval(&doc));
+        assert!(doc.map.get().is_some());
+    }
+
+    #[test]
+    fn test_snippets_render_markdown() {
+        let content = "---\ntitle: Widget\n---\n# The **widget** guide\n\n\
+            See the [widget docs](widget.md) or <b>widget</b> help.\n\n\
+            | name | *widget* |\n|---|---|\n\n```\nwidget **raw**\n```\n";
+        let matches = search_with(content, "widget", &SearchOptions::default());
+        let snippets: Vec<(&str, Vec<(usize, usize)>)> = matches
+            .iter()
+            .map(|m| {
+                let ranges = m.snippet_ranges.iter().map(|r| (r.start, r.end)).collect();
+                (m.snippet.as_str(), ranges)
+            })
+            .collect();
+        assert_eq!(
+            snippets,
+            vec![
+                ("title: Widget", vec![(7, 13)]),
+                ("The widget guide", vec![(4, 10)]),
+                (
+                    "See the widget docs or widget help.",
+                    vec![(8, 14), (23, 29)]
+                ),
+                ("name · widget", vec![(8, 14)]),
+                ("widget **raw**", vec![(0, 6)]),
+            ]
+        );
+        // The raw line and its ranges are kept for exact positioning.
+        assert_eq!(
+            matches[2].line_text,
+            "See the [widget docs](widget.md) or <b>widget</b> help."
+        );
+        assert_eq!(matches[2].ranges.len(), 3);
+    }
 }
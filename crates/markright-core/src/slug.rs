@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// How heading text is turned into an anchor id.
+///
+/// Each style follows the platform it is named after, so links copied from
+/// that platform resolve in the viewer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlugStyle {
+    /// The original slugs: lowercase letters and digits, with runs
+    /// of spaces and hyphens collapsed to one hyphen.
+    #[default]
+    Legacy,
+    /// GitHub: lowercase, punctuation other than `-` and `_` removed, and
+    /// every space turned into a hyphen.
+    Github,
+    /// GitLab: like GitHub, then runs of hyphens collapsed to one.
+    Gitlab,
+    /// mdBook: like GitHub, but every whitespace character becomes a hyphen
+    /// and only ASCII letters are lowercased.
+    Mdbook,
+    /// Pandoc: `.` is kept, runs of whitespace become one hyphen, and
+    /// everything before the first letter is removed, leaving `section` if
+    /// nothing is left.
+    Pandoc,
+}
+
+impl SlugStyle {
+    /// The slug of `text` in this style, before duplicates are numbered.
+    pub fn slugify(self, text: &str) -> String {
+        match self {
+            Self::Legacy => legacy(text),
+            Self::Github => text
+                .to_lowercase()
+                .chars()
+                .filter_map(|c| match c {
+                    ' ' => Some('-'),
+                    c if is_word(c) || c == '-' => Some(c),
+                    _ => None,
+                })
+                .collect(),
+            Self::Gitlab => {
+                let mut slug = String::with_capacity(text.len());
+                for c in text.to_lowercase().chars() {
+                    let c = if c == ' ' { '-' } else { c };
+                    if (is_word(c) || c == '-') && !(c == '-' && slug.ends_with('-')) {
+                        slug.push(c);
+                    }
+                }
+                slug
+            }
+            Self::Mdbook => text
+                .chars()
+                .filter_map(|c| {
+                    if is_word(c) || c == '-' {
+                        Some(c.to_ascii_lowercase())
+                    } else if c.is_whitespace() {
+                        Some('-')
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+            Self::Pandoc => {
+                let mut slug = String::with_capacity(text.len());
+                for word in text.split_whitespace() {
+                    if !slug.is_empty() {
+                        slug.push('-');
+                    }
+                    slug.extend(
+                        word.chars()
+                            .filter(|&c| is_word(c) || matches!(c, '-' | '.'))
+                            .flat_map(char::to_lowercase),
+                    );
+                }
+                match slug.find(char::is_alphabetic) {
+                    Some(start) => slug[start..].to_string(),
+                    None => "section".to_string(),
+                }
+            }
+        }
+    }
+}
+
+/// Letters, digits and `_`, the characters every style keeps.
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn legacy(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c
+            } else if c == ' ' || c == '-' {
+                '-'
+            } else {
+                '\0'
+            }
+        })
+        .filter(|&c| c != '\0')
+        .collect::<String>()
+        .split('-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Assigns unique ids to the headings of one document, in order.
+///
+/// A repeated slug gets `-1`, `-2` and so on appended, skipping ids that
+/// are already taken, as GitHub does.
+#[derive(Debug, Default)]
+pub struct Slugger {
+    style: SlugStyle,
+    /// Ids handed out, each with the number of times it was asked for again.
+    taken: HashMap<String, usize>,
+}
+
+impl Slugger {
+    pub fn new(style: SlugStyle) -> Self {
+        Self {
+            style,
+            taken: HashMap::new(),
+        }
+    }
+
+    /// The id for the next heading with `text`.
+    pub fn slug(&mut self, text: &str) -> String {
+        let base = self.style.slugify(text);
+        let mut id = base.clone();
+        let mut count = self.taken.get(&base).copied().unwrap_or(0);
+        while self.taken.contains_key(&id) {
+            count += 1;
+            id = format!("{base}-{count}");
+        }
+        if id != base {
+            self.taken.insert(base, count);
+        }
+        self.taken.insert(id.clone(), 0);
+        id
+    }
+
+    /// Mark `id`, set explicitly on a heading, as taken so that no slug
+    /// duplicates it.
+    pub fn reserve(&mut self, id: &str) {
+        self.taken.entry(id.to_string()).or_insert(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slugs(style: SlugStyle, headings: &[&str]) -> Vec<String> {
+        let mut slugger = Slugger::new(style);
+        headings.iter().map(|h| slugger.slug(h)).collect()
+    }
+
+    #[test]
+    fn test_reserved_ids_are_skipped() {
+        let mut slugger = Slugger::new(SlugStyle::Github);
+        slugger.reserve("intro");
+        assert_eq!(slugger.slug("Intro"), "intro-1");
+    }
+
+    #[test]
+    fn test_legacy() {
+        let slug = |text| SlugStyle::Legacy.slugify(text);
+        assert_eq!(slug("Hello World"), "hello-world");
+        assert_eq!(slug("What's New?"), "whats-new");
+        assert_eq!(slug("  Multiple   Spaces  "), "multiple-spaces");
+        assert_eq!(slug("my_function — overview"), "myfunction-overview");
+    }
+
+    #[test]
+    fn test_github() {
+        // Examples from github-slugger's fixtures.
+        let slug = |text| SlugStyle::Github.slugify(text);
+        assert_eq!(slug("my_function — overview"), "my_function--overview");
+        assert_eq!(slug("Foo Bar"), "foo-bar");
+        assert_eq!(slug("foo  bar"), "foo--bar");
+        assert_eq!(slug("I ♥ unicode"), "i--unicode");
+        assert_eq!(slug("Привет non-latin 你好"), "привет-non-latin-你好");
+        assert_eq!(slug("Emoji 😄"), "emoji-");
+        assert_eq!(slug("1.2.3 - Release notes"), "123---release-notes");
+        assert_eq!(
+            slugs(SlugStyle::Github, &["foo", "foo", "foo-1", "foo"]),
+            vec!["foo", "foo-1", "foo-1-1", "foo-2"]
+        );
+    }
+
+    #[test]
+    fn test_gitlab() {
+        // Examples from GitLab's Markdown documentation.
+        assert_eq!(
+            slugs(
+                SlugStyle::Gitlab,
+                &[
+                    "This heading has spaces in it",
+                    "This heading has a \u{1f603} in it",
+                    "This heading has Unicode in it: 한글",
+                    "This heading has spaces in it",
+                    "This heading has spaces in it",
+                    "This heading has 3.5 in it (and parentheses)",
+                    "This      heading has     multiple spaces",
+                ]
+            ),
+            vec![
+                "this-heading-has-spaces-in-it",
+                "this-heading-has-a-in-it",
+                "this-heading-has-unicode-in-it-한글",
+                "this-heading-has-spaces-in-it-1",
+                "this-heading-has-spaces-in-it-2",
+                "this-heading-has-35-in-it-and-parentheses",
+                "this-heading-has-multiple-spaces",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mdbook() {
+        // Examples from mdBook's `normalize_id` tests.
+        let slug = |text| SlugStyle::Mdbook.slugify(text);
+        assert_eq!(
+            slug("`--passes`: add more rustdoc passes"),
+            "--passes-add-more-rustdoc-passes"
+        );
+        assert_eq!(
+            slug("Method-call 🐙 expressions \u{1f47c}"),
+            "method-call--expressions-"
+        );
+        assert_eq!(slug("_-_12345"), "_-_12345");
+        assert_eq!(slug("12345"), "12345");
+        assert_eq!(slug("中文"), "中文");
+        assert_eq!(slug("にほんご"), "にほんご");
+        assert_eq!(slug("한국어"), "한국어");
+        assert_eq!(slug("Über"), "Über");
+        assert_eq!(slug(""), "");
+    }
+
+    #[test]
+    fn test_pandoc() {
+        // Examples from the Pandoc manual's section on heading identifiers.
+        let slug = |text| SlugStyle::Pandoc.slugify(text);
+        assert_eq!(
+            slug("Heading identifiers in HTML"),
+            "heading-identifiers-in-html"
+        );
+        assert_eq!(slug("Maître d'hôtel"), "maître-dhôtel");
+        assert_eq!(slug("Dogs?--in my house?"), "dogs--in-my-house");
+        assert_eq!(slug("HTML, S5, or RTF?"), "html-s5-or-rtf");
+        assert_eq!(slug("3. Applications"), "applications");
+        assert_eq!(slug("33"), "section");
+        assert_eq!(slug("Version 2.1"), "version-2.1");
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::ast::prose_runs;
+use crate::frontmatter::Frontmatter;
+use crate::index::SearchIndex;
+
+/// A tag and the number of files carrying it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// A file carrying a tag.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaggedFile {
+    pub path: PathBuf,
+    /// Path relative to the root folder, with `/` separators.
+    pub rel_path: String,
+    pub title: Option<String>,
+}
+
+/// Map from tags to the files of a folder that carry them.
+///
+/// Built from the [`SearchIndex`], which reads each file's tags when it is
+/// indexed, so refreshing the index and rebuilding this map only reads the
+/// files that changed.
+pub struct TagIndex {
+    root: PathBuf,
+    /// Case-folded tag to its files, in path order.
+    tags: BTreeMap<String, Vec<TaggedFile>>,
+}
+
+impl TagIndex {
+    /// Collect the tags of the files in `index`.
+    pub fn from_index(index: &SearchIndex) -> Self {
+        Self::new(index.root(), index.tagged_files())
+    }
+
+    /// Collect `files`, given as paths relative to `root` with their titles
+    /// and tags.
+    pub fn new<'a>(
+        root: &Path,
+        files: impl IntoIterator<Item = (&'a Path, Option<&'a str>, &'a [String])>,
+    ) -> Self {
+        let mut tags: BTreeMap<String, Vec<TaggedFile>> = BTreeMap::new();
+        for (rel, title, file_tags) in files {
+            let rel_path = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            for tag in file_tags {
+                tags.entry(tag.clone()).or_default().push(TaggedFile {
+                    path: root.join(rel),
+                    rel_path: rel_path.clone(),
+                    title: title.map(str::to_string),
+                });
+            }
+        }
+        Self {
+            root: root.to_path_buf(),
+            tags,
+        }
+    }
+
+    /// The root folder the tags were collected from.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Every tag with its number of files, most used first, then by name.
+    pub fn tags(&self) -> Vec<TagCount> {
+        let mut counts: Vec<TagCount> = self
+            .tags
+            .iter()
+            .map(|(tag, files)| TagCount {
+                tag: tag.clone(),
+                count: files.len(),
+            })
+            .collect();
+        counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+        counts
+    }
+
+    /// The files carrying `tag`, in path order. The tag is compared
+    /// case-insensitively, with or without a leading `#`.
+    pub fn files(&self, tag: &str) -> &[TaggedFile] {
+        let tag = caseless::default_case_fold_str(tag.strip_prefix('#').unwrap_or(tag));
+        self.tags.get(&tag).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// The tags of a document: frontmatter `tags` and `keywords`, then inline
+/// `#tags` in its prose, case-folded and without duplicates.
+pub fn document_tags(frontmatter: Option<&Frontmatter>, body: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    let mut add = |tag: &str| {
+        let tag = caseless::default_case_fold_str(tag);
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    };
+    if let Some(fm) = frontmatter {
+        for tag in fm.tags().into_iter().chain(fm.list("keywords")) {
+            add(&tag);
+        }
+    }
+    // Only parse the body when it can hold an inline tag.
+    if body.contains('#') {
+        for run in prose_runs(body) {
+            for tag in inline_tags(&run) {
+                add(tag);
+            }
+        }
+    }
+    tags
+}
+
+/// Inline tags such as `#release` or `#team/docs` in a run of prose.
+///
+/// A tag starts after whitespace or an opening bracket, so URL fragments and
+/// `C#` are not tags, and must contain a letter, so `#42` is not one either.
+fn inline_tags(text: &str) -> Vec<&str> {
+    let is_tag_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '/');
+    let mut tags = Vec::new();
+    let mut prev = None;
+    for (i, c) in text.char_indices() {
+        if c == '#' && prev.is_none_or(|p: char| p.is_whitespace() || matches!(p, '(' | '[')) {
+            let rest = &text[i + 1..];
+            let end = rest.find(|c| !is_tag_char(c)).unwrap_or(rest.len());
+            let tag = rest[..end].trim_end_matches(['-', '/']);
+            if tag.chars().any(char::is_alphabetic) {
+                tags.push(tag);
+            }
+        }
+        prev = Some(c);
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontmatter::strip_frontmatter;
+
+    fn tags_of(content: &str) -> Vec<String> {
+        let (fm, body) = strip_frontmatter(content);
+        document_tags(fm.as_ref(), body)
+    }
+
+    #[test]
+    fn test_inline_tags() {
+        assert_eq!(
+            inline_tags("Ship #release-2 (#Team/Docs) today #"),
+            vec!["release-2", "Team/Docs"]
+        );
+        assert!(inline_tags("issue #42, C# and page.html#intro").is_empty());
+    }
+
+    #[test]
+    fn test_document_tags() {
+        let content = "---\ntags: [Ops, runbook]\nkeywords: oncall\n---\n# Heading #not\n\n\
+            Page the #OnCall and #ops people.\n\n`#code` and\n\n```\n#fence\n```\n\n\
+            - list #item\n";
+        assert_eq!(tags_of(content), vec!["ops", "runbook", "oncall", "item"]);
+    }
+
+    #[test]
+    fn test_tag_index() {
+        let a: &[String] = &["ops".to_string(), "runbook".to_string()];
+        let b: &[String] = &["ops".to_string()];
+        let index = TagIndex::new(
+            Path::new("/root"),
+            [
+                (Path::new("a.md"), Some("Alpha"), a),
+                (Path::new("docs/b.md"), None, b),
+            ],
+        );
+        assert_eq!(
+            index.tags(),
+            vec![
+                TagCount {
+                    tag: "ops".to_string(),
+                    count: 2
+                },
+                TagCount {
+                    tag: "runbook".to_string(),
+                    count: 1
+                },
+            ]
+        );
+        let files: Vec<&str> = index
+            .files("#OPS")
+            .iter()
+            .map(|f| f.rel_path.as_str())
+            .collect();
+        assert_eq!(files, vec!["a.md", "docs/b.md"]);
+        assert_eq!(index.files("runbook")[0].title.as_deref(), Some("Alpha"));
+        assert!(index.files("missing").is_empty());
+    }
+}
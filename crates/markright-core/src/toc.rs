@@ -1,13 +1,219 @@
-use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use crate::ast::MdNode;
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{BlockKind, MdNode, source_map};
+use crate::files::{is_within_root, modified_millis, write_atomic};
+use crate::frontmatter::{Frontmatter, strip_frontmatter};
+use crate::slug::SlugStyle;
 
 /// A heading entry for the Table of Contents.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct TocEntry {
     pub level: u8,
     pub text: String,
     pub id: String,
+    /// Section number, when numbering is on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<String>,
+}
+
+/// How headings are numbered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberingStyle {
+    /// Headings are not numbered.
+    #[default]
+    None,
+    /// `1`, `1.2`, `1.2.3`.
+    #[serde(alias = "1.2.3")]
+    Decimal,
+    /// `1`, `1.a`, `1.a.i`, cycling through numbers, letters and roman
+    /// numerals at deeper levels.
+    #[serde(alias = "1.a.i")]
+    Outline,
+}
+
+impl NumberingStyle {
+    /// The style for a document, from its frontmatter `numbering` field.
+    ///
+    /// `numbering: true` turns numbering on, in this style or in decimal if
+    /// this style is `None`; `numbering: false` turns it off; a style name
+    /// such as `numbering: 1.a.i` picks that style. Without the field, or
+    /// with a value that is none of these, the style is unchanged.
+    #[must_use]
+    pub fn for_document(self, frontmatter: Option<&Frontmatter>) -> Self {
+        let Some(value) = frontmatter.and_then(|fm| fm.get("numbering")) else {
+            return self;
+        };
+        match value.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "on" if self == Self::None => Self::Decimal,
+            "false" | "no" | "off" | "none" => Self::None,
+            "decimal" | "1.2.3" => Self::Decimal,
+            "outline" | "1.a.i" => Self::Outline,
+            _ => self,
+        }
+    }
+
+    /// Format a section number from its position at each depth, 1-based.
+    fn format(self, path: &[usize]) -> String {
+        path.iter()
+            .enumerate()
+            .map(|(depth, &n)| match (self, depth % 3) {
+                (Self::Outline, 1) => alphabetic(n),
+                (Self::Outline, 2) => roman(n),
+                _ => n.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+/// `1` → `a`, `26` → `z`, `27` → `aa`.
+fn alphabetic(mut n: usize) -> String {
+    let mut letters = Vec::new();
+    while n > 0 {
+        n -= 1;
+        letters.push(char::from(b'a' + u8::try_from(n % 26).unwrap_or(0)));
+        n /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+/// Lowercase roman numeral of `n`.
+fn roman(mut n: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut numeral = String::new();
+    for (value, digits) in NUMERALS {
+        while n >= value {
+            numeral.push_str(digits);
+            n -= value;
+        }
+    }
+    numeral
+}
+
+/// Number the headings of a serialized AST in `style`, setting the `number`
+/// of each heading node. With [`NumberingStyle::None`] existing numbers are
+/// cleared.
+///
+/// Numbers follow the nesting of [`nest_toc`]: a heading is numbered below
+/// the closest heading before it with a lower level, so `H1, H3` numbers
+/// as `1, 1.1`. A lone H1 at the start of the document is its title and is
+/// not numbered.
+pub fn number_headings(root: &mut MdNode, style: NumberingStyle) {
+    let mut headings = Vec::new();
+    collect_heading_nodes(root, &mut headings);
+    let h1_count = headings.iter().filter(|h| h.level == Some(1)).count();
+    let title = h1_count == 1 && headings.first().is_some_and(|h| h.level == Some(1));
+
+    // Level and position of each heading on the path to the current one.
+    let mut path: Vec<(u8, usize)> = Vec::new();
+    for (i, heading) in headings.into_iter().enumerate() {
+        let level = heading.level.unwrap_or(1);
+        if style == NumberingStyle::None || (title && i == 0) {
+            heading.number = None;
+            continue;
+        }
+        let mut position = 1;
+        while let Some(&(last_level, last_position)) = path.last() {
+            if last_level < level {
+                break;
+            }
+            position = last_position + 1;
+            path.pop();
+        }
+        path.push((level, position));
+        let positions: Vec<usize> = path.iter().map(|&(_, p)| p).collect();
+        heading.number = Some(style.format(&positions));
+    }
+}
+
+fn collect_heading_nodes<'a>(node: &'a mut MdNode, headings: &mut Vec<&'a mut MdNode>) {
+    if node.node_type == "Heading" {
+        headings.push(node);
+        return;
+    }
+    for child in &mut node.children {
+        collect_heading_nodes(child, headings);
+    }
+}
+
+/// A TOC entry with the entries of its subsections.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TocNode {
+    #[serde(flatten)]
+    pub entry: TocEntry,
+    pub children: Vec<TocNode>,
+}
+
+/// Which headings a nested TOC includes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TocOptions {
+    /// Shallowest heading level to include.
+    pub min_level: u8,
+    /// Deepest heading level to include.
+    pub max_level: u8,
+    /// Leave out the document title: the first heading, if it is an H1.
+    pub exclude_title: bool,
+}
+
+impl Default for TocOptions {
+    fn default() -> Self {
+        Self {
+            min_level: 1,
+            max_level: 6,
+            exclude_title: false,
+        }
+    }
+}
+
+/// Nest flat TOC entries by level.
+///
+/// Each entry goes under the closest entry before it with a lower level,
+/// so a skipped level (an H3 straight after an H1) nests one step, without
+/// an empty placeholder. Entries with no such entry, like the H2s of a
+/// document without an H1 or an H2 that comes before the first H1, are top
+/// level. An entry never becomes the parent of a shallower one that follows
+/// it: after `H1, H3, H2` the H2 is a sibling of the H3.
+pub fn nest_toc(entries: &[TocEntry], options: &TocOptions) -> Vec<TocNode> {
+    let skip_title = options.exclude_title && entries.first().is_some_and(|e| e.level == 1);
+    let included: Vec<&TocEntry> = entries
+        .iter()
+        .skip(usize::from(skip_title))
+        .filter(|e| (options.min_level..=options.max_level).contains(&e.level))
+        .collect();
+    let mut next = 0;
+    nest_below(&included, &mut next, 0)
+}
+
+/// Nest the entries from `next` on until one at or above `parent_level`.
+fn nest_below(entries: &[&TocEntry], next: &mut usize, parent_level: u8) -> Vec<TocNode> {
+    let mut nodes = Vec::new();
+    while let Some(entry) = entries.get(*next).filter(|e| e.level > parent_level) {
+        *next += 1;
+        nodes.push(TocNode {
+            entry: (*entry).clone(),
+            children: nest_below(entries, next, entry.level),
+        });
+    }
+    nodes
 }
 
 /// Extract headings from a serialized `MdNode` AST tree.
@@ -26,6 +232,7 @@ fn collect_headings(node: &MdNode, entries: &mut Vec<TocEntry>) {
             level,
             text,
             id: id.to_string(),
+            number: node.number.clone(),
         });
     }
     for child in &node.children {
@@ -45,6 +252,187 @@ fn collect_node_text(node: &MdNode) -> String {
     text
 }
 
+/// Marker comment after which a Markdown TOC is written.
+const TOC_START: &str = "<!-- toc -->";
+/// Marker comment closing a Markdown TOC.
+const TOC_END: &str = "<!-- tocstop -->";
+
+/// Error returned when a Markdown TOC cannot be written into a document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TocError {
+    /// The document has no `<!-- toc -->` marker to write the TOC after.
+    NoMarker,
+    /// The file is not inside the open folder.
+    OutsideRoot { path: PathBuf },
+    /// The file changed since it was read.
+    Modified { path: PathBuf },
+    /// The file could not be read or written.
+    Io { path: PathBuf, message: String },
+}
+
+impl std::fmt::Display for TocError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoMarker => write!(f, "the document has no {TOC_START} marker"),
+            Self::OutsideRoot { path } => {
+                write!(f, "{} is outside the open folder", path.display())
+            }
+            Self::Modified { path } => write!(f, "{} changed since it was read", path.display()),
+            Self::Io { path, message } => write!(f, "{}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for TocError {}
+
+/// Write a Markdown TOC of the document's headings after its
+/// `<!-- toc -->` marker, replacing everything up to the closing
+/// `<!-- tocstop -->` (or `<!-- /toc -->`) marker. The closing marker is
+/// added if missing.
+///
+/// The TOC is a nested list of links to the headings' ids in the `slugs`
+/// style, as in the rendered document, and includes the headings chosen by
+/// `options`.
+/// Markers inside code blocks are ignored, and the document's line endings
+/// are kept. Refreshing an up-to-date TOC returns the content unchanged.
+///
+/// # Errors
+///
+/// Returns [`TocError::NoMarker`] if the document has no start marker.
+pub fn insert_markdown_toc(
+    content: &str,
+    options: &TocOptions,
+    slugs: SlugStyle,
+) -> Result<String, TocError> {
+    let (_, body) = strip_frontmatter(content);
+    let map = source_map(body, slugs);
+    let lines: Vec<&str> = body.split_inclusive('\n').collect();
+    let is_marker = |i: usize, names: &[&str]| {
+        let in_code = map
+            .block_at(i + 1)
+            .is_some_and(|b| matches!(b.kind, BlockKind::Code { .. }));
+        !in_code && marker_name(lines[i]).is_some_and(|name| names.contains(&name.as_str()))
+    };
+    let start = (0..lines.len())
+        .find(|&i| is_marker(i, &["toc"]))
+        .ok_or(TocError::NoMarker)?;
+    let end = (start + 1..lines.len()).find(|&i| is_marker(i, &["tocstop", "/toc"]));
+
+    let newline = if body.contains("\r\n") { "\r\n" } else { "\n" };
+    let entries: Vec<TocEntry> = map
+        .headings
+        .iter()
+        .map(|h| TocEntry {
+            level: h.level,
+            text: h.text.clone(),
+            id: h.id.clone(),
+            number: None,
+        })
+        .collect();
+    let mut list = String::new();
+    write_toc_list(&nest_toc(&entries, options), 0, newline, &mut list);
+
+    let mut out = String::with_capacity(content.len() + list.len());
+    out.push_str(&content[..content.len() - body.len()]);
+    for line in &lines[..=start] {
+        out.push_str(line);
+    }
+    if !lines[start].ends_with('\n') {
+        out.push_str(newline);
+    }
+    out.push_str(newline);
+    if !list.is_empty() {
+        out.push_str(&list);
+        out.push_str(newline);
+    }
+    let rest = if let Some(end) = end {
+        &lines[end..]
+    } else {
+        out.push_str(TOC_END);
+        out.push_str(newline);
+        &lines[start + 1..]
+    };
+    for line in rest {
+        out.push_str(line);
+    }
+    Ok(out)
+}
+
+/// Refresh the Markdown TOC of the file at `path` under `root` with
+/// [`insert_markdown_toc`], refusing if its modification time is no longer
+/// `mtime`, in milliseconds since the Unix epoch as from
+/// [`modified_millis`]. Returns whether the file changed; an up-to-date
+/// file is not written.
+///
+/// # Errors
+///
+/// Returns [`TocError::OutsideRoot`] if the file is not under `root`,
+/// [`TocError::Modified`] if it changed since `mtime`,
+/// [`TocError::NoMarker`] if it has no start marker, or [`TocError::Io`] if
+/// it cannot be read or written.
+pub fn write_markdown_toc(
+    root: &Path,
+    path: &Path,
+    mtime: u64,
+    options: &TocOptions,
+    slugs: SlugStyle,
+) -> Result<bool, TocError> {
+    let io_error = |e: std::io::Error| TocError::Io {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    };
+    if !is_within_root(root, path).map_err(io_error)? {
+        return Err(TocError::OutsideRoot {
+            path: path.to_path_buf(),
+        });
+    }
+    if modified_millis(path) != Some(mtime) {
+        return Err(TocError::Modified {
+            path: path.to_path_buf(),
+        });
+    }
+    let content = fs::read_to_string(path).map_err(io_error)?;
+    let updated = insert_markdown_toc(&content, options, slugs)?;
+    if updated == content {
+        return Ok(false);
+    }
+    write_atomic(path, &updated).map_err(io_error)?;
+    Ok(true)
+}
+
+/// The lowercased name inside a line holding only an HTML comment, such as
+/// `toc` for `<!-- toc -->`.
+fn marker_name(line: &str) -> Option<String> {
+    let inner = line
+        .trim()
+        .strip_prefix("<!--")?
+        .strip_suffix("-->")?
+        .trim();
+    Some(inner.to_ascii_lowercase())
+}
+
+/// Write `nodes` as a nested Markdown list of heading links, indented two
+/// spaces per level.
+fn write_toc_list(nodes: &[TocNode], depth: usize, newline: &str, out: &mut String) {
+    for node in nodes {
+        let text = node
+            .entry
+            .text
+            .replace('\\', "\\\\")
+            .replace('[', "\\[")
+            .replace(']', "\\]");
+        out.push_str(&"  ".repeat(depth));
+        out.push_str("- [");
+        out.push_str(&text);
+        out.push_str("](#");
+        out.push_str(&node.entry.id);
+        out.push(')');
+        out.push_str(newline);
+        write_toc_list(&node.children, depth + 1, newline, out);
+    }
+}
+
 /// Extract headings from Markdown source for TOC generation.
 ///
 /// Simple regex-based implementation. Prefer `extract_toc_from_ast` for
@@ -73,7 +461,12 @@ pub fn extract_toc(markdown: &str) -> Vec<TocEntry> {
                     .to_lowercase()
                     .replace(|c: char| !c.is_alphanumeric() && c != ' ' && c != '-', "")
                     .replace(' ', "-");
-                entries.push(TocEntry { level, text, id });
+                entries.push(TocEntry {
+                    level,
+                    text,
+                    id,
+                    number: None,
+                });
             }
         }
     }
@@ -88,7 +481,8 @@ mod tests {
 
     #[test]
     fn test_extract_toc_from_ast() {
-        let ast = serialize_ast("# Title\n\nSome text\n\n## Section 1\n\n### Sub 1.1\n\n## Section 2");
+        let ast =
+            serialize_ast("# Title\n\nSome text\n\n## Section 1\n\n### Sub 1.1\n\n## Section 2");
         let toc = extract_toc_from_ast(&ast);
         assert_eq!(toc.len(), 4);
         assert_eq!(toc[0].level, 1);
@@ -112,6 +506,219 @@ mod tests {
         assert_eq!(toc[2].id, "foo-2");
     }
 
+    /// The nested TOC as `level:text` labels, children in brackets.
+    fn outline(nodes: &[TocNode]) -> String {
+        nodes
+            .iter()
+            .map(|n| {
+                let label = format!("{}:{}", n.entry.level, n.entry.text);
+                if n.children.is_empty() {
+                    label
+                } else {
+                    format!("{label}[{}]", outline(&n.children))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn nested(markdown: &str, options: &TocOptions) -> String {
+        outline(&nest_toc(
+            &extract_toc_from_ast(&serialize_ast(markdown)),
+            options,
+        ))
+    }
+
+    #[test]
+    fn test_nest_toc() {
+        let all = TocOptions::default();
+        assert_eq!(
+            nested("# T\n## A\n### A1\n## B\n# U", &all),
+            "1:T[2:A[3:A1] 2:B] 1:U"
+        );
+        // Skipped levels nest one step; shallower entries close deeper ones.
+        assert_eq!(
+            nested("# T\n### X\n## A\n#### Y\n## B", &all),
+            "1:T[3:X 2:A[4:Y] 2:B]"
+        );
+        // No H1, or headings before the first H1, stay at the top level.
+        assert_eq!(nested("## A\n### A1\n## B", &all), "2:A[3:A1] 2:B");
+        assert_eq!(nested("### X\n## A\n# T\n## B", &all), "3:X 2:A 1:T[2:B]");
+        assert!(nest_toc(&[], &all).is_empty());
+    }
+
+    #[test]
+    fn test_nest_toc_options() {
+        let md = "# Title\n## A\n### A1\n#### A1a\n## B\n# Appendix";
+        let no_title = TocOptions {
+            exclude_title: true,
+            ..TocOptions::default()
+        };
+        assert_eq!(nested(md, &no_title), "2:A[3:A1[4:A1a]] 2:B 1:Appendix");
+        let depth = TocOptions {
+            min_level: 2,
+            max_level: 3,
+            exclude_title: false,
+        };
+        assert_eq!(nested(md, &depth), "2:A[3:A1] 2:B");
+        // Only a leading H1 is a title.
+        assert_eq!(nested("## A\n# T", &no_title), "2:A 1:T");
+    }
+
+    fn numbers(markdown: &str, style: NumberingStyle) -> Vec<String> {
+        let mut ast = serialize_ast(markdown);
+        number_headings(&mut ast, style);
+        extract_toc_from_ast(&ast)
+            .into_iter()
+            .map(|e| format!("{} {}", e.number.as_deref().unwrap_or("-"), e.text))
+            .collect()
+    }
+
+    #[test]
+    fn test_number_headings() {
+        let md = "# Spec\n## Scope\n## Design\n### Parser\n#### Lexer\n### Renderer\n## Tests";
+        assert_eq!(
+            numbers(md, NumberingStyle::Decimal),
+            vec![
+                "- Spec",
+                "1 Scope",
+                "2 Design",
+                "2.1 Parser",
+                "2.1.1 Lexer",
+                "2.2 Renderer",
+                "3 Tests"
+            ]
+        );
+        assert_eq!(
+            numbers(md, NumberingStyle::Outline),
+            vec![
+                "- Spec",
+                "1 Scope",
+                "2 Design",
+                "2.a Parser",
+                "2.a.i Lexer",
+                "2.b Renderer",
+                "3 Tests"
+            ]
+        );
+        assert_eq!(numbers(md, NumberingStyle::None)[1], "- Scope");
+        // Several H1s are chapters, and skipped levels nest one step.
+        assert_eq!(
+            numbers("# One\n### A\n## B\n# Two", NumberingStyle::Decimal),
+            vec!["1 One", "1.1 A", "1.2 B", "2 Two"]
+        );
+        assert_eq!(alphabetic(28), "ab");
+        assert_eq!(roman(14), "xiv");
+    }
+
+    #[test]
+    fn test_numbering_from_frontmatter() {
+        let style = |content: &str, default: NumberingStyle| {
+            let (fm, _) = crate::frontmatter::strip_frontmatter(content);
+            default.for_document(fm.as_ref())
+        };
+        let on = "---\nnumbering: true\n---\n";
+        assert_eq!(style(on, NumberingStyle::None), NumberingStyle::Decimal);
+        assert_eq!(style(on, NumberingStyle::Outline), NumberingStyle::Outline);
+        assert_eq!(
+            style("---\nnumbering: \"1.a.i\"\n---\n", NumberingStyle::None),
+            NumberingStyle::Outline
+        );
+        assert_eq!(
+            style("---\nnumbering: off\n---\n", NumberingStyle::Decimal),
+            NumberingStyle::None
+        );
+        assert_eq!(
+            style("# No frontmatter", NumberingStyle::Decimal),
+            NumberingStyle::Decimal
+        );
+    }
+
+    #[test]
+    fn test_insert_markdown_toc() {
+        let md = "# Project\n\n<!-- toc -->\n- stale\n<!-- tocstop -->\n\n\
+            ## Install [beta]\n\n### Linux\n\n## Usage\n";
+        let options = TocOptions {
+            exclude_title: true,
+            ..TocOptions::default()
+        };
+        let updated = insert_markdown_toc(md, &options, SlugStyle::Legacy).unwrap();
+        assert_eq!(
+            updated,
+            "# Project\n\n<!-- toc -->\n\n- [Install \\[beta\\]](#install-beta)\n  \
+            - [Linux](#linux)\n- [Usage](#usage)\n\n<!-- tocstop -->\n\n\
+            ## Install [beta]\n\n### Linux\n\n## Usage\n"
+        );
+        assert_eq!(
+            insert_markdown_toc(&updated, &options, SlugStyle::Legacy).unwrap(),
+            updated
+        );
+        let github = insert_markdown_toc(
+            "<!-- toc -->\n# my_function — overview\n",
+            &TocOptions::default(),
+            SlugStyle::Github,
+        )
+        .unwrap();
+        assert!(github.contains("- [my_function — overview](#my_function--overview)"));
+    }
+
+    #[test]
+    fn test_insert_markdown_toc_markers() {
+        let options = TocOptions::default();
+        // A missing end marker is added; markers in code are not markers.
+        let md =
+            "---\ntitle: T\n---\n<!-- TOC -->\r\n\r\n```\r\n<!-- tocstop -->\r\n```\r\n# A\r\n";
+        assert_eq!(
+            insert_markdown_toc(md, &options, SlugStyle::Legacy).unwrap(),
+            "---\ntitle: T\n---\n<!-- TOC -->\r\n\r\n- [A](#a)\r\n\r\n<!-- tocstop -->\r\n\
+            \r\n```\r\n<!-- tocstop -->\r\n```\r\n# A\r\n"
+        );
+        assert_eq!(
+            insert_markdown_toc(
+                "# A\n\n```\n<!-- toc -->\n```\n",
+                &options,
+                SlugStyle::Legacy
+            ),
+            Err(TocError::NoMarker)
+        );
+    }
+
+    #[test]
+    fn test_write_markdown_toc() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("docs");
+        fs::create_dir(&root).unwrap();
+        let path = root.join("README.md");
+        fs::write(&path, "<!-- toc -->\n<!-- /toc -->\n# A\n").unwrap();
+        let options = TocOptions::default();
+        let write = |path: &Path, mtime| {
+            write_markdown_toc(&root, path, mtime, &options, SlugStyle::Legacy)
+        };
+
+        let mtime = modified_millis(&path).unwrap();
+        assert_eq!(
+            write(&path, mtime + 1),
+            Err(TocError::Modified { path: path.clone() })
+        );
+        assert!(write(&path, mtime).unwrap());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "<!-- toc -->\n\n- [A](#a)\n\n<!-- /toc -->\n# A\n"
+        );
+        assert!(!write(&path, modified_millis(&path).unwrap()).unwrap());
+
+        let outside = dir.path().join("other.md");
+        fs::write(&outside, "<!-- toc -->\n# B\n").unwrap();
+        let escaped = root.join("../other.md");
+        assert_eq!(
+            write(&escaped, modified_millis(&outside).unwrap()),
+            Err(TocError::OutsideRoot {
+                path: escaped.clone()
+            })
+        );
+        assert_eq!(fs::read_to_string(&outside).unwrap(), "<!-- toc -->\n# B\n");
+    }
+
     #[allow(deprecated)]
     #[test]
     fn test_extract_toc_legacy() {
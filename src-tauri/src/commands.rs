@@ -1,32 +1,121 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use markright_core::ast::{MdNode, serialize_ast_themed};
-use markright_core::config::AppConfig;
-use markright_core::frontmatter::{Frontmatter, strip_frontmatter};
+use markright_core::config::{AppConfig, FolderConfig};
+use markright_core::files::modified_millis;
+use markright_core::frontmatter::{
+    DocumentProperties, FieldEdit, Frontmatter, FrontmatterEditError, FrontmatterError,
+    parse_frontmatter, strip_frontmatter, write_frontmatter_edits,
+};
+use markright_core::fuzzy::{FileFinder, FileMatch, HeadingFinder, HeadingMatch};
+use markright_core::index::SearchIndex;
 use markright_core::license::{LicenseStatus, check_license_file};
-use markright_core::search::{SearchResult, search_files};
-use markright_core::toc::{TocEntry, extract_toc_from_ast};
+use markright_core::links::{Backlink, LinkGraph};
+use markright_core::replace::{
+    FilePreview, ReplaceError, apply_replacements, preview_replacements,
+};
+use markright_core::saved_search::{SavedSearchError, SearchStore, WorkspaceSearches};
+use markright_core::schema::{
+    FileViolations, SchemaViolation, config_violations, validate_frontmatter, validate_tree,
+};
+use markright_core::search::{
+    CancelToken, SearchBatch, SearchError, SearchOptions, SearchResult, search_files_streaming,
+};
+use markright_core::tags::{TagCount, TagIndex, TaggedFile};
+use markright_core::toc::{
+    NumberingStyle, TocEntry, TocError, TocNode, TocOptions, extract_toc_from_ast, nest_toc,
+    number_headings, write_markdown_toc,
+};
 use markright_core::tree::{TreeNode, build_tree};
 use serde::Serialize;
-use tauri::{AppHandle, Manager, State};
+use tauri::{AppHandle, Emitter, Manager, State};
 
 use crate::state::AppState;
 
 /// Response for `get_document` containing the full parsed document.
 #[derive(Debug, Serialize)]
 pub struct DocumentResponse {
+    /// Modification time of the file as read, in milliseconds since the Unix
+    /// epoch, to pass back to `edit_frontmatter`.
+    pub mtime: Option<u64>,
     pub ast: MdNode,
+    /// Empty when the frontmatter sets `toc: false`.
     pub toc: Vec<TocEntry>,
     pub frontmatter: Option<Frontmatter>,
+    /// The frontmatter fields the viewer acts on, such as `lang`.
+    pub properties: DocumentProperties,
+    /// Why the frontmatter could not be parsed, if it could not.
+    pub frontmatter_error: Option<FrontmatterError>,
+    /// How the frontmatter fails the schema set for the document's folder.
+    pub schema_violations: Vec<SchemaViolation>,
 }
 
+/// Structured error for commands whose failures the frontend needs to inspect.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum CommandError {
+    /// The command needs an open folder but none is open.
+    NoFolderOpen,
+    /// The search query could not be executed.
+    Search(SearchError),
+    /// Replacements could not be previewed or applied.
+    Replace(ReplaceError),
+    /// A saved search could not be found or changed.
+    SavedSearch(SavedSearchError),
+    /// A Markdown TOC could not be written into a file.
+    Toc(TocError),
+    /// Frontmatter fields could not be changed.
+    Frontmatter(FrontmatterEditError),
+    /// The command failed unexpectedly, e.g. its worker thread panicked.
+    Internal(String),
+}
+
+impl From<SearchError> for CommandError {
+    fn from(e: SearchError) -> Self {
+        Self::Search(e)
+    }
+}
+
+impl From<ReplaceError> for CommandError {
+    fn from(e: ReplaceError) -> Self {
+        Self::Replace(e)
+    }
+}
+
+impl From<SavedSearchError> for CommandError {
+    fn from(e: SavedSearchError) -> Self {
+        Self::SavedSearch(e)
+    }
+}
+
+impl From<TocError> for CommandError {
+    fn from(e: TocError) -> Self {
+        Self::Toc(e)
+    }
+}
+
+impl From<FrontmatterEditError> for CommandError {
+    fn from(e: FrontmatterEditError) -> Self {
+        Self::Frontmatter(e)
+    }
+}
+
+/// How long the search index is used as it is before the files are checked
+/// for changes again.
+const INDEX_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
 /// Open a folder and return its file tree.
 ///
+/// The folder's search index is loaded and brought up to date on a
+/// background thread, cancelling the indexing of the previous folder.
+///
 /// Tauri commands require owned argument types for deserialization.
 #[tauri::command]
 #[allow(clippy::needless_pass_by_value)]
 pub fn open_folder(
     path: String,
+    app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<Vec<TreeNode>, String> {
     let path = PathBuf::from(&path);
@@ -35,7 +124,10 @@ pub fn open_folder(
     }
 
     let tree = build_tree(&path).map_err(|e| e.to_string())?;
-    *state.root_folder.lock().unwrap() = Some(path);
+    *state.root_folder.lock().unwrap() = Some(path.clone());
+    let previous = std::mem::take(&mut *state.index_cancel.lock().unwrap());
+    previous.cancel();
+    refresh_index_in_background(&app, path);
     Ok(tree)
 }
 
@@ -49,43 +141,559 @@ pub fn get_tree(state: State<'_, AppState>) -> Result<Vec<TreeNode>, String> {
 }
 
 /// Parse a Markdown file and return its AST, TOC, and frontmatter.
+///
+/// Headings are numbered in the `numbering` style unless the document's
+/// frontmatter sets its own, and get ids in the slug style of its folder.
 #[tauri::command]
 #[allow(clippy::needless_pass_by_value)]
-pub fn get_document(path: String, code_theme: Option<String>) -> Result<DocumentResponse, String> {
+pub fn get_document(
+    path: String,
+    code_theme: Option<String>,
+    numbering: Option<NumberingStyle>,
+    state: State<'_, AppState>,
+) -> Result<DocumentResponse, String> {
     let path = PathBuf::from(&path);
+    // Read the mtime first so a write racing the read is caught on edit.
+    let mtime = modified_millis(&path);
     let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
     let theme = code_theme.as_deref().unwrap_or("ocean");
+    let folder = folder_config(&state, &path);
+    let slugs = folder.slug_style();
 
-    let (frontmatter, body) = strip_frontmatter(&content);
-    let ast = serialize_ast_themed(body, theme);
-    let toc = extract_toc_from_ast(&ast);
+    let (frontmatter, body) = parse_frontmatter(&content);
+    let (frontmatter, frontmatter_error) = match frontmatter {
+        Ok(frontmatter) => (frontmatter, None),
+        Err(e) => (None, Some(e)),
+    };
+    // Frontmatter that does not parse is reported as `frontmatter_error`.
+    let mut schema_violations = config_violations(&folder);
+    if let Some(schema) = &folder.frontmatter_schema
+        && frontmatter_error.is_none()
+    {
+        schema_violations.extend(validate_frontmatter(schema, frontmatter.as_ref()));
+    }
+    let properties = DocumentProperties::from_frontmatter(frontmatter.as_ref());
+    let mut ast = serialize_ast_themed(body, theme, slugs);
+    let style = numbering.unwrap_or_default().for_document(frontmatter.as_ref());
+    number_headings(&mut ast, style);
+    let toc = if properties.toc {
+        extract_toc_from_ast(&ast)
+    } else {
+        Vec::new()
+    };
 
     Ok(DocumentResponse {
+        mtime,
         ast,
         toc,
         frontmatter,
+        properties,
+        frontmatter_error,
+        schema_violations,
     })
 }
 
-/// Get the TOC for a Markdown file.
+/// Get the TOC for a Markdown file, numbered like `get_document`.
 #[tauri::command]
 #[allow(clippy::needless_pass_by_value)]
-pub fn get_toc(path: String) -> Result<Vec<TocEntry>, String> {
-    let path = PathBuf::from(&path);
-    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn get_toc(
+    path: String,
+    numbering: Option<NumberingStyle>,
+    state: State<'_, AppState>,
+) -> Result<Vec<TocEntry>, String> {
+    document_toc(Path::new(&path), numbering, &state)
+}
+
+/// Get the TOC for a Markdown file nested by heading level, limited to the
+/// levels chosen in `options`.
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn get_nested_toc(
+    path: String,
+    options: Option<TocOptions>,
+    numbering: Option<NumberingStyle>,
+    state: State<'_, AppState>,
+) -> Result<Vec<TocNode>, String> {
+    let toc = document_toc(Path::new(&path), numbering, &state)?;
+    Ok(nest_toc(&toc, &options.unwrap_or_default()))
+}
 
-    let (_, body) = strip_frontmatter(&content);
-    let ast = serialize_ast_themed(body, "ocean");
+fn document_toc(
+    path: &Path,
+    numbering: Option<NumberingStyle>,
+    state: &AppState,
+) -> Result<Vec<TocEntry>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let slugs = folder_config(state, path).slug_style();
+
+    let (frontmatter, body) = strip_frontmatter(&content);
+    if frontmatter.as_ref().is_some_and(|fm| !fm.toc()) {
+        return Ok(Vec::new());
+    }
+    let mut ast = serialize_ast_themed(body, "ocean", slugs);
+    let style = numbering.unwrap_or_default().for_document(frontmatter.as_ref());
+    number_headings(&mut ast, style);
     Ok(extract_toc_from_ast(&ast))
 }
 
+/// Write a Markdown TOC of the file's headings between its `<!-- toc -->`
+/// and `<!-- tocstop -->` markers, replacing the previous one. Returns
+/// whether the file changed.
+///
+/// `mtime` is the modification time from `get_document`; the file is not
+/// written if it has changed since.
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn update_toc(
+    path: String,
+    mtime: u64,
+    options: Option<TocOptions>,
+    state: State<'_, AppState>,
+) -> Result<bool, CommandError> {
+    let root = state.root_folder.lock().unwrap().clone();
+    let root = root.ok_or(CommandError::NoFolderOpen)?;
+    let path = Path::new(&path);
+    let slugs = folder_config(&state, path).slug_style();
+    let options = options.unwrap_or_default();
+    Ok(write_markdown_toc(&root, path, mtime, &options, slugs)?)
+}
+
+/// Change frontmatter fields of the file at `path`, leaving the rest of the
+/// file as it is, and return its new modification time.
+///
+/// `mtime` is the modification time from `get_document` or the previous
+/// edit; the file is not written if it has changed since.
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn edit_frontmatter(
+    path: String,
+    mtime: u64,
+    edits: Vec<FieldEdit>,
+    state: State<'_, AppState>,
+) -> Result<u64, CommandError> {
+    let root = state.root_folder.lock().unwrap().clone();
+    let root = root.ok_or(CommandError::NoFolderOpen)?;
+    Ok(write_frontmatter_edits(&root, Path::new(&path), mtime, &edits)?)
+}
+
+/// The folder config for `path`, from its folder up to the open folder.
+fn folder_config(state: &AppState, path: &Path) -> FolderConfig {
+    let root = state.root_folder.lock().unwrap().clone();
+    FolderConfig::for_file(root.as_deref(), path)
+}
+
+/// Payload of the `search-progress` event emitted while a search runs.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchProgress {
+    pub query_id: u64,
+    #[serde(flatten)]
+    pub batch: SearchBatch,
+}
+
 /// Search all markdown files in the open folder for a query string.
+///
+/// Runs on a background thread against the folder's search index, which is
+/// refreshed separately (see `with_index`). Until the index is first built,
+/// the files are searched directly instead. Matches are streamed as
+/// `search-progress` events tagged with `query_id` while files are read, and
+/// the ranked results are returned once the search completes. Starting a
+/// search cancels the previous one, which then fails with a `cancelled`
+/// search error.
+///
+/// `options` defaults to case-insensitive substring search when omitted.
+/// Searches are not added to the history; see `record_search`.
+#[tauri::command]
+pub async fn search(
+    query_id: u64,
+    query: String,
+    options: Option<SearchOptions>,
+    app: AppHandle,
+) -> Result<Vec<SearchResult>, CommandError> {
+    let state = app.state::<AppState>();
+    let root = state
+        .root_folder
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or(CommandError::NoFolderOpen)?;
+    let options = options.unwrap_or_default();
+    let cancel = CancelToken::default();
+    let previous = state
+        .active_search
+        .lock()
+        .unwrap()
+        .replace((query_id, cancel.clone()));
+    if let Some((_, previous)) = previous {
+        previous.cancel();
+    }
+
+    let worker = app.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let mut on_batch = |batch| {
+            let _ = worker.emit("search-progress", SearchProgress { query_id, batch });
+        };
+        // Only picking the files holds the index lock; they are read after.
+        let prepared = with_loaded_index(&worker, &root, |index| {
+            index.prepare_search(&query, &options)
+        });
+        match prepared {
+            Some(prepared) => prepared?.run(50, &cancel, &mut on_batch),
+            None => search_files_streaming(&root, &query, &options, 50, &cancel, &mut on_batch),
+        }
+    })
+    .await;
+
+    {
+        let mut active = state.active_search.lock().unwrap();
+        if active.as_ref().is_some_and(|(id, _)| *id == query_id) {
+            *active = None;
+        }
+    }
+    Ok(result.map_err(|e| CommandError::Internal(e.to_string()))??)
+}
+
+/// Cancel the search started with `query_id`, if it is still running.
 #[tauri::command]
 #[allow(clippy::needless_pass_by_value)]
-pub fn search(query: String, state: State<'_, AppState>) -> Result<Vec<SearchResult>, String> {
-    let root = state.root_folder.lock().unwrap();
-    let root = root.as_ref().ok_or("No folder is open")?;
-    Ok(search_files(root, &query, 50))
+pub fn cancel_search(query_id: u64, state: State<'_, AppState>) {
+    if let Some((id, cancel)) = state.active_search.lock().unwrap().as_ref()
+        && *id == query_id
+    {
+        cancel.cancel();
+    }
+}
+
+/// List the saved searches and recent queries of the open folder.
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn list_searches(app: AppHandle) -> Result<WorkspaceSearches, CommandError> {
+    workspace_searches(&app)
+}
+
+/// Add a query the user submitted to the search history of the open folder.
+///
+/// Called when a search is confirmed, such as by pressing Enter or opening a
+/// result, rather than for each search run while the query is typed.
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn record_search(
+    query: String,
+    options: Option<SearchOptions>,
+    app: AppHandle,
+) -> Result<(), CommandError> {
+    let root = open_root(&app)?;
+    let options = options.unwrap_or_default();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| u64::try_from(since.as_millis()).unwrap_or(u64::MAX));
+    update_searches(&app, &root, |searches| {
+        searches.record(&query, &options, now);
+        Ok(())
+    })
+}
+
+/// Save a search for the open folder under `name`, replacing any saved
+/// search already called that.
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn save_search(
+    name: String,
+    query: String,
+    options: Option<SearchOptions>,
+    app: AppHandle,
+) -> Result<(), CommandError> {
+    let root = open_root(&app)?;
+    let options = options.unwrap_or_default();
+    update_searches(&app, &root, |searches| {
+        searches.save(&name, &query, &options)
+    })
+}
+
+/// Run the saved search `name` of the open folder, as `search` does.
+#[tauri::command]
+pub async fn run_saved_search(
+    query_id: u64,
+    name: String,
+    app: AppHandle,
+) -> Result<Vec<SearchResult>, CommandError> {
+    let saved = workspace_searches(&app)?
+        .get(&name)
+        .cloned()
+        .ok_or(SavedSearchError::NotFound { name })?;
+    search(query_id, saved.query, Some(saved.options), app).await
+}
+
+/// Rename the saved search `name` of the open folder to `new_name`.
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn rename_saved_search(
+    name: String,
+    new_name: String,
+    app: AppHandle,
+) -> Result<(), CommandError> {
+    let root = open_root(&app)?;
+    update_searches(&app, &root, |searches| searches.rename(&name, &new_name))
+}
+
+/// Delete the saved search `name` of the open folder.
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn delete_saved_search(name: String, app: AppHandle) -> Result<(), CommandError> {
+    let root = open_root(&app)?;
+    update_searches(&app, &root, |searches| searches.delete(&name))
+}
+
+/// Preview replacing matches of `query` with `replacement` across the open
+/// folder, returning the changed lines of each file without writing them.
+#[tauri::command]
+pub async fn preview_replace(
+    query: String,
+    replacement: String,
+    options: Option<SearchOptions>,
+    app: AppHandle,
+) -> Result<Vec<FilePreview>, CommandError> {
+    let root = open_root(&app)?;
+    let options = options.unwrap_or_default();
+    run_blocking(move || Ok(preview_replacements(&root, &query, &replacement, &options)?)).await
+}
+
+/// Write the chosen edits from `preview_replace`, refusing if any file is
+/// outside the open folder or changed since the preview. Returns the paths
+/// written.
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn apply_replace(
+    files: Vec<FilePreview>,
+    state: State<'_, AppState>,
+) -> Result<Vec<PathBuf>, CommandError> {
+    let root = state.root_folder.lock().unwrap().clone();
+    let root = root.ok_or(CommandError::NoFolderOpen)?;
+    Ok(apply_replacements(&root, &files)?)
+}
+
+/// Fuzzy-match file paths and frontmatter titles in the open folder, for
+/// quick open.
+///
+/// Candidates are taken from the search index and cached between calls, so
+/// each keystroke only scores them. Pass `refresh` when the finder opens to
+/// rebuild them from the latest index.
+#[tauri::command]
+pub async fn find_files(
+    query: String,
+    limit: Option<usize>,
+    refresh: Option<bool>,
+    app: AppHandle,
+) -> Result<Vec<FileMatch>, CommandError> {
+    run_blocking(move || {
+        let root = open_root(&app)?;
+        let state = app.state::<AppState>();
+        let mut cached = state.file_finder.lock().unwrap();
+        if refresh.unwrap_or(false) || cached.as_ref().is_some_and(|f| f.root() != root) {
+            *cached = None;
+        }
+        let finder = match cached.take() {
+            Some(finder) => finder,
+            None => with_index(&app, FileFinder::from_index)?,
+        };
+        let finder = cached.insert(finder);
+        Ok(finder.find(&query, limit.unwrap_or(50)))
+    })
+    .await
+}
+
+/// Fuzzy-match the headings of every file in the open folder, to go to a
+/// section by name. Each match has the file path and the heading's anchor.
+///
+/// Candidates are cached like those of `find_files`; pass `refresh` when the
+/// picker opens.
+#[tauri::command]
+pub async fn find_headings(
+    query: String,
+    limit: Option<usize>,
+    refresh: Option<bool>,
+    app: AppHandle,
+) -> Result<Vec<HeadingMatch>, CommandError> {
+    run_blocking(move || {
+        let root = open_root(&app)?;
+        let state = app.state::<AppState>();
+        let mut cached = state.heading_finder.lock().unwrap();
+        if refresh.unwrap_or(false) || cached.as_ref().is_some_and(|f| f.root() != root) {
+            *cached = None;
+        }
+        let finder = match cached.take() {
+            Some(finder) => finder,
+            None => with_index(&app, HeadingFinder::from_index)?,
+        };
+        let finder = cached.insert(finder);
+        Ok(finder.find(&query, limit.unwrap_or(50)))
+    })
+    .await
+}
+
+/// List the tags used in the open folder with their number of files, most
+/// used first. Tags come from frontmatter `tags` and `keywords` and inline
+/// `#tags` in prose.
+#[tauri::command]
+pub async fn list_tags(app: AppHandle) -> Result<Vec<TagCount>, CommandError> {
+    run_blocking(move || Ok(tag_index(&app)?.tags())).await
+}
+
+/// List the files of the open folder carrying `tag`, in path order.
+#[tauri::command]
+pub async fn get_tagged_files(
+    tag: String,
+    app: AppHandle,
+) -> Result<Vec<TaggedFile>, CommandError> {
+    run_blocking(move || Ok(tag_index(&app)?.files(&tag).to_vec())).await
+}
+
+/// Tags of the open folder, from the search index.
+fn tag_index(app: &AppHandle) -> Result<TagIndex, CommandError> {
+    with_index(app, TagIndex::from_index)
+}
+
+/// List the links from other files of the open folder to `path`, with the
+/// text of each link and the line around it.
+///
+/// Links are read from the search index, so only files changed since it
+/// was last refreshed are parsed again.
+#[tauri::command]
+pub async fn get_backlinks(path: String, app: AppHandle) -> Result<Vec<Backlink>, CommandError> {
+    run_blocking(move || {
+        let graph = with_index(&app, LinkGraph::from_index)?;
+        Ok(graph.backlinks(Path::new(&path)).to_vec())
+    })
+    .await
+}
+
+/// Check the frontmatter of every document in the open folder against the
+/// schemas set in its `.markright.json` files, listing the files that fail.
+#[tauri::command]
+pub async fn validate_frontmatter_tree(
+    app: AppHandle,
+) -> Result<Vec<FileViolations>, CommandError> {
+    let root = open_root(&app)?;
+    run_blocking(move || Ok(validate_tree(&root))).await
+}
+
+/// Directory holding the persistent search indexes, one file per root folder.
+fn index_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join("search-index"))
+}
+
+/// Run `f` on a blocking thread, so commands that read files or wait for
+/// the search index do not hold up the main thread.
+async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, CommandError> + Send + 'static,
+) -> Result<T, CommandError> {
+    let result = tauri::async_runtime::spawn_blocking(f).await;
+    result.map_err(|e| CommandError::Internal(e.to_string()))?
+}
+
+/// Run `f` on the search index of the open folder, waiting for the index
+/// if it has not been built yet. Only call it off the main thread, through
+/// [`run_blocking`], as the first build reads the whole folder.
+fn with_index<T>(app: &AppHandle, f: impl FnOnce(&SearchIndex) -> T) -> Result<T, CommandError> {
+    let root = open_root(app)?;
+    let state = app.state::<AppState>();
+    let loaded = state
+        .search_index
+        .read()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|index| index.root() == root);
+    if !loaded {
+        refresh_index(app, &root);
+    }
+    with_loaded_index(app, &root, f)
+        .ok_or_else(|| CommandError::Internal("the open folder changed".to_string()))
+}
+
+/// Run `f` on the search index for `root`, or return `None` if it has not
+/// been loaded.
+///
+/// `f` sees the index as last refreshed. If that was more than
+/// [`INDEX_REFRESH_INTERVAL`] ago, a refresh is started in the background
+/// for later calls.
+///
+/// The read lock is held while `f` runs, and a refresh waiting for the write
+/// lock holds back every other reader meanwhile, so `f` must not read files.
+fn with_loaded_index<T>(
+    app: &AppHandle,
+    root: &Path,
+    f: impl FnOnce(&SearchIndex) -> T,
+) -> Option<T> {
+    let state = app.state::<AppState>();
+    let slot = state.search_index.read().unwrap();
+    let index = slot.as_ref().filter(|index| index.root() == root)?;
+    // A refresh holding the lock is already running.
+    let refresh_due = state.index_refreshed.try_lock().is_ok_and(|refreshed| {
+        refreshed.is_none_or(|at| at.elapsed() >= INDEX_REFRESH_INTERVAL)
+    });
+    if refresh_due {
+        refresh_index_in_background(app, root.to_path_buf());
+    }
+    Some(f(index))
+}
+
+/// Run [`refresh_index`] on a background thread.
+fn refresh_index_in_background(app: &AppHandle, root: PathBuf) {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || refresh_index(&app, &root));
+}
+
+/// Bring the search index for `root` up to date, loading it from disk first
+/// if no index for `root` is loaded. Does nothing if it was refreshed less
+/// than [`INDEX_REFRESH_INTERVAL`] ago, or if `root` is no longer open.
+///
+/// Files are read while the index stays available to searches, and only the
+/// changes are merged in under the write lock. Opening another folder
+/// cancels the refresh, keeping the files read so far. The index is only a
+/// cache, so failing to persist it is not an error.
+fn refresh_index(app: &AppHandle, root: &Path) {
+    let state = app.state::<AppState>();
+    let mut refreshed = state.index_refreshed.lock().unwrap();
+    let cancel = state.index_cancel.lock().unwrap().clone();
+    if state.root_folder.lock().unwrap().as_deref() != Some(root) {
+        return;
+    }
+    let loaded = state
+        .search_index
+        .read()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|index| index.root() == root);
+    if loaded && refreshed.is_some_and(|at| at.elapsed() < INDEX_REFRESH_INTERVAL) {
+        return;
+    }
+
+    let index_dir = index_dir(app).ok();
+    if !loaded {
+        let index = match &index_dir {
+            Some(dir) => SearchIndex::load(root, dir),
+            None => SearchIndex::new(root),
+        };
+        *state.search_index.write().unwrap() = Some(index);
+    }
+    let update = match state.search_index.read().unwrap().as_ref() {
+        Some(index) if index.root() == root => index.scan(&cancel),
+        _ => return,
+    };
+    let complete = update.is_complete();
+    let changed = match state.search_index.write().unwrap().as_mut() {
+        Some(index) if index.root() == root => index.apply(update),
+        _ => return,
+    };
+    if complete {
+        *refreshed = Some(Instant::now());
+    }
+    if changed
+        && let Some(dir) = &index_dir
+        && let Some(index) = state.search_index.read().unwrap().as_ref()
+    {
+        let _ = index.save(dir);
+    }
 }
 
 fn config_path(app: &AppHandle) -> Result<PathBuf, String> {
@@ -93,6 +701,44 @@ fn config_path(app: &AppHandle) -> Result<PathBuf, String> {
     Ok(dir.join("settings.json"))
 }
 
+/// Location of the saved searches and search history, next to `settings.json`.
+fn searches_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join("searches.json"))
+}
+
+fn open_root(app: &AppHandle) -> Result<PathBuf, CommandError> {
+    let state = app.state::<AppState>();
+    let root = state.root_folder.lock().unwrap().clone();
+    root.ok_or(CommandError::NoFolderOpen)
+}
+
+/// The saved searches and history of the open folder.
+fn workspace_searches(app: &AppHandle) -> Result<WorkspaceSearches, CommandError> {
+    let root = open_root(app)?;
+    let path = searches_path(app).map_err(CommandError::Internal)?;
+    let state = app.state::<AppState>();
+    let mut slot = state.search_store.lock().unwrap();
+    let store = slot.get_or_insert_with(|| SearchStore::load(&path));
+    Ok(store.workspace(&root))
+}
+
+/// Change the saved searches and history of the folder at `root` and save
+/// them. Nothing is saved if `change` fails.
+fn update_searches<T>(
+    app: &AppHandle,
+    root: &Path,
+    change: impl FnOnce(&mut WorkspaceSearches) -> Result<T, SavedSearchError>,
+) -> Result<T, CommandError> {
+    let path = searches_path(app).map_err(CommandError::Internal)?;
+    let state = app.state::<AppState>();
+    let mut slot = state.search_store.lock().unwrap();
+    let store = slot.get_or_insert_with(|| SearchStore::load(&path));
+    let value = change(store.workspace_mut(root))?;
+    store.save(&path).map_err(CommandError::Internal)?;
+    Ok(value)
+}
+
 /// Load the user's persisted configuration.
 #[tauri::command]
 #[allow(clippy::needless_pass_by_value)]
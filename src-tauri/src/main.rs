@@ -22,7 +22,25 @@ fn main() {
             commands::get_tree,
             commands::get_document,
             commands::get_toc,
+            commands::get_nested_toc,
+            commands::update_toc,
+            commands::edit_frontmatter,
             commands::search,
+            commands::cancel_search,
+            commands::list_searches,
+            commands::record_search,
+            commands::save_search,
+            commands::run_saved_search,
+            commands::rename_saved_search,
+            commands::delete_saved_search,
+            commands::find_files,
+            commands::find_headings,
+            commands::list_tags,
+            commands::get_tagged_files,
+            commands::get_backlinks,
+            commands::validate_frontmatter_tree,
+            commands::preview_replace,
+            commands::apply_replace,
             commands::get_config,
             commands::save_config,
             commands::check_license,
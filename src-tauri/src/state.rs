@@ -1,9 +1,32 @@
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::{Mutex, RwLock};
+use std::time::Instant;
+
+use markright_core::fuzzy::{FileFinder, HeadingFinder};
+use markright_core::index::SearchIndex;
+use markright_core::saved_search::SearchStore;
+use markright_core::search::CancelToken;
 
 /// Shared application state managed by Tauri.
 pub struct AppState {
     pub root_folder: Mutex<Option<PathBuf>>,
+    /// Persistent search index for the open folder, built in the background
+    /// when the folder is opened.
+    pub search_index: RwLock<Option<SearchIndex>>,
+    /// When the search index was last brought up to date. Held while it is
+    /// being refreshed, so only one refresh runs at a time.
+    pub index_refreshed: Mutex<Option<Instant>>,
+    /// Cancels the running refresh of the search index when another folder
+    /// is opened.
+    pub index_cancel: Mutex<CancelToken>,
+    /// Quick-open candidates, built from the search index on first use.
+    pub file_finder: Mutex<Option<FileFinder>>,
+    /// Go-to-heading candidates, built from the search index on first use.
+    pub heading_finder: Mutex<Option<HeadingFinder>>,
+    /// Id and cancel token of the most recently started search.
+    pub active_search: Mutex<Option<(u64, CancelToken)>>,
+    /// Saved searches and search history of every workspace, loaded on first use.
+    pub search_store: Mutex<Option<SearchStore>>,
     /// File path passed as a CLI argument on launch.
     pub initial_file: Mutex<Option<PathBuf>>,
 }
@@ -12,6 +35,13 @@ impl AppState {
     pub fn with_initial_file(file: Option<PathBuf>) -> Self {
         Self {
             root_folder: Mutex::new(None),
+            search_index: RwLock::new(None),
+            index_refreshed: Mutex::new(None),
+            index_cancel: Mutex::new(CancelToken::default()),
+            file_finder: Mutex::new(None),
+            heading_finder: Mutex::new(None),
+            active_search: Mutex::new(None),
+            search_store: Mutex::new(None),
             initial_file: Mutex::new(file),
         }
     }
@@ -1,5 +1,5 @@
 import { Component, Show, onCleanup, onMount, createEffect, createMemo } from "solid-js";
-import { ast, setActiveTocId, findBarOpen, setFindBarOpen, zoom, contentWidth, setIsFullscreen } from "../../stores/app";
+import { ast, documentLang, setActiveTocId, findBarOpen, setFindBarOpen, zoom, contentWidth, setIsFullscreen } from "../../stores/app";
 import { updateConfig } from "../../stores/actions";
 import { getCurrentWindow } from "@tauri-apps/api/window";
 import MdRenderer from "../markdown/MdRenderer";
@@ -88,7 +88,12 @@ const ContentArea: Component = () => {
   return (
     <div class="flex min-w-0 flex-1 flex-col">
       <main ref={contentRef} class="min-w-0 flex-1 overflow-y-auto p-8">
-        <div class={`mx-auto ${widthClass()}`} style={{ zoom: `${zoom()}%` }}>
+        <div
+          class={`mx-auto ${widthClass()}`}
+          classList={{ "hyphens-auto": documentLang() !== null }}
+          lang={documentLang() ?? undefined}
+          style={{ zoom: `${zoom()}%` }}
+        >
           <Show
             when={ast()}
             fallback={
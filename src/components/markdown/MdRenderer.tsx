@@ -10,6 +10,17 @@ const MdRenderer: Component<{ node: MdNode }> = (props) => {
     </For>
   );
 
+  const headingClass = () => props.node.classes?.join(" ");
+
+  const heading = () => (
+    <>
+      <Show when={props.node.number}>
+        <span class="tabular-nums">{props.node.number} </span>
+      </Show>
+      {children()}
+    </>
+  );
+
   return (
     <Switch fallback={children()}>
       <Match when={props.node.type === "Document"}>
@@ -17,22 +28,22 @@ const MdRenderer: Component<{ node: MdNode }> = (props) => {
       </Match>
 
       <Match when={props.node.type === "Heading" && props.node.level === 1}>
-        <h1 id={props.node.id}>{children()}</h1>
+        <h1 id={props.node.id} class={headingClass()}>{heading()}</h1>
       </Match>
       <Match when={props.node.type === "Heading" && props.node.level === 2}>
-        <h2 id={props.node.id}>{children()}</h2>
+        <h2 id={props.node.id} class={headingClass()}>{heading()}</h2>
       </Match>
       <Match when={props.node.type === "Heading" && props.node.level === 3}>
-        <h3 id={props.node.id}>{children()}</h3>
+        <h3 id={props.node.id} class={headingClass()}>{heading()}</h3>
       </Match>
       <Match when={props.node.type === "Heading" && props.node.level === 4}>
-        <h4 id={props.node.id}>{children()}</h4>
+        <h4 id={props.node.id} class={headingClass()}>{heading()}</h4>
       </Match>
       <Match when={props.node.type === "Heading" && props.node.level === 5}>
-        <h5 id={props.node.id}>{children()}</h5>
+        <h5 id={props.node.id} class={headingClass()}>{heading()}</h5>
       </Match>
       <Match when={props.node.type === "Heading" && props.node.level === 6}>
-        <h6 id={props.node.id}>{children()}</h6>
+        <h6 id={props.node.id} class={headingClass()}>{heading()}</h6>
       </Match>
 
       <Match when={props.node.type === "Paragraph"}>
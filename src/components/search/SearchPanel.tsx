@@ -1,24 +1,64 @@
 import { Component, For, Show } from "solid-js";
-import { searchQuery, searchResults } from "../../stores/app";
-import { performSearch, openDocument, toggleSearchMode } from "../../stores/actions";
-import type { SearchMatch } from "../../lib/types";
+import { searchOptions, searchProgress, searchQuery, searchResults } from "../../stores/app";
+import {
+  performSearch,
+  openDocument,
+  submitSearch,
+  toggleSearchMode,
+  toggleSearchOption,
+} from "../../stores/actions";
+import type { BlockKind, SearchMatch } from "../../lib/types";
 
 const HighlightedLine: Component<{ match: SearchMatch }> = (props) => {
-  const before = () => props.match.line_text.slice(0, props.match.col_start);
-  const matched = () => props.match.line_text.slice(props.match.col_start, props.match.col_end);
-  const after = () => props.match.line_text.slice(props.match.col_end);
+  // Split the snippet into alternating plain/matched segments using UTF-16 offsets.
+  const segments = () => {
+    const text = props.match.snippet;
+    const parts: { text: string; matched: boolean }[] = [];
+    let pos = 0;
+    for (const range of props.match.snippet_ranges) {
+      parts.push({ text: text.slice(pos, range.start_utf16), matched: false });
+      parts.push({ text: text.slice(range.start_utf16, range.end_utf16), matched: true });
+      pos = range.end_utf16;
+    }
+    parts.push({ text: text.slice(pos), matched: false });
+    return parts;
+  };
 
   return (
     <span class="text-xs text-gray-600 dark:text-gray-400">
-      {before()}
-      <mark class="bg-yellow-200 text-yellow-900 dark:bg-yellow-800 dark:text-yellow-100">
-        {matched()}
-      </mark>
-      {after()}
+      <For each={segments()}>
+        {(part) => (
+          <Show when={part.matched} fallback={part.text}>
+            <mark class="bg-yellow-200 text-yellow-900 dark:bg-yellow-800 dark:text-yellow-100">
+              {part.text}
+            </mark>
+          </Show>
+        )}
+      </For>
     </span>
   );
 };
 
+/** Short label for matches outside prose, e.g. `rust` for a Rust code block. */
+function blockLabel(block: BlockKind): string | null {
+  switch (block.type) {
+    case "prose":
+    case "heading":
+      return null;
+    case "code":
+      return block.language ?? "code";
+    default:
+      return block.type;
+  }
+}
+
+/** Search modes shown as toggle buttons under the search box. */
+const SEARCH_MODES = [
+  { option: "case_sensitive", label: "Aa", title: "Match case" },
+  { option: "whole_word", label: "ab", title: "Match whole word" },
+  { option: "regex", label: ".*", title: "Use regular expression" },
+] as const;
+
 const SearchPanel: Component = () => {
   let inputRef: HTMLInputElement | undefined;
 
@@ -27,13 +67,25 @@ const SearchPanel: Component = () => {
     performSearch(target.value);
   };
 
+  const handleKeyDown = (e: KeyboardEvent) => {
+    if (e.key === "Enter") submitSearch();
+  };
+
   const handleClear = () => {
     performSearch("");
     inputRef?.focus();
   };
 
-  const handleResultClick = (path: string) => {
-    openDocument(path);
+  const handleResultClick = (path: string, anchor?: string | null) => {
+    submitSearch();
+    openDocument(path).then(() => {
+      if (anchor) {
+        // Small delay to let the DOM render
+        setTimeout(() => {
+          document.getElementById(anchor)?.scrollIntoView({ behavior: "smooth" });
+        }, 50);
+      }
+    });
     toggleSearchMode();
   };
 
@@ -59,6 +111,7 @@ const SearchPanel: Component = () => {
           placeholder="Search files..."
           value={searchQuery()}
           onInput={handleInput}
+          onKeyDown={handleKeyDown}
           class="w-full rounded border border-gray-300 bg-white py-1.5 pl-8 pr-8 text-sm text-gray-900 placeholder-gray-400 focus:border-blue-500 focus:outline-none dark:border-gray-700 dark:bg-gray-900 dark:text-gray-100 dark:placeholder-gray-500 dark:focus:border-blue-400"
           autofocus
         />
@@ -79,6 +132,35 @@ const SearchPanel: Component = () => {
         </Show>
       </div>
 
+      <div class="flex gap-1 px-2 pb-2">
+        <For each={SEARCH_MODES}>
+          {(mode) => (
+            <button
+              class="rounded border px-1.5 py-0.5 font-mono text-xs"
+              classList={{
+                "border-blue-500 bg-blue-50 text-blue-700 dark:border-blue-400 dark:bg-blue-900/40 dark:text-blue-300":
+                  !!searchOptions()[mode.option],
+                "border-gray-300 text-gray-500 hover:text-gray-700 dark:border-gray-700 dark:text-gray-400 dark:hover:text-gray-300":
+                  !searchOptions()[mode.option],
+              }}
+              title={mode.title}
+              aria-pressed={!!searchOptions()[mode.option]}
+              onClick={() => toggleSearchOption(mode.option)}
+            >
+              {mode.label}
+            </button>
+          )}
+        </For>
+      </div>
+
+      <Show when={searchProgress()}>
+        {(progress) => (
+          <p class="px-2 pb-2 text-xs tabular-nums text-gray-500 dark:text-gray-400">
+            Searching… {progress().scanned} / {progress().total} files
+          </p>
+        )}
+      </Show>
+
       <div class="flex-1 overflow-y-auto px-2">
         <Show
           when={searchQuery().trim()}
@@ -111,15 +193,29 @@ const SearchPanel: Component = () => {
                         {(match) => (
                           <li>
                             <button
-                              class="flex w-full items-start gap-2 rounded px-1 py-0.5 text-left hover:bg-gray-100 dark:hover:bg-gray-800"
-                              onClick={() => handleResultClick(result.path)}
+                              class="w-full rounded px-1 py-0.5 text-left hover:bg-gray-100 dark:hover:bg-gray-800"
+                              onClick={() => handleResultClick(result.path, match.anchor)}
                             >
-                              <span class="shrink-0 text-xs tabular-nums text-gray-500 dark:text-gray-400">
-                                {match.line_number}
-                              </span>
-                              <span class="min-w-0 truncate">
-                                <HighlightedLine match={match} />
-                              </span>
+                              <Show when={match.heading_path.length > 0}>
+                                <div class="truncate text-[11px] text-gray-400 dark:text-gray-500">
+                                  {match.heading_path.join(" > ")}
+                                </div>
+                              </Show>
+                              <div class="flex items-start gap-2">
+                                <span class="shrink-0 text-xs tabular-nums text-gray-500 dark:text-gray-400">
+                                  {match.line_number}
+                                </span>
+                                <span class="min-w-0 truncate">
+                                  <HighlightedLine match={match} />
+                                </span>
+                                <Show when={blockLabel(match.block)}>
+                                  {(label) => (
+                                    <span class="ml-auto shrink-0 rounded bg-gray-100 px-1 text-[10px] text-gray-500 dark:bg-gray-800 dark:text-gray-400">
+                                      {label()}
+                                    </span>
+                                  )}
+                                </Show>
+                              </div>
                             </button>
                           </li>
                         )}
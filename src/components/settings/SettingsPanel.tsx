@@ -17,6 +17,7 @@ import {
   contentWidth,
   licenseStatus,
   codeTheme,
+  headingNumbering,
   isLicensed,
 } from "../../stores/app";
 import { updateConfig, activateLicense, promptUpgrade } from "../../stores/actions";
@@ -104,6 +105,26 @@ const SettingsPanel: Component = () => {
               })}
             </div>
           </Row>
+          <Row label="Heading Numbers">
+            <div class="flex gap-3">
+              {([
+                ["none", "None"],
+                ["decimal", "1.2.3"],
+                ["outline", "1.a.i"],
+              ] as const).map(([value, label]) => (
+                <label class="flex cursor-pointer items-center gap-1.5 text-sm">
+                  <input
+                    type="radio"
+                    name="heading-numbering"
+                    checked={headingNumbering() === value}
+                    onChange={() => updateConfig({ heading_numbering: value })}
+                    class="accent-blue-500"
+                  />
+                  {label}
+                </label>
+              ))}
+            </div>
+          </Row>
         </Section>
 
         {/* Content Font */}
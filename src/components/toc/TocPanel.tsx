@@ -31,6 +31,9 @@ const TocPanel: Component = () => {
                   style={{ "padding-left": `${(entry.level - 1) * 12 + 8}px` }}
                   onClick={() => handleClick(entry.id)}
                 >
+                  <Show when={entry.number}>
+                    <span class="mr-1 tabular-nums text-gray-400">{entry.number}</span>
+                  </Show>
                   {entry.text}
                 </button>
               </li>
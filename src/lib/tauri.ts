@@ -1,5 +1,6 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { AppConfig, DocumentResponse, LicenseStatus, TreeNode, TocEntry, SearchResult } from "./types";
+import { listen } from "@tauri-apps/api/event";
+import type { AppConfig, Backlink, DocumentResponse, FieldEdit, FileMatch, FileViolations, FilePreview, HeadingMatch, LicenseStatus, TreeNode, NumberingStyle, TocEntry, TocNode, TocOptions, SearchOptions, SearchProgress, SearchResult, TagCount, TaggedFile, WorkspaceSearches } from "./types";
 
 export async function openFolder(path: string): Promise<TreeNode[]> {
   return invoke<TreeNode[]>("open_folder", { path });
@@ -9,16 +10,122 @@ export async function getTree(): Promise<TreeNode[]> {
   return invoke<TreeNode[]>("get_tree");
 }
 
-export async function getDocument(path: string, codeTheme?: string): Promise<DocumentResponse> {
-  return invoke<DocumentResponse>("get_document", { path, codeTheme });
+export async function getDocument(path: string, codeTheme?: string, numbering?: NumberingStyle): Promise<DocumentResponse> {
+  return invoke<DocumentResponse>("get_document", { path, codeTheme, numbering });
 }
 
-export async function getToc(path: string): Promise<TocEntry[]> {
-  return invoke<TocEntry[]>("get_toc", { path });
+export async function getToc(path: string, numbering?: NumberingStyle): Promise<TocEntry[]> {
+  return invoke<TocEntry[]>("get_toc", { path, numbering });
 }
 
-export async function search(query: string): Promise<SearchResult[]> {
-  return invoke<SearchResult[]>("search", { query });
+export async function getNestedToc(path: string, options?: Partial<TocOptions>, numbering?: NumberingStyle): Promise<TocNode[]> {
+  return invoke<TocNode[]>("get_nested_toc", { path, options, numbering });
+}
+
+/** Rewrite the file's `<!-- toc -->` section, refusing if it changed since `mtime`; returns whether it changed. */
+export async function updateToc(path: string, mtime: number, options?: Partial<TocOptions>): Promise<boolean> {
+  return invoke<boolean>("update_toc", { path, mtime, options });
+}
+
+/** Change frontmatter fields of `path`, refusing if it changed since `mtime`; resolves to the new mtime. */
+export async function editFrontmatter(path: string, mtime: number, edits: FieldEdit[]): Promise<number> {
+  return invoke<number>("edit_frontmatter", { path, mtime, edits });
+}
+
+/** Run a search, passing streamed batches to `onProgress`; resolves to the ranked results. */
+export async function search(
+  queryId: number,
+  query: string,
+  options: SearchOptions | undefined,
+  onProgress: (progress: SearchProgress) => void,
+): Promise<SearchResult[]> {
+  const unlisten = await listen<SearchProgress>("search-progress", (event) => {
+    if (event.payload.query_id === queryId) onProgress(event.payload);
+  });
+  try {
+    return await invoke<SearchResult[]>("search", { queryId, query, options });
+  } finally {
+    unlisten();
+  }
+}
+
+export async function cancelSearch(queryId: number): Promise<void> {
+  return invoke<void>("cancel_search", { queryId });
+}
+
+export async function listSearches(): Promise<WorkspaceSearches> {
+  return invoke<WorkspaceSearches>("list_searches");
+}
+
+/** Add a submitted query to the open folder's search history. */
+export async function recordSearch(query: string, options?: SearchOptions): Promise<void> {
+  return invoke<void>("record_search", { query, options });
+}
+
+export async function saveSearch(name: string, query: string, options?: SearchOptions): Promise<void> {
+  return invoke<void>("save_search", { name, query, options });
+}
+
+/** Run a saved search like `search`, streaming batches to `onProgress`. */
+export async function runSavedSearch(
+  queryId: number,
+  name: string,
+  onProgress: (progress: SearchProgress) => void,
+): Promise<SearchResult[]> {
+  const unlisten = await listen<SearchProgress>("search-progress", (event) => {
+    if (event.payload.query_id === queryId) onProgress(event.payload);
+  });
+  try {
+    return await invoke<SearchResult[]>("run_saved_search", { queryId, name });
+  } finally {
+    unlisten();
+  }
+}
+
+export async function renameSavedSearch(name: string, newName: string): Promise<void> {
+  return invoke<void>("rename_saved_search", { name, newName });
+}
+
+export async function deleteSavedSearch(name: string): Promise<void> {
+  return invoke<void>("delete_saved_search", { name });
+}
+
+/** Fuzzy-find files by path or title; pass `refresh` when the finder opens. */
+export async function findFiles(query: string, limit?: number, refresh?: boolean): Promise<FileMatch[]> {
+  return invoke<FileMatch[]>("find_files", { query, limit, refresh });
+}
+
+/** Fuzzy-find headings across the open folder; pass `refresh` when the picker opens. */
+export async function findHeadings(query: string, limit?: number, refresh?: boolean): Promise<HeadingMatch[]> {
+  return invoke<HeadingMatch[]>("find_headings", { query, limit, refresh });
+}
+
+/** Tags of the open folder with their file counts, most used first. */
+export async function listTags(): Promise<TagCount[]> {
+  return invoke<TagCount[]>("list_tags");
+}
+
+export async function getTaggedFiles(tag: string): Promise<TaggedFile[]> {
+  return invoke<TaggedFile[]>("get_tagged_files", { tag });
+}
+
+/** Links from other files of the open folder to `path`. */
+export async function getBacklinks(path: string): Promise<Backlink[]> {
+  return invoke<Backlink[]>("get_backlinks", { path });
+}
+
+/** Files of the open folder whose frontmatter fails their folder's schema. */
+export async function validateFrontmatterTree(): Promise<FileViolations[]> {
+  return invoke<FileViolations[]>("validate_frontmatter_tree");
+}
+
+export async function previewReplace(query: string, replacement: string, options?: SearchOptions): Promise<FilePreview[]> {
+  return invoke<FilePreview[]>("preview_replace", { query, replacement, options });
+}
+
+/** Apply the chosen edits from `previewReplace`; returns the paths written. */
+export async function applyReplace(files: FilePreview[]): Promise<string[]> {
+  return invoke<string[]>("apply_replace", { files });
 }
 
 export async function getConfig(): Promise<AppConfig> {
@@ -4,6 +4,7 @@ export interface MdNode {
   children: MdNode[];
   level?: number;
   id?: string;
+  number?: string;
   literal?: string;
   url?: string;
   title?: string;
@@ -16,6 +17,7 @@ export interface MdNode {
   checked?: boolean;
   header?: boolean;
   alignments?: string[];
+  classes?: string[];
 }
 
 /** File/directory entry in the navigation tree. */
@@ -31,19 +33,98 @@ export interface TocEntry {
   level: number;
   text: string;
   id: string;
+  /** Section number such as "3.2.1", when numbering is on. */
+  number?: string;
 }
 
-/** Frontmatter parsed from YAML header. */
+/** How headings are numbered. */
+export type NumberingStyle = "none" | "decimal" | "outline";
+
+/** TOC entry with the entries of its subsections. */
+export interface TocNode extends TocEntry {
+  children: TocNode[];
+}
+
+/** Which headings a nested TOC includes. */
+export interface TocOptions {
+  min_level: number;
+  max_level: number;
+  /** Leave out the first heading if it is an H1. */
+  exclude_title: boolean;
+}
+
+/** A frontmatter value, as parsed from YAML, TOML or JSON. */
+export type FrontmatterValue =
+  | string
+  | number
+  | boolean
+  | null
+  | FrontmatterValue[]
+  | { [key: string]: FrontmatterValue };
+
+/** Language of a frontmatter block: `---` YAML, `+++` TOML or `;;;` JSON. */
+export type FrontmatterFormat = "yaml" | "toml" | "json";
+
+/** Frontmatter parsed from the document header. */
 export interface Frontmatter {
   title: string | null;
-  fields: Record<string, string>;
+  fields: Record<string, FrontmatterValue>;
+  format: FrontmatterFormat;
+}
+
+/** A frontmatter date; `time` is `[hour, minute, second]` if given. */
+export interface DocumentDate {
+  year: number;
+  month: number;
+  day: number;
+  time: [number, number, number] | null;
+  /** Offset from UTC in minutes. */
+  offset_minutes: number | null;
+}
+
+/** The frontmatter fields with a defined meaning. */
+export interface DocumentProperties {
+  title: string | null;
+  description: string | null;
+  authors: string[];
+  date: DocumentDate | null;
+  tags: string[];
+  draft: boolean;
+  /** Language for hyphenation and spellcheck, such as `de-CH`. */
+  lang: string | null;
+  toc: boolean;
+}
+
+/** A way in which frontmatter fails its folder's schema. */
+export interface SchemaViolation {
+  /** Offending field, such as `owner` or `links[1].url`; empty for the whole block. */
+  field: string;
+  message: string;
+}
+
+/** The schema violations of one file. */
+export interface FileViolations {
+  path: string;
+  rel_path: string;
+  violations: SchemaViolation[];
+}
+
+/** Why a frontmatter block could not be parsed. */
+export interface FrontmatterError {
+  line: number;
+  message: string;
 }
 
 /** Response from the get_document command. */
 export interface DocumentResponse {
+  /** File modification time as read, to pass to `editFrontmatter` and `updateToc`. */
+  mtime: number | null;
   ast: MdNode;
   toc: TocEntry[];
   frontmatter: Frontmatter | null;
+  properties: DocumentProperties;
+  frontmatter_error: FrontmatterError | null;
+  schema_violations: SchemaViolation[];
 }
 
 /** A file containing search matches. */
@@ -51,14 +132,207 @@ export interface SearchResult {
   path: string;
   name: string;
   matches: SearchMatch[];
+  /** BM25 relevance score; results arrive sorted by it, highest first. */
+  score: number;
+}
+
+/** Matching modes for cross-file search. `all_matches` defaults to true, the rest to false. */
+export interface SearchOptions {
+  regex?: boolean;
+  whole_word?: boolean;
+  case_sensitive?: boolean;
+  all_matches?: boolean;
+  /** Parse the query as `"phrase" a OR b -c path: title: tag: heading: code: field:value`. */
+  structured?: boolean;
+  /** Lines of context to include before and after each matching line. */
+  context_lines?: number;
+  /** Only match lines in these kinds of block; all kinds if empty. */
+  blocks?: BlockKind[];
+}
+
+/** Kind of Markdown block a line belongs to. As a filter, code without a language matches any code block. */
+export type BlockKind =
+  | { type: "prose" }
+  | { type: "heading" }
+  | { type: "code"; language?: string | null }
+  | { type: "table" }
+  | { type: "frontmatter" };
+
+/** A named search saved for the open folder. */
+export interface SavedSearch {
+  name: string;
+  query: string;
+  options: SearchOptions;
 }
 
-/** A single line matching the search query. */
+/** A query from the open folder's search history. */
+export interface RecentSearch {
+  query: string;
+  options: SearchOptions;
+  /** Milliseconds since the Unix epoch. */
+  searched_at: number;
+}
+
+/** Saved searches and recent queries (most recent first) of the open folder. */
+export interface WorkspaceSearches {
+  saved: SavedSearch[];
+  history: RecentSearch[];
+}
+
+export type SavedSearchError =
+  | { kind: "not_found"; name: string }
+  | { kind: "name_taken"; name: string }
+  | { kind: "empty_name" };
+
+/** A heading found by `findHeadings`, with its file and anchor id. */
+export interface HeadingMatch {
+  path: string;
+  rel_path: string;
+  level: number;
+  text: string;
+  anchor: string;
+  score: number;
+  /** Matched spans of `text`. */
+  ranges: MatchRange[];
+}
+
+/** A tag used in the open folder and the number of files carrying it. */
+export interface TagCount {
+  tag: string;
+  count: number;
+}
+
+/** A file carrying a tag. */
+export interface TaggedFile {
+  path: string;
+  rel_path: string;
+  title: string | null;
+}
+
+/** A link to a file, from the file that contains it. */
+export interface Backlink {
+  path: string;
+  rel_path: string;
+  text: string;
+  line: number;
+  /** Plain text of the line holding the link. */
+  context: string;
+  anchor: string | null;
+  /** Heading the anchor points to, or null if it matches none. */
+  heading: string | null;
+}
+
+/** Structured error returned by commands such as `search`. */
+export type CommandError =
+  | { kind: "no_folder_open" }
+  | { kind: "search"; detail: SearchError }
+  | { kind: "replace"; detail: ReplaceError }
+  | { kind: "saved_search"; detail: SavedSearchError }
+  | { kind: "toc"; detail: TocError }
+  | { kind: "frontmatter"; detail: FrontmatterEditError }
+  | { kind: "internal"; detail: string };
+
+/** Reason a Markdown TOC could not be written into a file. */
+export type TocError =
+  | { kind: "no_marker" }
+  | { kind: "outside_root"; path: string }
+  | { kind: "modified"; path: string }
+  | { kind: "io"; path: string; message: string };
+
+/** A change to one top-level frontmatter field. */
+export type FieldEdit =
+  | { op: "set"; key: string; value: FrontmatterValue }
+  | { op: "remove"; key: string }
+  | { op: "append"; key: string; value: FrontmatterValue };
+
+/** Reason frontmatter fields could not be changed. */
+export type FrontmatterEditError =
+  | { kind: "parse"; line: number; message: string }
+  | { kind: "unsupported_format"; format: FrontmatterFormat }
+  | { kind: "invalid_key"; key: string }
+  | { kind: "not_a_list"; key: string }
+  | { kind: "outside_root"; path: string }
+  | { kind: "modified"; path: string }
+  | { kind: "io"; path: string; message: string };
+
+/** Reason replacements could not be previewed or applied. */
+export type ReplaceError =
+  | { kind: "search"; detail: SearchError }
+  | { kind: "modified"; path: string }
+  | { kind: "outside_root"; path: string }
+  | { kind: "io"; path: string; message: string }
+  /** Writing `path` failed after the files in `written` were replaced. */
+  | { kind: "incomplete"; path: string; message: string; written: string[] };
+
+/** Planned replacements in one file, from `preview_replace`. */
+export interface FilePreview {
+  path: string;
+  /** Modification time at preview, in milliseconds; the file is not written if it changed. */
+  mtime: number;
+  edits: LineEdit[];
+}
+
+/** One changed line of a replace preview. */
+export interface LineEdit {
+  line_number: number;
+  before: string;
+  after: string;
+  before_ranges: MatchRange[];
+  after_ranges: MatchRange[];
+}
+
+/** Reason a search query could not be executed. */
+export type SearchError =
+  | { kind: "invalid_regex"; pattern: string; message: string }
+  | { kind: "invalid_query"; message: string; position: number }
+  | { kind: "cancelled" };
+
+/** A file matched by the quick-open fuzzy finder. */
+export interface FileMatch {
+  path: string;
+  rel_path: string;
+  title: string | null;
+  score: number;
+  /** Matched spans of `rel_path`; empty if the title matched better. */
+  path_ranges: MatchRange[];
+  /** Matched spans of `title`; empty if the path matched better. */
+  title_ranges: MatchRange[];
+}
+
+/** Payload of the `search-progress` event: matches from one batch of files. */
+export interface SearchProgress {
+  query_id: number;
+  /** Matching files of this batch, unranked. */
+  results: SearchResult[];
+  scanned: number;
+  total: number;
+}
+
+/** A line containing one or more matches of the search query. */
 export interface SearchMatch {
   line_number: number;
   line_text: string;
-  col_start: number;
-  col_end: number;
+  ranges: MatchRange[];
+  /** The line as plain text rendered from its block, without Markdown or HTML syntax. */
+  snippet: string;
+  /** Matched spans within `snippet`. */
+  snippet_ranges: MatchRange[];
+  /** Kind of block the line belongs to. */
+  block: BlockKind;
+  context_before: string[];
+  context_after: string[];
+  /** Enclosing heading texts, outermost first. */
+  heading_path: string[];
+  /** Id of the innermost enclosing heading in the rendered document. */
+  anchor: string | null;
+}
+
+/** Match span within a line, as Rust byte offsets and JS (UTF-16) offsets. */
+export interface MatchRange {
+  start: number;
+  end: number;
+  start_utf16: number;
+  end_utf16: number;
 }
 
 /** License verification status from Rust backend. */
@@ -83,4 +357,5 @@ export interface AppConfig {
   zoom: number;
   content_width: "default" | "fit" | "a4";
   code_theme: string;
+  heading_numbering: NumberingStyle;
 }
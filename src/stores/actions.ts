@@ -1,5 +1,5 @@
-import { openFolder as ipcOpenFolder, getDocument, search as ipcSearch, getConfig, saveConfig, checkLicense, activateLicense as ipcActivateLicense, getInitialFile } from "../lib/tauri";
-import type { AppConfig } from "../lib/types";
+import { openFolder as ipcOpenFolder, getDocument, search as ipcSearch, cancelSearch, recordSearch, saveSearch, getConfig, saveConfig, checkLicense, activateLicense as ipcActivateLicense, getInitialFile } from "../lib/tauri";
+import type { AppConfig, SearchResult } from "../lib/types";
 import {
   setTree,
   setRootFolder,
@@ -7,11 +7,16 @@ import {
   setAst,
   setToc,
   setFrontmatter,
+  setDocumentLang,
   setActiveTocId,
   setSearchQuery,
   setSearchResults,
+  setSearchProgress,
   setSearchMode,
   searchMode,
+  searchQuery,
+  searchOptions,
+  setSearchOptions,
   currentPath,
   setLicenseStatus,
   setShowUpgradePrompt,
@@ -29,10 +34,12 @@ import {
   setZoom,
   setContentWidth,
   setCodeTheme,
+  setHeadingNumbering,
   setDarkActive,
   zoom,
   contentWidth,
   codeTheme,
+  headingNumbering,
   leftPanelWidth,
   rightPanelWidth,
   showLeftPanel,
@@ -54,19 +61,33 @@ export async function openFolder(path: string): Promise<void> {
   setAst(null);
   setToc([]);
   setFrontmatter(null);
+  setDocumentLang(null);
   setActiveTocId(null);
 }
 
 export async function openDocument(path: string): Promise<void> {
-  const doc = await getDocument(path, codeTheme());
+  const doc = await getDocument(path, codeTheme(), headingNumbering());
   setCurrentPath(path);
   setAst(doc.ast);
   setToc(doc.toc);
   setFrontmatter(doc.frontmatter);
+  setDocumentLang(doc.properties.lang);
   setActiveTocId(null);
 }
 
 let searchTimer: ReturnType<typeof setTimeout> | null = null;
+let searchId = 0;
+let searchRunning = false;
+
+/** Cancel the running search, if any, and ignore its remaining output. */
+function cancelRunningSearch(): void {
+  if (searchRunning) {
+    void cancelSearch(searchId);
+    searchRunning = false;
+  }
+  searchId++;
+  setSearchProgress(null);
+}
 
 export function performSearch(query: string): void {
   setSearchQuery(query);
@@ -74,6 +95,7 @@ export function performSearch(query: string): void {
   if (searchTimer) {
     clearTimeout(searchTimer);
   }
+  cancelRunningSearch();
 
   if (!query.trim()) {
     setSearchResults([]);
@@ -81,18 +103,50 @@ export function performSearch(query: string): void {
   }
 
   searchTimer = setTimeout(async () => {
+    const id = ++searchId;
+    let streamed: SearchResult[] = [];
+    searchRunning = true;
     try {
-      const results = await ipcSearch(query);
-      setSearchResults(results);
+      const results = await ipcSearch(id, query, searchOptions(), (progress) => {
+        if (id !== searchId) return;
+        // Show matches as they arrive; the final list replaces them ranked.
+        streamed = streamed.concat(progress.results);
+        setSearchResults(streamed);
+        setSearchProgress({ scanned: progress.scanned, total: progress.total });
+      });
+      if (id === searchId) setSearchResults(results);
     } catch {
-      setSearchResults([]);
+      if (id === searchId) setSearchResults([]);
+    } finally {
+      if (id === searchId) {
+        searchRunning = false;
+        setSearchProgress(null);
+      }
     }
   }, 250);
 }
 
+/** Turn a search mode on or off and rerun the current query with it. */
+export function toggleSearchOption(option: "regex" | "whole_word" | "case_sensitive"): void {
+  setSearchOptions((prev) => ({ ...prev, [option]: !prev[option] }));
+  performSearch(searchQuery());
+}
+
+/** Add the current query to the search history once the user settles on it. */
+export function submitSearch(): void {
+  const query = searchQuery().trim();
+  if (query) void recordSearch(query, searchOptions()).catch(() => {});
+}
+
+/** Save the current query and its modes under `name`. */
+export async function saveCurrentSearch(name: string): Promise<void> {
+  await saveSearch(name, searchQuery().trim(), searchOptions());
+}
+
 export function toggleSearchMode(): void {
   setSearchMode((prev) => !prev);
   if (!searchMode()) {
+    cancelRunningSearch();
     setSearchQuery("");
     setSearchResults([]);
   }
@@ -159,6 +213,7 @@ export async function loadConfig(): Promise<void> {
     setZoom(cfg.zoom);
     setContentWidth(cfg.content_width);
     if (cfg.code_theme) setCodeTheme(cfg.code_theme);
+    if (cfg.heading_numbering) setHeadingNumbering(cfg.heading_numbering);
     applyCssVars();
   } catch {
     // Use defaults — CSS vars already set in global.css
@@ -180,6 +235,7 @@ function currentConfig(): AppConfig {
     zoom: zoom(),
     content_width: contentWidth(),
     code_theme: codeTheme(),
+    heading_numbering: headingNumbering(),
   };
 }
 
@@ -250,6 +306,11 @@ export function updateConfig(partial: Partial<AppConfig>): void {
     const path = currentPath();
     if (path) openDocument(path);
   }
+  if (partial.heading_numbering !== undefined) {
+    setHeadingNumbering(partial.heading_numbering);
+    const path = currentPath();
+    if (path) openDocument(path);
+  }
   applyCssVars();
   persistConfig();
 }
@@ -6,6 +6,8 @@ import type {
   Frontmatter,
   SearchResult,
   LicenseStatus,
+  NumberingStyle,
+  SearchOptions,
 } from "../lib/types";
 
 export const [tree, setTree] = createSignal<TreeNode[]>([]);
@@ -14,12 +16,18 @@ export const [currentPath, setCurrentPath] = createSignal<string | null>(null);
 export const [ast, setAst] = createSignal<MdNode | null>(null);
 export const [toc, setToc] = createSignal<TocEntry[]>([]);
 export const [frontmatter, setFrontmatter] = createSignal<Frontmatter | null>(null);
+/** Language set by the document's frontmatter, for hyphenation and spellcheck. */
+export const [documentLang, setDocumentLang] = createSignal<string | null>(null);
 export const [activeTocId, setActiveTocId] = createSignal<string | null>(null);
 
 // Search
 export const [searchMode, setSearchMode] = createSignal(false);
 export const [searchQuery, setSearchQuery] = createSignal("");
+/** Modes toggled in the search panel: regex, whole word and case sensitive. */
+export const [searchOptions, setSearchOptions] = createSignal<SearchOptions>({});
 export const [searchResults, setSearchResults] = createSignal<SearchResult[]>([]);
+/** Files scanned / total of the running search, or null when none is running. */
+export const [searchProgress, setSearchProgress] = createSignal<{ scanned: number; total: number } | null>(null);
 
 // Panel layout
 export const [leftPanelWidth, setLeftPanelWidth] = createSignal(256);
@@ -37,6 +45,7 @@ export const [theme, setTheme] = createSignal<"light" | "dark" | "system">("syst
 export const [zoom, setZoom] = createSignal(100);
 export const [contentWidth, setContentWidth] = createSignal<"default" | "fit" | "a4">("default");
 export const [codeTheme, setCodeTheme] = createSignal("ocean");
+export const [headingNumbering, setHeadingNumbering] = createSignal<NumberingStyle>("none");
 
 // Dark mode — synchronously updated by applyDarkMode() in actions.ts
 export const [isDark, setDarkActive] = createSignal(